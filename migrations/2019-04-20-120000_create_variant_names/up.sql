@@ -0,0 +1,7 @@
+CREATE TABLE variant_names (
+    id TEXT PRIMARY KEY NOT NULL,
+    toggle_id TEXT NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_variant_names_toggle_id_name ON variant_names (toggle_id, name);
@@ -0,0 +1,8 @@
+CREATE TABLE scheduled_changes (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT NOT NULL,
+    toggle_id TEXT NOT NULL,
+    at BIGINT NOT NULL
+);
+
+CREATE INDEX ix_scheduled_changes_at ON scheduled_changes (at);
@@ -0,0 +1,23 @@
+CREATE TABLE project_names (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_project_names_name ON project_names (name);
+
+-- Names of the projects created so far, as last renamed. Project streams
+-- are the ones created without the id of a project or toggle they belong
+-- to. Names weren't unique before, so the first project keeps a shared one.
+INSERT OR IGNORE INTO project_names (id, name)
+SELECT
+    created.aggregate_id,
+    COALESCE((
+        SELECT json_extract(renamed.data, '$.Renamed.name') FROM events AS renamed
+        WHERE renamed.aggregate_id = created.aggregate_id AND renamed.type = 'Renamed'
+        ORDER BY renamed.generation DESC LIMIT 1
+    ), json_extract(created.data, '$.Created.name'))
+FROM events AS created
+WHERE created.type = 'Created'
+    AND json_extract(created.data, '$.Created.project_id') IS NULL
+    AND json_extract(created.data, '$.Created.toggle_id') IS NULL
+ORDER BY created.rowid;
@@ -0,0 +1,10 @@
+CREATE TABLE project_names (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_project_names_name ON project_names (name);
+
+INSERT INTO project_names (id, name) SELECT id, name FROM projects;
+
+DROP TABLE projects;
@@ -0,0 +1,24 @@
+CREATE TABLE projects (
+    id TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    archived BOOLEAN NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_projects_name ON projects (name);
+CREATE INDEX ix_projects_created_at_id ON projects (created_at, id);
+
+INSERT INTO projects (id, name, archived, created_at)
+SELECT
+    project_names.id,
+    project_names.name,
+    COALESCE((
+        SELECT type = 'Archived' FROM events
+        WHERE aggregate_id = project_names.id AND type IN ('Archived', 'Restored')
+        ORDER BY generation DESC LIMIT 1
+    ), 0),
+    events.created_at
+FROM project_names
+JOIN events ON events.aggregate_id = project_names.id AND events.generation = 0;
+
+DROP TABLE project_names;
@@ -0,0 +1,22 @@
+DROP TABLE audit_log;
+DROP TABLE toggle_environments;
+DROP TABLE toggles;
+DROP TABLE projection_checkpoints;
+DROP INDEX ix_events_aggregate_type;
+
+CREATE TABLE events_backup AS
+SELECT id, aggregate_id, generation, created_at, type, data FROM events;
+DROP TABLE events;
+CREATE TABLE events (
+    id TEXT PRIMARY KEY NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+INSERT INTO events SELECT * FROM events_backup;
+DROP TABLE events_backup;
+
+CREATE INDEX ix_events_aggregate_id ON events (aggregate_id);
+CREATE UNIQUE INDEX uq_aggregate_id_generation_id ON events (aggregate_id, generation);
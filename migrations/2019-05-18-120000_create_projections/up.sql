@@ -0,0 +1,82 @@
+ALTER TABLE events ADD COLUMN aggregate_type TEXT NOT NULL DEFAULT '';
+
+-- Existing streams are typed by what only their kind of aggregate
+-- records. Streams that can't be told apart this way are left untyped
+-- and are skipped by projections. Projects are the only aggregate
+-- created without the id of the project or toggle it belongs to.
+UPDATE events SET aggregate_type = 'project'
+WHERE aggregate_id IN (
+    SELECT aggregate_id FROM events
+    WHERE type = 'Created'
+        AND data NOT LIKE '{"Created":{%"project_id":%'
+        AND data NOT LIKE '{"Created":{%"toggle_id":%'
+);
+
+UPDATE events SET aggregate_type = 'variant'
+WHERE aggregate_id IN (
+    SELECT aggregate_id FROM events
+    WHERE type = 'Created' AND data LIKE '{"Created":{%"toggle_id":%'
+);
+
+UPDATE events SET aggregate_type = 'toggle'
+WHERE aggregate_type = '' AND aggregate_id IN (
+    SELECT aggregate_id FROM events
+    WHERE type IN (
+        'Enabled', 'Disabled', 'RolloutChanged', 'PrerequisitesChanged',
+        'ChangeScheduled', 'ScheduleCancelled', 'ScheduleExecuted', 'Retired', 'Revived'
+    )
+);
+
+UPDATE events SET aggregate_type = 'segment'
+WHERE aggregate_type = '' AND aggregate_id IN (
+    SELECT aggregate_id FROM events WHERE type IN ('IncludedChanged', 'ExcludedChanged')
+);
+
+UPDATE events SET aggregate_type = 'environment'
+WHERE aggregate_type = '' AND aggregate_id IN (
+    SELECT aggregate_id FROM events WHERE type = 'Archived'
+);
+
+CREATE INDEX ix_events_aggregate_type ON events (aggregate_type);
+
+CREATE TABLE projection_checkpoints (
+    name TEXT PRIMARY KEY NOT NULL,
+    position BIGINT NOT NULL
+);
+
+-- Rebuilt by its projection from the first event
+DELETE FROM projects;
+
+CREATE TABLE toggles (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    retired BOOLEAN NOT NULL
+);
+
+CREATE INDEX ix_toggles_project_id ON toggles (project_id);
+
+CREATE TABLE toggle_environments (
+    toggle_id TEXT NOT NULL,
+    environment_id TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    rollout INTEGER,
+    PRIMARY KEY (toggle_id, environment_id)
+);
+
+CREATE INDEX ix_toggle_environments_environment_id ON toggle_environments (environment_id);
+
+CREATE TABLE audit_log (
+    position BIGINT PRIMARY KEY NOT NULL,
+    event_id TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    project_id TEXT,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL
+);
+
+CREATE INDEX ix_audit_log_project_id ON audit_log (project_id, position);
+CREATE INDEX ix_audit_log_aggregate_id ON audit_log (aggregate_id, position);
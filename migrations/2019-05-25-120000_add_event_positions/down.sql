@@ -0,0 +1,21 @@
+CREATE TABLE events_new (
+    id TEXT PRIMARY KEY NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL DEFAULT ''
+);
+
+INSERT INTO events_new
+SELECT id, aggregate_id, generation, created_at, type, data, aggregate_type
+FROM events
+ORDER BY position;
+
+DROP TABLE events;
+ALTER TABLE events_new RENAME TO events;
+
+CREATE INDEX ix_events_aggregate_id ON events (aggregate_id);
+CREATE UNIQUE INDEX uq_aggregate_id_generation_id ON events (aggregate_id, generation);
+CREATE INDEX ix_events_aggregate_type ON events (aggregate_type);
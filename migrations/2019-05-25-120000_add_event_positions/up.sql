@@ -0,0 +1,26 @@
+-- AUTOINCREMENT so a position is never handed out twice,
+-- even after the last event stored has been deleted
+CREATE TABLE events_new (
+    id TEXT NOT NULL UNIQUE,
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL DEFAULT '',
+    position INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL
+);
+
+-- Events keep the rowid they were ordered by so far,
+-- so projection checkpoints stay where they are
+INSERT INTO events_new (id, aggregate_id, generation, created_at, type, data, aggregate_type, position)
+SELECT id, aggregate_id, generation, created_at, type, data, aggregate_type, rowid
+FROM events
+ORDER BY rowid;
+
+DROP TABLE events;
+ALTER TABLE events_new RENAME TO events;
+
+CREATE INDEX ix_events_aggregate_id ON events (aggregate_id);
+CREATE UNIQUE INDEX uq_aggregate_id_generation_id ON events (aggregate_id, generation);
+CREATE INDEX ix_events_aggregate_type ON events (aggregate_type);
@@ -0,0 +1,6 @@
+CREATE TABLE snapshots (
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (aggregate_id, generation)
+);
@@ -0,0 +1,2 @@
+ALTER TABLE events ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE audit_log ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
@@ -0,0 +1,46 @@
+CREATE TABLE events_new (
+    id TEXT NOT NULL UNIQUE,
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL DEFAULT '',
+    position INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}'
+);
+
+INSERT INTO events_new (id, aggregate_id, generation, created_at, type, data, aggregate_type, position, metadata)
+SELECT id, aggregate_id, generation, created_at, type, data, aggregate_type, position, metadata
+FROM events
+ORDER BY position;
+
+DROP TABLE events;
+ALTER TABLE events_new RENAME TO events;
+
+CREATE INDEX ix_events_aggregate_id ON events (aggregate_id);
+CREATE UNIQUE INDEX uq_aggregate_id_generation_id ON events (aggregate_id, generation);
+CREATE INDEX ix_events_aggregate_type ON events (aggregate_type);
+
+CREATE TABLE audit_log_new (
+    position BIGINT PRIMARY KEY NOT NULL,
+    event_id TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    project_id TEXT,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}'
+);
+
+INSERT INTO audit_log_new
+SELECT position, event_id, aggregate_type, aggregate_id, project_id, generation, created_at, type, data, metadata
+FROM audit_log;
+
+DROP TABLE audit_log;
+ALTER TABLE audit_log_new RENAME TO audit_log;
+
+CREATE INDEX ix_audit_log_project_id ON audit_log (project_id, position);
+CREATE INDEX ix_audit_log_aggregate_id ON audit_log (aggregate_id, position);
@@ -0,0 +1,3 @@
+-- Events stored so far have the first version of their payload
+ALTER TABLE events ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE audit_log ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,26 @@
+-- The event store as the SQLite migrations leave it. Positions come
+-- from a sequence, so one is never handed out twice, and the unique
+-- index is what catches writers racing for the same generation.
+CREATE TABLE events (
+    id TEXT NOT NULL UNIQUE,
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL DEFAULT '',
+    position BIGSERIAL PRIMARY KEY NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX ix_events_aggregate_id ON events (aggregate_id);
+CREATE UNIQUE INDEX uq_aggregate_id_generation_id ON events (aggregate_id, generation);
+CREATE INDEX ix_events_aggregate_type ON events (aggregate_type);
+
+CREATE TABLE snapshots (
+    aggregate_id TEXT NOT NULL,
+    generation INTEGER NOT NULL,
+    data TEXT NOT NULL,
+    PRIMARY KEY (aggregate_id, generation)
+);
@@ -0,0 +1,7 @@
+DROP TABLE variant_names;
+DROP TABLE scheduled_changes;
+DROP TABLE audit_log;
+DROP TABLE toggle_environments;
+DROP TABLE toggles;
+DROP TABLE projects;
+DROP TABLE projection_checkpoints;
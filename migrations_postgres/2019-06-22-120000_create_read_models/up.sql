@@ -0,0 +1,70 @@
+-- The read models as the SQLite migrations leave them. Names and
+-- timestamps compare by their bytes, as they do on SQLite, so lists
+-- come out in the same order whatever the database's locale.
+CREATE TABLE projection_checkpoints (
+    name TEXT PRIMARY KEY NOT NULL,
+    position BIGINT NOT NULL
+);
+
+CREATE TABLE projects (
+    id TEXT COLLATE "C" PRIMARY KEY NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    archived BOOLEAN NOT NULL,
+    created_at TEXT COLLATE "C" NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_projects_name ON projects (name);
+CREATE INDEX ix_projects_created_at_id ON projects (created_at, id);
+
+CREATE TABLE toggles (
+    id TEXT COLLATE "C" PRIMARY KEY NOT NULL,
+    project_id TEXT NOT NULL,
+    name TEXT COLLATE "C" NOT NULL,
+    retired BOOLEAN NOT NULL
+);
+
+CREATE INDEX ix_toggles_project_id ON toggles (project_id);
+
+CREATE TABLE toggle_environments (
+    toggle_id TEXT NOT NULL,
+    environment_id TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL,
+    rollout INTEGER,
+    PRIMARY KEY (toggle_id, environment_id)
+);
+
+CREATE INDEX ix_toggle_environments_environment_id ON toggle_environments (environment_id);
+
+CREATE TABLE audit_log (
+    position BIGINT PRIMARY KEY NOT NULL,
+    event_id TEXT NOT NULL,
+    aggregate_type TEXT NOT NULL,
+    aggregate_id TEXT NOT NULL,
+    project_id TEXT,
+    generation INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    type TEXT NOT NULL,
+    data TEXT NOT NULL,
+    metadata TEXT NOT NULL DEFAULT '{}',
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX ix_audit_log_project_id ON audit_log (project_id, position);
+CREATE INDEX ix_audit_log_aggregate_id ON audit_log (aggregate_id, position);
+
+CREATE TABLE scheduled_changes (
+    id TEXT PRIMARY KEY NOT NULL,
+    project_id TEXT NOT NULL,
+    toggle_id TEXT NOT NULL,
+    at BIGINT NOT NULL
+);
+
+CREATE INDEX ix_scheduled_changes_at ON scheduled_changes (at);
+
+CREATE TABLE variant_names (
+    id TEXT PRIMARY KEY NOT NULL,
+    toggle_id TEXT NOT NULL,
+    name TEXT COLLATE "C" NOT NULL
+);
+
+CREATE UNIQUE INDEX uq_variant_names_toggle_id_name ON variant_names (toggle_id, name);
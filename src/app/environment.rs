@@ -0,0 +1,440 @@
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, HttpRequest, HttpResponse, Json, Path, State};
+use chrono::Utc;
+use diesel::Connection;
+use futures::{future, Future};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{Generation, Metadata};
+use crate::environment;
+use crate::environment::{
+    CreateEnvironmentHandler, EnvironmentAction, EnvironmentId, GetEnvironmentHandler,
+    UpdateEnvironmentHandler,
+};
+use crate::event_store::DieselEventStore;
+use crate::project::{Project, ProjectId};
+use crate::toggle::list::{DieselToggles, ToggleSummary, Toggles};
+
+use super::{
+    if_match, request_metadata, with_etag, write_transaction, AppError, AppState, Executor,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewEnvironment {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenameEnvironment {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Environment {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub archived: bool,
+}
+
+/// Domain Environment to DTO Environment
+impl From<environment::Environment> for Environment {
+    fn from(e: environment::Environment) -> Self {
+        Self {
+            id: e.id.into(),
+            project_id: e.project_id.into(),
+            name: e.name,
+            archived: e.archived,
+        }
+    }
+}
+
+/// A Toggle as it is within an Environment
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnvironmentToggle {
+    pub id: Uuid,
+    pub name: String,
+    pub enabled: bool,
+    pub rollout: Option<u8>,
+}
+
+/// Read model Toggle to DTO EnvironmentToggle
+impl From<ToggleSummary> for EnvironmentToggle {
+    fn from(t: ToggleSummary) -> Self {
+        Self {
+            id: t.id.into(),
+            name: t.name,
+            enabled: t.enabled,
+            rollout: t.rollout,
+        }
+    }
+}
+
+struct CreateEnvironment {
+    project_id: ProjectId,
+    name: String,
+    metadata: Metadata,
+}
+
+impl Message for CreateEnvironment {
+    type Result = Result<environment::Environment, AppError>;
+}
+
+impl Handler<CreateEnvironment> for Executor {
+    type Result = Result<environment::Environment, AppError>;
+
+    fn handle(&mut self, msg: CreateEnvironment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let projects = &DieselEventStore::<_, Project>::new(db);
+                let repository = &mut DieselEventStore::<_, environment::Environment>::new(db);
+                let handler = &mut CreateEnvironmentHandler {
+                    projects,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let environment = handler
+                    .handle(environment::CreateEnvironment {
+                        id: Uuid::new_v4(),
+                        project_id: msg.project_id,
+                        name: msg.name,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(environment)
+            })
+        })
+    }
+}
+
+struct GetEnvironment {
+    project_id: ProjectId,
+    id: EnvironmentId,
+}
+
+impl Message for GetEnvironment {
+    type Result = Result<environment::Environment, AppError>;
+}
+
+impl Handler<GetEnvironment> for Executor {
+    type Result = Result<environment::Environment, AppError>;
+
+    fn handle(&mut self, msg: GetEnvironment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let repository = &DieselEventStore::<_, environment::Environment>::new(db);
+                let handler = &GetEnvironmentHandler { repository };
+
+                let environment = handler
+                    .handle(environment::GetEnvironment {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(environment)
+            })
+        })
+    }
+}
+
+struct ListEnvironmentToggles {
+    project_id: ProjectId,
+    id: EnvironmentId,
+}
+
+impl Message for ListEnvironmentToggles {
+    type Result = Result<Vec<ToggleSummary>, AppError>;
+}
+
+impl Handler<ListEnvironmentToggles> for Executor {
+    type Result = Result<Vec<ToggleSummary>, AppError>;
+
+    fn handle(&mut self, msg: ListEnvironmentToggles, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let repository = &DieselEventStore::<_, environment::Environment>::new(db);
+                let handler = &GetEnvironmentHandler { repository };
+                let environment = handler
+                    .handle(environment::GetEnvironment {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+
+                let toggles = DieselToggles { db }.list(environment.project_id, environment.id)?;
+                Ok(toggles)
+            })
+        })
+    }
+}
+
+struct UpdateEnvironment {
+    project_id: ProjectId,
+    id: EnvironmentId,
+    action: EnvironmentAction,
+    expected_generation: Option<Generation>,
+    metadata: Metadata,
+}
+
+impl Message for UpdateEnvironment {
+    type Result = Result<environment::Environment, AppError>;
+}
+
+impl Handler<UpdateEnvironment> for Executor {
+    type Result = Result<environment::Environment, AppError>;
+
+    fn handle(&mut self, msg: UpdateEnvironment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let repository = &mut DieselEventStore::<_, environment::Environment>::new(db);
+                let handler = &mut UpdateEnvironmentHandler {
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let environment = handler
+                    .handle(environment::UpdateEnvironment {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                        action: msg.action,
+                        expected_generation: msg.expected_generation,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(environment)
+            })
+        })
+    }
+}
+
+pub fn create_environment(
+    (project_id, body, req): (Path<ProjectId>, Json<NewEnvironment>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    req.state()
+        .executor
+        .send(CreateEnvironment {
+            project_id: *project_id,
+            name: body.name.clone(),
+            metadata: request_metadata(&req),
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Environment::from(x))))
+        .responder()
+}
+
+pub fn get_environment(
+    (path, state): (Path<(ProjectId, EnvironmentId)>, State<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    state
+        .executor
+        .send(GetEnvironment {
+            project_id: path.0,
+            id: path.1,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Environment::from(x))))
+        .responder()
+}
+
+pub fn list_environment_toggles(
+    (path, state): (Path<(ProjectId, EnvironmentId)>, State<AppState>),
+) -> impl Future<Item = Json<Vec<EnvironmentToggle>>, Error = AppError> {
+    state
+        .executor
+        .send(ListEnvironmentToggles {
+            project_id: path.0,
+            id: path.1,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| Json(x.into_iter().map(EnvironmentToggle::from).collect())))
+        .responder()
+}
+
+fn update_environment(
+    path: &(ProjectId, EnvironmentId),
+    req: &HttpRequest<AppState>,
+    action: EnvironmentAction,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let (project_id, id) = *path;
+    let executor = req.state().executor.clone();
+    let metadata = request_metadata(req);
+    future::result(if_match(req))
+        .and_then(move |expected_generation| {
+            executor
+                .send(UpdateEnvironment {
+                    project_id,
+                    id,
+                    action,
+                    expected_generation,
+                    metadata,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation, Environment::from(x))))
+        .responder()
+}
+
+pub fn rename_environment(
+    (path, body, req): (
+        Path<(ProjectId, EnvironmentId)>,
+        Json<RenameEnvironment>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_environment(
+        &path,
+        &req,
+        EnvironmentAction::Rename(body.into_inner().name),
+    )
+}
+
+pub fn archive_environment(
+    (path, req): (Path<(ProjectId, EnvironmentId)>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_environment(&path, &req, EnvironmentAction::Archive)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::toggle::{NewToggle, Toggle};
+    use super::super::{CreateProject, Project};
+    use super::{Environment, EnvironmentToggle, NewEnvironment, RenameEnvironment};
+
+    #[test]
+    fn test_environment_lifecycle() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8092").unwrap().start();
+            tx.send("127.0.0.1:8092").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+
+        let environment: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .json(&NewEnvironment {
+                name: "staging".to_owned(),
+            })
+            .send()?
+            .json()?;
+        assert_eq!(environment.name, "staging");
+
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/{}/environments/{}/enable",
+                addr, project.id, toggle.id, environment.id
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let toggles: Vec<EnvironmentToggle> = client
+            .get(&format!(
+                "http://{}/projects/{}/environments/{}/toggles",
+                addr, project.id, environment.id
+            ))
+            .send()?
+            .json()?;
+        assert_eq!(toggles.len(), 1);
+        assert_eq!(toggles[0].id, toggle.id);
+        assert!(toggles[0].enabled);
+
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/{}/rename",
+                addr, project.id, environment.id
+            ))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .json(&RenameEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/{}/archive",
+                addr, project.id, environment.id
+            ))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/{}/archive",
+                addr, project.id, environment.id
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let mut response = client
+            .get(&format!(
+                "http://{}/projects/{}/environments/{}",
+                addr, project.id, environment.id
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"2\"")),
+        );
+        let environment: Environment = response.json()?;
+        assert_eq!(environment.name, "production");
+        assert!(environment.archived);
+
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/{}/rename",
+                addr, project.id, environment.id
+            ))
+            .json(&RenameEnvironment {
+                name: "staging".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
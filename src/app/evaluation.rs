@@ -0,0 +1,283 @@
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, Json, State};
+use diesel::Connection;
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Aggregate;
+use crate::environment;
+use crate::environment::{EnvironmentId, GetEnvironmentHandler};
+use crate::evaluation;
+use crate::evaluation::{Context, Evaluation, Segments, Toggles};
+use crate::event_store::error::EventStoreError;
+use crate::event_store::DieselEventStore;
+use crate::project::ProjectId;
+use crate::segment;
+use crate::segment::error::GetSegmentHandlerError;
+use crate::segment::{GetSegmentHandler, SegmentId};
+use crate::toggle;
+use crate::toggle::error::GetToggleHandlerError;
+use crate::toggle::{GetToggleHandler, ToggleId};
+use crate::variant;
+use crate::variant::{DieselVariantNames, ListVariantsHandler, Variants};
+
+use super::{AppError, AppState, Executor};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Evaluate {
+    pub project_id: ProjectId,
+    pub environment_id: EnvironmentId,
+    pub toggle_id: ToggleId,
+    #[serde(default)]
+    pub context: Context,
+}
+
+impl Message for Evaluate {
+    type Result = Result<Evaluation, AppError>;
+}
+
+impl Handler<Evaluate> for Executor {
+    type Result = Result<Evaluation, AppError>;
+
+    fn handle(&mut self, msg: Evaluate, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let environments = &DieselEventStore::<_, environment::Environment>::new(db);
+                let environment = GetEnvironmentHandler {
+                    repository: environments,
+                }
+                .handle(environment::GetEnvironment {
+                    project_id: msg.project_id,
+                    id: msg.environment_id,
+                })
+                .map_err(|e| -> AppError { e.into() })?;
+
+                let repository = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let handler = &GetToggleHandler { repository };
+
+                let toggle = handler
+                    .handle(toggle::GetToggle {
+                        project_id: msg.project_id,
+                        id: msg.toggle_id,
+                        as_of: None,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+
+                // Prerequisites and Rules may outlive the Toggles and Segments they
+                // refer to, which then fail every prerequisite and have no members
+                let mut toggles = Toggles::new();
+                let mut pending: Vec<ToggleId> = toggle
+                    .prerequisites()
+                    .iter()
+                    .map(|prerequisite| prerequisite.toggle_id)
+                    .collect();
+                while let Some(id) = pending.pop() {
+                    if toggles.contains_key(&id) {
+                        continue;
+                    }
+                    let result = handler.handle(toggle::GetToggle {
+                        project_id: msg.project_id,
+                        id,
+                        as_of: None,
+                    });
+                    match result {
+                        Ok(prerequisite) => {
+                            pending
+                                .extend(prerequisite.prerequisites().iter().map(|p| p.toggle_id));
+                            toggles.insert(id, prerequisite);
+                        }
+                        Err(GetToggleHandlerError::RepositoryError(
+                            EventStoreError::NotFoundError,
+                        )) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                let segment_ids: Vec<SegmentId> = toggles
+                    .values()
+                    .chain(Some(&toggle))
+                    .filter_map(|toggle| toggle.environments().get(&environment.id))
+                    .flat_map(|state| state.rules.iter().flat_map(|rule| rule.segment_ids()))
+                    .collect();
+                let segments_repository = &DieselEventStore::<_, segment::Segment>::new(db);
+                let mut segments = Segments::new();
+                for id in segment_ids {
+                    let result = GetSegmentHandler {
+                        repository: segments_repository,
+                    }
+                    .handle(segment::GetSegment {
+                        project_id: msg.project_id,
+                        id,
+                    });
+                    match result {
+                        Ok(segment) => {
+                            segments.insert(id, segment);
+                        }
+                        Err(GetSegmentHandlerError::RepositoryError(
+                            EventStoreError::NotFoundError,
+                        )) => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+
+                let handler = ListVariantsHandler {
+                    toggles: repository,
+                    names: &DieselVariantNames { db },
+                    repository: &DieselEventStore::<_, variant::Variant>::new(db),
+                };
+                let mut variants = Variants::new();
+                for toggle in toggles.values().chain(Some(&toggle)) {
+                    let list = handler
+                        .handle(variant::ListVariants {
+                            project_id: msg.project_id,
+                            toggle_id: *toggle.id(),
+                        })
+                        .map_err(|e| -> AppError { e.into() })?;
+                    variants.extend(list.into_iter().map(|variant| (variant.id, variant)));
+                }
+                Ok(evaluation::evaluate(
+                    &toggle,
+                    environment.id,
+                    &msg.context,
+                    &segments,
+                    &toggles,
+                    &variants,
+                ))
+            })
+        })
+    }
+}
+
+pub fn evaluate(
+    (body, state): (Json<Evaluate>, State<AppState>),
+) -> impl Future<Item = Json<Evaluation>, Error = AppError> {
+    state
+        .executor
+        .send(body.into_inner())
+        .from_err()
+        .and_then(|res| res.map(Json))
+        .responder()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+    use uuid::Uuid;
+
+    use crate::evaluation::{Context, Evaluation, Reason};
+
+    use super::super::environment::{Environment, NewEnvironment};
+    use super::super::toggle::{NewToggle, Toggle};
+    use super::super::{CreateProject, Project};
+    use super::Evaluate;
+
+    #[test]
+    fn test_evaluate() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8091").unwrap().start();
+            tx.send("127.0.0.1:8091").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let environment: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .json(&NewEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/{}/environments/{}/enable",
+                addr, project.id, toggle.id, environment.id
+            ))
+            .send()?;
+
+        let evaluation: Evaluation = client
+            .post(&format!("http://{}/evaluate", addr))
+            .json(&Evaluate {
+                project_id: project.id.into(),
+                environment_id: environment.id.into(),
+                toggle_id: toggle.id.into(),
+                context: Context {
+                    user_id: Some("user".to_owned()),
+                    ..Context::default()
+                },
+            })
+            .send()?
+            .json()?;
+        assert_eq!(
+            evaluation,
+            Evaluation {
+                value: true,
+                variant: "on".to_owned(),
+                variant_id: None,
+                reason: Reason::On,
+            },
+        );
+
+        let response = client
+            .post(&format!("http://{}/evaluate", addr))
+            .json(&Evaluate {
+                project_id: project.id.into(),
+                environment_id: environment.id.into(),
+                toggle_id: Uuid::new_v4().into(),
+                context: Context::default(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        let response = client
+            .post(&format!("http://{}/evaluate", addr))
+            .json(&Evaluate {
+                project_id: project.id.into(),
+                environment_id: Uuid::new_v4().into(),
+                toggle_id: toggle.id.into(),
+                context: Context::default(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, Json, Query, State};
+use chrono::{DateTime, Utc};
+use diesel::Connection;
+use futures::Future;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models::Event;
+use crate::domain::Metadata;
+use crate::event_store::error::DomainEventError;
+use crate::event_store::{DieselEventLog, EventStore};
+
+use super::{AppError, AppState, Executor};
+
+/// Events read when no limit is given.
+const DEFAULT_LIMIT: usize = 100;
+/// Most events read at a time.
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ReadEventsQuery {
+    /// Position of the last event already read, 0 to read from the first event.
+    pub from: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StoredEvent {
+    position: i64,
+    id: Uuid,
+    aggregate_type: String,
+    aggregate_id: Uuid,
+    generation: i32,
+    created_at: DateTime<Utc>,
+    #[serde(rename = "type")]
+    type_: String,
+    data: serde_json::Value,
+    /// Version of the shape of `data`, which is as it was stored.
+    version: i32,
+    metadata: Metadata,
+}
+
+impl StoredEvent {
+    fn from_event(event: Event) -> Result<Self, DomainEventError> {
+        Ok(Self {
+            position: event.position,
+            id: Uuid::parse_str(&event.id)?,
+            aggregate_type: event.aggregate_type,
+            aggregate_id: Uuid::parse_str(&event.aggregate_id)?,
+            generation: event.generation,
+            created_at: event.created_at.parse::<DateTime<Utc>>()?,
+            type_: event.type_,
+            data: serde_json::from_str(&event.data)?,
+            version: event.version,
+            metadata: serde_json::from_str(&event.metadata)?,
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Events {
+    events: Vec<StoredEvent>,
+    /// Position to read the events stored after these from.
+    next: i64,
+}
+
+struct ReadEvents {
+    from: i64,
+    limit: usize,
+}
+
+impl Message for ReadEvents {
+    type Result = Result<Events, AppError>;
+}
+
+impl Handler<ReadEvents> for Executor {
+    type Result = Result<Events, AppError>;
+
+    fn handle(&mut self, msg: ReadEvents, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let events = DieselEventLog { db }
+                    .read_all(msg.from, msg.limit)?
+                    .into_iter()
+                    .map(StoredEvent::from_event)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let next = events.last().map_or(msg.from, |event| event.position);
+                Ok(Events { events, next })
+            })
+        })
+    }
+}
+
+pub fn read_events(
+    (query, state): (Query<ReadEventsQuery>, State<AppState>),
+) -> impl Future<Item = Json<Events>, Error = AppError> {
+    state
+        .executor
+        .send(ReadEvents {
+            from: query.from.unwrap_or(0).max(0),
+            limit: query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        })
+        .from_err()
+        .and_then(|res| res.map(Json))
+        .responder()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::CreateProject;
+    use super::Events;
+
+    #[test]
+    fn test_read_events() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8099").unwrap().start();
+            tx.send("127.0.0.1:8099").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        for name in &["checkout", "search"] {
+            let response = client
+                .post(&format!("http://{}/projects/create", addr))
+                .header("X-Actor", "alice")
+                .header("X-Reason", "launch")
+                .json(&CreateProject {
+                    name: (*name).to_owned(),
+                })
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+
+        let page: Events = client
+            .get(&format!("http://{}/events", addr))
+            .send()?
+            .json()?;
+        assert_eq!(page.next, 2);
+        let positions = page.events.iter().map(|e| e.position).collect::<Vec<_>>();
+        assert_eq!(positions, vec![1, 2]);
+        assert_eq!(page.events[0].aggregate_type, "project");
+        assert_eq!(page.events[0].version, 1);
+        assert_eq!(page.events[0].data["Created"]["name"], "checkout");
+        let metadata = &page.events[0].metadata;
+        assert_eq!(metadata.actor, Some("alice".to_owned()));
+        assert_eq!(metadata.reason, Some("launch".to_owned()));
+        assert_eq!(metadata.client_ip, Some("127.0.0.1".to_owned()));
+        assert!(metadata.correlation_id.is_some());
+        assert_ne!(
+            metadata.correlation_id,
+            page.events[1].metadata.correlation_id
+        );
+
+        let page: Events = client
+            .get(&format!("http://{}/events?from=1&limit=1", addr))
+            .send()?
+            .json()?;
+        assert_eq!(page.next, 2);
+        assert_eq!(page.events.len(), 1);
+        assert_eq!(page.events[0].data["Created"]["name"], "search");
+
+        // Nothing new yet, so read from the same position again later
+        let page: Events = client
+            .get(&format!("http://{}/events?from=2", addr))
+            .send()?
+            .json()?;
+        assert_eq!(page.next, 2);
+        assert!(page.events.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,334 @@
+use actix::{Handler, Message};
+use actix_web::{http::header, AsyncResponder, HttpResponse, Path, Query, State};
+use chrono::{DateTime, Utc};
+use diesel::Connection;
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+use crate::environment::EnvironmentId;
+use crate::history::{GetHistoryHandler, HistoryEntry, HistoryFilter};
+use crate::project::ProjectId;
+use crate::projection::audit::DieselAuditLog;
+use crate::toggle::ToggleId;
+
+use super::{AppError, AppState, Executor};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct HistoryQuery {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub actor: Option<String>,
+    pub environment_id: Option<EnvironmentId>,
+    /// JSON unless asked for otherwise.
+    pub format: Option<Format>,
+}
+
+impl HistoryQuery {
+    fn filter(&self) -> HistoryFilter {
+        HistoryFilter {
+            from: self.from,
+            to: self.to,
+            actor: self.actor.clone(),
+            environment_id: self.environment_id,
+        }
+    }
+}
+
+struct GetHistory {
+    project_id: ProjectId,
+    toggle_id: Option<ToggleId>,
+    filter: HistoryFilter,
+}
+
+impl Message for GetHistory {
+    type Result = Result<Vec<HistoryEntry>, AppError>;
+}
+
+impl Handler<GetHistory> for Executor {
+    type Result = Result<Vec<HistoryEntry>, AppError>;
+
+    fn handle(&mut self, msg: GetHistory, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let audit_log = &DieselAuditLog { db };
+                let handler = &GetHistoryHandler { audit_log };
+
+                let entries = handler
+                    .handle(crate::history::GetHistory {
+                        project_id: msg.project_id,
+                        toggle_id: msg.toggle_id,
+                        filter: msg.filter,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(entries)
+            })
+        })
+    }
+}
+
+/// Quotes a CSV field when it has to be, doubling any quotes within it.
+fn csv_field(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+fn to_csv(entries: &[HistoryEntry]) -> String {
+    let mut csv = String::from(
+        "position,created_at,actor,reason,aggregate_type,aggregate_id,name,\
+         environment_id,change,summary,before,after\r\n",
+    );
+    for entry in entries {
+        let fields = [
+            entry.position.to_string(),
+            entry.created_at.to_rfc3339(),
+            entry.metadata.actor.clone().unwrap_or_default(),
+            entry.metadata.reason.clone().unwrap_or_default(),
+            entry.aggregate_type.clone(),
+            entry.aggregate_id.to_string(),
+            entry.name.clone(),
+            entry
+                .environment_id
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            entry.change.clone(),
+            entry.summary.clone(),
+            entry
+                .before
+                .as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            entry
+                .after
+                .as_ref()
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+        ];
+        let row = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>();
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+fn respond(entries: Vec<HistoryEntry>, format: Option<Format>) -> HttpResponse {
+    match format.unwrap_or(Format::Json) {
+        Format::Json => HttpResponse::Ok().json(entries),
+        Format::Csv => HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .header(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"history.csv\"",
+            )
+            .body(to_csv(&entries)),
+    }
+}
+
+pub fn get_project_history(
+    (path, query, state): (Path<ProjectId>, Query<HistoryQuery>, State<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let format = query.format;
+    state
+        .executor
+        .send(GetHistory {
+            project_id: *path,
+            toggle_id: None,
+            filter: query.filter(),
+        })
+        .from_err()
+        .and_then(move |res| res.map(|entries| respond(entries, format)))
+        .responder()
+}
+
+pub fn get_toggle_history(
+    (path, query, state): (
+        Path<(ProjectId, ToggleId)>,
+        Query<HistoryQuery>,
+        State<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let format = query.format;
+    state
+        .executor
+        .send(GetHistory {
+            project_id: path.0,
+            toggle_id: Some(path.1),
+            filter: query.filter(),
+        })
+        .from_err()
+        .and_then(move |res| res.map(|entries| respond(entries, format)))
+        .responder()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::environment::{Environment, NewEnvironment};
+    use super::super::toggle::{NewToggle, Rollout, Toggle};
+    use super::super::{CreateProject, Project};
+    use crate::history::HistoryEntry;
+
+    #[test]
+    fn test_history() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8100").unwrap().start();
+            tx.send("127.0.0.1:8100").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .header("X-Actor", "alice")
+            .json(&CreateProject {
+                name: "checkout".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let production: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .header("X-Actor", "alice")
+            .json(&NewEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .header("X-Actor", "alice")
+            .json(&NewToggle {
+                name: "new-cart".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/{}/environments/{}/rollout",
+                addr, project.id, toggle.id, production.id
+            ))
+            .header("X-Actor", "bob")
+            .header("X-Reason", "slow start, then everyone")
+            .json(&Rollout { percentage: 10 })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let entries: Vec<HistoryEntry> = client
+            .get(&format!("http://{}/projects/{}/history", addr, project.id))
+            .send()?
+            .json()?;
+        let changes = entries
+            .iter()
+            .map(|e| (e.aggregate_type.as_str(), e.change.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                ("project", "Created"),
+                ("environment", "Created"),
+                ("toggle", "Created"),
+                ("toggle", "RolloutChanged"),
+            ],
+        );
+        assert_eq!(
+            entries[3].summary,
+            "Rolled toggle \"new-cart\" out to 10% in production"
+        );
+        assert_eq!(entries[3].metadata.actor, Some("bob".to_owned()));
+
+        let entries: Vec<HistoryEntry> = client
+            .get(&format!(
+                "http://{}/projects/{}/toggles/{}/history?actor=alice",
+                addr, project.id, toggle.id
+            ))
+            .send()?
+            .json()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].change, "Created");
+
+        let entries: Vec<HistoryEntry> = client
+            .get(&format!(
+                "http://{}/projects/{}/history?environment_id={}",
+                addr, project.id, production.id
+            ))
+            .send()?
+            .json()?;
+        assert_eq!(entries.len(), 2);
+
+        let entries: Vec<HistoryEntry> = client
+            .get(&format!(
+                "http://{}/projects/{}/history?to=2000-01-01T00:00:00Z",
+                addr, project.id
+            ))
+            .send()?
+            .json()?;
+        assert!(entries.is_empty());
+
+        let mut response = client
+            .get(&format!(
+                "http://{}/projects/{}/toggles/{}/history?format=csv",
+                addr, project.id, toggle.id
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(reqwest::header::CONTENT_TYPE),
+            Some(&reqwest::header::HeaderValue::from_static(
+                "text/csv; charset=utf-8"
+            )),
+        );
+        let csv = response.text()?;
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("position,created_at,actor,reason,"));
+        assert!(lines[2].contains(",bob,\"slow start, then everyone\",toggle,"));
+        assert!(lines[2].ends_with(",\"{\"\"rollout\"\":null}\",\"{\"\"rollout\"\":10}\""));
+
+        let response = client
+            .get(&format!(
+                "http://{}/projects/{}/toggles/{}/history",
+                addr, project.id, production.id
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
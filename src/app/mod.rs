@@ -1,29 +1,98 @@
+/// Run `$body` with `$db` bound to a connection from the Executor's
+/// Database. The body is compiled for each kind of connection, so it
+/// can use the stores of whichever Backend the Database is on.
+macro_rules! with_connection {
+    ($database:expr, |$db:ident| $body:expr) => {
+        match $database {
+            $crate::app::Database::Sqlite(pool) => {
+                let connection = pool.get().map_err(|e| -> AppError { e.into() })?;
+                let $db: &diesel::sqlite::SqliteConnection = &connection;
+                $body
+            }
+            #[cfg(feature = "postgres")]
+            $crate::app::Database::Postgres(pool) => {
+                let connection = pool.get().map_err(|e| -> AppError { e.into() })?;
+                let $db: &diesel::pg::PgConnection = &connection;
+                $body
+            }
+        }
+    };
+}
+
+mod environment;
+mod evaluation;
+mod event;
+mod history;
+mod projection;
+mod scheduler;
+mod segment;
+mod toggle;
+mod variant;
+
+use std::net::SocketAddr;
+
 use actix::{Actor, Addr, Handler, Message, SyncArbiter, SyncContext};
 use actix_web::middleware::Logger;
 use actix_web::AsyncResponder;
 use actix_web::{
-    dev::FromParam, error::ResponseError, http::StatusCode, server, server::HttpServer,
-    HttpResponse, Json, Path, State,
+    dev::FromParam,
+    error::ResponseError,
+    http::header::{self, Header},
+    http::StatusCode,
+    server,
+    server::HttpServer,
+    HttpMessage, HttpRequest, HttpResponse, Json, Path, Query, State,
 };
 use actix_web::{http::Method, App};
-use chrono::Utc;
-use diesel::r2d2::{ConnectionManager, Pool};
+use chrono::{DateTime, Utc};
+#[cfg(feature = "postgres")]
+use diesel::pg::PgConnection;
+use diesel::r2d2::{Builder, ConnectionManager, CustomizeConnection, Pool};
 use diesel::sqlite::SqliteConnection;
 use diesel::Connection;
 use failure::Error;
 use failure_derive::Fail;
-use futures::Future;
+use futures::{future, Future};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::database::Backend;
+#[cfg(not(feature = "postgres"))]
+use crate::database::UnsupportedBackend;
+use crate::domain::{Generation, Metadata, PointInTime};
+use crate::environment::error::{
+    CreateEnvironmentHandlerError, EnvironmentError, GetEnvironmentHandlerError,
+    UpdateEnvironmentHandlerError,
+};
+use crate::event_store::{
+    error::{DomainEventError, EventStoreError},
+    DieselEventStore,
+};
+use crate::history::error::HistoryHandlerError;
 use crate::project;
 use crate::project::{
     error::{
-        CreateProjectHandlerError, ListProjectHandlerError, ProjectIdParseError,
-        SqliteRepositoryError,
+        CreateProjectHandlerError, ListProjectHandlerError, ListProjectsHandlerError, ProjectError,
+        ProjectIdParseError, UpdateProjectHandlerError,
     },
-    CreateProjectHandler, ListProjectHandler, ProjectId, SqliteRepository,
+    list::{DieselProjects, ProjectQuery, ProjectSort, ProjectSummary, SortOrder},
+    CreateProjectHandler, ListProjectHandler, ListProjectsHandler, ProjectAction, ProjectId,
+    ProjectPage, UpdateProjectHandler,
+};
+use crate::projection::error::ProjectionError;
+use crate::segment::error::{
+    CreateSegmentHandlerError, GetSegmentHandlerError, UpdateSegmentHandlerError,
+};
+use crate::toggle::error::{
+    CreateToggleHandlerError, GetToggleHandlerError, ToggleError, UpdateToggleHandlerError,
 };
+use crate::variant::error::{
+    CreateVariantHandlerError, GetVariantHandlerError, ListVariantsHandlerError,
+    UpdateVariantHandlerError, VariantError,
+};
+
+use self::projection::Projections;
+use self::scheduler::Scheduler;
 
 impl FromParam for ProjectId {
     type Err = ProjectIdParseError;
@@ -41,11 +110,6 @@ impl ResponseError for ProjectIdParseError {
     }
 }
 
-struct Environment {
-    id: Uuid,
-    name: String,
-}
-
 struct Feature {
     id: Uuid,
     name: String,
@@ -59,27 +123,6 @@ struct Toggle {
     retired: bool,
 }
 
-#[derive(Debug)]
-pub struct Variant {
-    id: Uuid,
-    generation: u64,
-    toggle_id: Uuid,
-    name: String,
-    retired: bool,
-}
-
-#[derive(Clone, Debug)]
-pub enum VariantEvent {
-    Created {
-        id: Uuid,
-        toggle_id: Uuid,
-        name: String,
-    },
-    Renamed(String),
-    Retired,
-    Revived,
-}
-
 #[derive(Debug, Fail)]
 pub enum AppError {
     #[fail(display = "database pool error")]
@@ -90,10 +133,52 @@ pub enum AppError {
     MailboxError(#[cause] actix::MailboxError),
     #[fail(display = "json payload error")]
     JsonPayloadError(#[cause] actix_web::error::JsonPayloadError),
+    #[fail(display = "precondition failed")]
+    PreconditionFailed,
+    #[fail(display = "only one of as_of and generation can be given")]
+    AmbiguousPointInTime,
+    #[fail(display = "domain event error")]
+    DomainEventError(#[cause] DomainEventError),
+    #[fail(display = "projection error")]
+    ProjectionError(#[cause] ProjectionError),
+    #[fail(display = "projection not found: {}", name)]
+    ProjectionNotFound { name: String },
     #[fail(display = "create project error")]
     CreateProjectError(#[cause] CreateProjectHandlerError),
     #[fail(display = "list project error")]
     ListProjectError(#[cause] ListProjectHandlerError),
+    #[fail(display = "list projects error")]
+    ListProjectsError(#[cause] ListProjectsHandlerError),
+    #[fail(display = "update project error")]
+    UpdateProjectError(#[cause] UpdateProjectHandlerError),
+    #[fail(display = "create environment error")]
+    CreateEnvironmentError(#[cause] CreateEnvironmentHandlerError),
+    #[fail(display = "get environment error")]
+    GetEnvironmentError(#[cause] GetEnvironmentHandlerError),
+    #[fail(display = "update environment error")]
+    UpdateEnvironmentError(#[cause] UpdateEnvironmentHandlerError),
+    #[fail(display = "create segment error")]
+    CreateSegmentError(#[cause] CreateSegmentHandlerError),
+    #[fail(display = "get segment error")]
+    GetSegmentError(#[cause] GetSegmentHandlerError),
+    #[fail(display = "update segment error")]
+    UpdateSegmentError(#[cause] UpdateSegmentHandlerError),
+    #[fail(display = "create toggle error")]
+    CreateToggleError(#[cause] CreateToggleHandlerError),
+    #[fail(display = "get toggle error")]
+    GetToggleError(#[cause] GetToggleHandlerError),
+    #[fail(display = "update toggle error")]
+    UpdateToggleError(#[cause] UpdateToggleHandlerError),
+    #[fail(display = "create variant error")]
+    CreateVariantError(#[cause] CreateVariantHandlerError),
+    #[fail(display = "get variant error")]
+    GetVariantError(#[cause] GetVariantHandlerError),
+    #[fail(display = "list variants error")]
+    ListVariantsError(#[cause] ListVariantsHandlerError),
+    #[fail(display = "update variant error")]
+    UpdateVariantError(#[cause] UpdateVariantHandlerError),
+    #[fail(display = "history error")]
+    HistoryError(#[cause] HistoryHandlerError),
 }
 
 impl From<r2d2::Error> for AppError {
@@ -120,6 +205,18 @@ impl From<actix_web::error::JsonPayloadError> for AppError {
     }
 }
 
+impl From<DomainEventError> for AppError {
+    fn from(e: DomainEventError) -> Self {
+        AppError::DomainEventError(e)
+    }
+}
+
+impl From<ProjectionError> for AppError {
+    fn from(e: ProjectionError) -> Self {
+        AppError::ProjectionError(e)
+    }
+}
+
 impl From<CreateProjectHandlerError> for AppError {
     fn from(e: CreateProjectHandlerError) -> Self {
         AppError::CreateProjectError(e)
@@ -132,6 +229,102 @@ impl From<ListProjectHandlerError> for AppError {
     }
 }
 
+impl From<ListProjectsHandlerError> for AppError {
+    fn from(e: ListProjectsHandlerError) -> Self {
+        AppError::ListProjectsError(e)
+    }
+}
+
+impl From<UpdateProjectHandlerError> for AppError {
+    fn from(e: UpdateProjectHandlerError) -> Self {
+        AppError::UpdateProjectError(e)
+    }
+}
+
+impl From<CreateEnvironmentHandlerError> for AppError {
+    fn from(e: CreateEnvironmentHandlerError) -> Self {
+        AppError::CreateEnvironmentError(e)
+    }
+}
+
+impl From<GetEnvironmentHandlerError> for AppError {
+    fn from(e: GetEnvironmentHandlerError) -> Self {
+        AppError::GetEnvironmentError(e)
+    }
+}
+
+impl From<UpdateEnvironmentHandlerError> for AppError {
+    fn from(e: UpdateEnvironmentHandlerError) -> Self {
+        AppError::UpdateEnvironmentError(e)
+    }
+}
+
+impl From<CreateSegmentHandlerError> for AppError {
+    fn from(e: CreateSegmentHandlerError) -> Self {
+        AppError::CreateSegmentError(e)
+    }
+}
+
+impl From<GetSegmentHandlerError> for AppError {
+    fn from(e: GetSegmentHandlerError) -> Self {
+        AppError::GetSegmentError(e)
+    }
+}
+
+impl From<UpdateSegmentHandlerError> for AppError {
+    fn from(e: UpdateSegmentHandlerError) -> Self {
+        AppError::UpdateSegmentError(e)
+    }
+}
+
+impl From<CreateToggleHandlerError> for AppError {
+    fn from(e: CreateToggleHandlerError) -> Self {
+        AppError::CreateToggleError(e)
+    }
+}
+
+impl From<GetToggleHandlerError> for AppError {
+    fn from(e: GetToggleHandlerError) -> Self {
+        AppError::GetToggleError(e)
+    }
+}
+
+impl From<UpdateToggleHandlerError> for AppError {
+    fn from(e: UpdateToggleHandlerError) -> Self {
+        AppError::UpdateToggleError(e)
+    }
+}
+
+impl From<CreateVariantHandlerError> for AppError {
+    fn from(e: CreateVariantHandlerError) -> Self {
+        AppError::CreateVariantError(e)
+    }
+}
+
+impl From<GetVariantHandlerError> for AppError {
+    fn from(e: GetVariantHandlerError) -> Self {
+        AppError::GetVariantError(e)
+    }
+}
+
+impl From<ListVariantsHandlerError> for AppError {
+    fn from(e: ListVariantsHandlerError) -> Self {
+        AppError::ListVariantsError(e)
+    }
+}
+
+impl From<UpdateVariantHandlerError> for AppError {
+    fn from(e: UpdateVariantHandlerError) -> Self {
+        AppError::UpdateVariantError(e)
+    }
+}
+
+impl From<HistoryHandlerError> for AppError {
+    fn from(e: HistoryHandlerError) -> Self {
+        AppError::HistoryError(e)
+    }
+}
+
 impl ResponseError for AppError {
     fn error_response(&self) -> HttpResponse {
         match *self {
@@ -139,17 +332,236 @@ impl ResponseError for AppError {
             AppError::DatabaseError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
             AppError::MailboxError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
             AppError::JsonPayloadError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::PreconditionFailed => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::AmbiguousPointInTime => HttpResponse::new(StatusCode::BAD_REQUEST),
+            AppError::DomainEventError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::ProjectionError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::ProjectionNotFound { .. } => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::CreateProjectError(CreateProjectHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateProjectError(CreateProjectHandlerError::ProjectError(
+                ProjectError::DuplicateName { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateProjectError(CreateProjectHandlerError::ProjectsError(_)) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
             AppError::CreateProjectError(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
             AppError::ListProjectError(ListProjectHandlerError::RepositoryError(
-                SqliteRepositoryError::NotFoundError,
+                EventStoreError::NotFoundError,
             )) => HttpResponse::new(StatusCode::NOT_FOUND),
             AppError::ListProjectError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::ListProjectsError(ListProjectsHandlerError::InvalidCursor { .. }) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::ListProjectsError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::UpdateProjectError(UpdateProjectHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::UpdateProjectError(UpdateProjectHandlerError::ProjectError(
+                ProjectError::DuplicateName { .. },
+            ))
+            | AppError::UpdateProjectError(UpdateProjectHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::UpdateProjectError(UpdateProjectHandlerError::ProjectError(_)) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::UpdateProjectError(UpdateProjectHandlerError::ConcurrencyConflict {
+                ..
+            }) => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::UpdateProjectError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::CreateEnvironmentError(
+                CreateEnvironmentHandlerError::ProjectRepositoryError(
+                    EventStoreError::NotFoundError,
+                ),
+            ) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::CreateEnvironmentError(CreateEnvironmentHandlerError::EnvironmentError(
+                _,
+            )) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            AppError::CreateEnvironmentError(CreateEnvironmentHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateEnvironmentError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            AppError::GetEnvironmentError(GetEnvironmentHandlerError::NotFoundError)
+            | AppError::GetEnvironmentError(GetEnvironmentHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::GetEnvironmentError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            AppError::UpdateEnvironmentError(UpdateEnvironmentHandlerError::NotFoundError)
+            | AppError::UpdateEnvironmentError(UpdateEnvironmentHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::UpdateEnvironmentError(UpdateEnvironmentHandlerError::EnvironmentError(
+                EnvironmentError::InvalidName { .. },
+            ))
+            | AppError::UpdateEnvironmentError(UpdateEnvironmentHandlerError::EnvironmentError(
+                EnvironmentError::InvalidStateEvent { .. },
+            )) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            AppError::UpdateEnvironmentError(
+                UpdateEnvironmentHandlerError::ConcurrencyConflict { .. },
+            ) => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::UpdateEnvironmentError(UpdateEnvironmentHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::UpdateEnvironmentError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            AppError::CreateSegmentError(CreateSegmentHandlerError::ProjectRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::CreateSegmentError(CreateSegmentHandlerError::SegmentError(_)) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::CreateSegmentError(CreateSegmentHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateSegmentError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::GetSegmentError(GetSegmentHandlerError::NotFoundError)
+            | AppError::GetSegmentError(GetSegmentHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::GetSegmentError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::UpdateSegmentError(UpdateSegmentHandlerError::NotFoundError)
+            | AppError::UpdateSegmentError(UpdateSegmentHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::UpdateSegmentError(UpdateSegmentHandlerError::SegmentError(_)) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::UpdateSegmentError(UpdateSegmentHandlerError::ConcurrencyConflict {
+                ..
+            }) => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::UpdateSegmentError(UpdateSegmentHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::UpdateSegmentError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::CreateToggleError(CreateToggleHandlerError::ProjectRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::CreateToggleError(CreateToggleHandlerError::ToggleError(_))
+            | AppError::CreateToggleError(CreateToggleHandlerError::ProjectError(
+                ProjectError::Archived { .. },
+            )) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            AppError::CreateToggleError(CreateToggleHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateToggleError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::GetToggleError(GetToggleHandlerError::NotFoundError)
+            | AppError::GetToggleError(GetToggleHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::GetToggleError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::UpdateToggleError(UpdateToggleHandlerError::NotFoundError)
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::EnvironmentRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidEnvironment { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidRollout { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidRule { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidPrerequisite { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::PrerequisiteCycle { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidSchedule { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidRevert { .. },
+            ))
+            | AppError::UpdateToggleError(UpdateToggleHandlerError::ToggleError(
+                ToggleError::InvalidStateEvent { .. },
+            )) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            AppError::UpdateToggleError(UpdateToggleHandlerError::ConcurrencyConflict {
+                ..
+            }) => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::UpdateToggleError(UpdateToggleHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::UpdateToggleError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::CreateVariantError(CreateVariantHandlerError::NotFoundError)
+            | AppError::CreateVariantError(CreateVariantHandlerError::ToggleRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::CreateVariantError(CreateVariantHandlerError::VariantError(
+                VariantError::DuplicateName { .. },
+            ))
+            | AppError::CreateVariantError(CreateVariantHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::CreateVariantError(CreateVariantHandlerError::VariantError(_)) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::CreateVariantError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::GetVariantError(GetVariantHandlerError::NotFoundError)
+            | AppError::GetVariantError(GetVariantHandlerError::ToggleRepositoryError(
+                EventStoreError::NotFoundError,
+            ))
+            | AppError::GetVariantError(GetVariantHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::GetVariantError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::ListVariantsError(ListVariantsHandlerError::NotFoundError)
+            | AppError::ListVariantsError(ListVariantsHandlerError::ToggleRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::ListVariantsError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::UpdateVariantError(UpdateVariantHandlerError::NotFoundError)
+            | AppError::UpdateVariantError(UpdateVariantHandlerError::ToggleRepositoryError(
+                EventStoreError::NotFoundError,
+            ))
+            | AppError::UpdateVariantError(UpdateVariantHandlerError::RepositoryError(
+                EventStoreError::NotFoundError,
+            )) => HttpResponse::new(StatusCode::NOT_FOUND),
+            AppError::UpdateVariantError(UpdateVariantHandlerError::VariantError(
+                VariantError::DuplicateName { .. },
+            ))
+            | AppError::UpdateVariantError(UpdateVariantHandlerError::RepositoryError(
+                EventStoreError::ConcurrencyConflict { .. },
+            )) => HttpResponse::new(StatusCode::CONFLICT),
+            AppError::UpdateVariantError(UpdateVariantHandlerError::VariantError(_)) => {
+                HttpResponse::new(StatusCode::BAD_REQUEST)
+            }
+            AppError::UpdateVariantError(UpdateVariantHandlerError::ConcurrencyConflict {
+                ..
+            }) => HttpResponse::new(StatusCode::PRECONDITION_FAILED),
+            AppError::UpdateVariantError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
+            AppError::HistoryError(HistoryHandlerError::ProjectNotFound) => {
+                HttpResponse::new(StatusCode::NOT_FOUND)
+            }
+            AppError::HistoryError(HistoryHandlerError::ToggleNotFound) => {
+                HttpResponse::new(StatusCode::NOT_FOUND)
+            }
+            AppError::HistoryError(_) => HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR),
         }
     }
 }
 
+/// Connections to the service's database, on whichever Backend its URL names.
+#[derive(Clone)]
+pub enum Database {
+    Sqlite(Pool<ConnectionManager<SqliteConnection>>),
+    #[cfg(feature = "postgres")]
+    Postgres(Pool<ConnectionManager<PgConnection>>),
+}
+
 pub struct Executor {
-    pub db: Pool<ConnectionManager<SqliteConnection>>,
+    pub db: Database,
 }
 
 impl Actor for Executor {
@@ -161,34 +573,178 @@ pub struct AppState {
     pub executor: Addr<Executor>,
 }
 
+/// How long a connection waits for another to release SQLite's lock on
+/// the database file, such as the Scheduler's, before giving up.
+const BUSY_TIMEOUT_MS: u32 = 5000;
+
+/// Has SQLite connections wait for the lock on the database file rather
+/// than fail at once with SQLITE_BUSY while another connection writes.
+#[derive(Debug)]
+struct BusyTimeout;
+
+impl CustomizeConnection<SqliteConnection, diesel::r2d2::Error> for BusyTimeout {
+    fn on_acquire(&self, db: &mut SqliteConnection) -> Result<(), diesel::r2d2::Error> {
+        db.execute(&format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS))
+            .map_err(diesel::r2d2::Error::QueryError)?;
+        Ok(())
+    }
+}
+
+/// A connection to a database commands can be run on.
+pub trait Writer: Projections {
+    /// Run `f` in a transaction holding the database's write lock from the
+    /// start, so two commands that both read before writing can't deadlock.
+    fn write_locked<T, F>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce() -> Result<T, AppError>;
+}
+
+impl Writer for SqliteConnection {
+    fn write_locked<T, F>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce() -> Result<T, AppError>,
+    {
+        self.immediate_transaction(f)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl Writer for PgConnection {
+    fn write_locked<T, F>(&self, f: F) -> Result<T, AppError>
+    where
+        F: FnOnce() -> Result<T, AppError>,
+    {
+        self.transaction(f)
+    }
+}
+
+/// Run a command in a transaction, applying the events it stored to
+/// every projection before committing so reads never lag behind writes.
+fn write_transaction<C, T, F>(db: &C, command: F) -> Result<T, AppError>
+where
+    C: Writer,
+    F: FnOnce() -> Result<T, AppError>,
+{
+    db.write_locked(|| {
+        let result = command()?;
+        db.catch_up()?;
+        Ok(result)
+    })
+}
+
+/// Respond with the representation of an aggregate,
+/// using its generation as the entity tag.
+fn with_etag<T: Serialize>(generation: Generation, body: T) -> HttpResponse {
+    let tag = i32::from(generation).to_string();
+    HttpResponse::Ok()
+        .set(header::ETag(header::EntityTag::strong(tag)))
+        .json(body)
+}
+
+/// The generation a client expects to be modifying, taken from the
+/// `If-Match` header. Both a missing header and `*` match any generation.
+fn if_match<S>(req: &HttpRequest<S>) -> Result<Option<Generation>, AppError> {
+    if !req.headers().contains_key(header::IF_MATCH) {
+        return Ok(None);
+    }
+    match header::IfMatch::parse(req).map_err(|_| AppError::PreconditionFailed)? {
+        header::IfMatch::Any => Ok(None),
+        // Generations are strong validators, so weak entity tags never match
+        header::IfMatch::Items(tags) => match tags.as_slice() {
+            [tag] if !tag.weak => tag
+                .tag()
+                .parse::<i32>()
+                .map(|generation| Some(generation.into()))
+                .map_err(|_| AppError::PreconditionFailed),
+            _ => Err(AppError::PreconditionFailed),
+        },
+    }
+}
+
+/// Query to read an aggregate as it was at a point in its
+/// history, either a time or a generation, rather than as it is now.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct PointInTimeQuery {
+    pub as_of: Option<DateTime<Utc>>,
+    pub generation: Option<i32>,
+}
+
+impl PointInTimeQuery {
+    fn point_in_time(&self) -> Result<Option<PointInTime>, AppError> {
+        match (self.as_of, self.generation) {
+            (None, None) => Ok(None),
+            (Some(at), None) => Ok(Some(PointInTime::Time(at))),
+            (None, Some(generation)) => Ok(Some(PointInTime::Generation(generation.into()))),
+            (Some(_), Some(_)) => Err(AppError::AmbiguousPointInTime),
+        }
+    }
+}
+
+/// Who is making a request and why, taken from headers the client sets:
+/// `X-Actor`, `X-Api-Key-Id`, `X-Correlation-Id`, `X-Causation-Id` and
+/// `X-Reason`. Requests without a correlation id are given a new one.
+fn request_metadata<S>(req: &HttpRequest<S>) -> Metadata {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let id = |name: &str| header(name).and_then(|value| Uuid::parse_str(&value).ok());
+    // The peer address has a port, which says nothing about the client
+    let client_ip = req.connection_info().remote().map(|remote| {
+        remote
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().to_string())
+            .unwrap_or_else(|_| remote.to_owned())
+    });
+    Metadata {
+        actor: header("X-Actor"),
+        api_key_id: header("X-Api-Key-Id"),
+        correlation_id: Some(id("X-Correlation-Id").unwrap_or_else(Uuid::new_v4)),
+        causation_id: id("X-Causation-Id"),
+        client_ip,
+        reason: header("X-Reason"),
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct CreateProject {
     pub name: String,
 }
 
-impl Message for CreateProject {
+struct CreateProjectMessage {
+    name: String,
+    metadata: Metadata,
+}
+
+impl Message for CreateProjectMessage {
     type Result = Result<project::Project, AppError>;
 }
 
-impl Handler<CreateProject> for Executor {
+impl Handler<CreateProjectMessage> for Executor {
     type Result = Result<project::Project, AppError>;
 
-    fn handle(&mut self, msg: CreateProject, _: &mut Self::Context) -> Self::Result {
-        let db = &self.db.get().map_err(|e| -> AppError { e.into() })?;
-        db.transaction::<_, AppError, _>(|| {
-            let repository = &mut SqliteRepository { db };
-            let handler = &mut CreateProjectHandler {
-                repository,
-                utc_now: Utc::now,
-            };
-
-            let project = handler
-                .handle(project::CreateProject {
-                    id: Uuid::new_v4(),
-                    name: msg.name,
-                })
-                .map_err(|e| -> AppError { e.into() })?;
-            Ok(project)
+    fn handle(&mut self, msg: CreateProjectMessage, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let projects = &DieselProjects { db };
+                let repository = &mut DieselEventStore::<_, project::Project>::new(db);
+                let handler = &mut CreateProjectHandler {
+                    projects,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let project = handler
+                    .handle(project::CreateProject {
+                        id: Uuid::new_v4(),
+                        name: msg.name,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(project)
+            })
         })
     }
 }
@@ -206,30 +762,32 @@ impl Handler<ListProject> for Executor {
     type Result = Result<project::Project, AppError>;
 
     fn handle(&mut self, msg: ListProject, _: &mut Self::Context) -> Self::Result {
-        let db = &self.db.get().map_err(|e| -> AppError { e.into() })?;
-        db.transaction::<_, AppError, _>(|| {
-            let repository = &mut SqliteRepository { db };
-            let handler = &mut ListProjectHandler { repository };
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let repository = &mut DieselEventStore::<_, project::Project>::new(db);
+                let handler = &mut ListProjectHandler { repository };
 
-            let project = handler
-                .handle(project::ListProject { id: msg.id })
-                .map_err(|e| -> AppError { e.into() })?;
-            Ok(project)
+                let project = handler
+                    .handle(project::ListProject { id: msg.id })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(project)
+            })
         })
     }
 }
 
 // Based on examples: https://github.com/actix/examples/blob/d3a69f0c58f2df583adea59a79969a8c23a03a2a/diesel/src/main.rs
 pub fn create_project(
-    (body, state): (Json<CreateProject>, State<AppState>),
-) -> impl Future<Item = Json<Project>, Error = AppError> {
-    state
+    (body, req): (Json<CreateProject>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    req.state()
         .executor
-        .send(CreateProject {
+        .send(CreateProjectMessage {
             name: body.name.clone(),
+            metadata: request_metadata(&req),
         })
         .from_err()
-        .and_then(|res| res.map(|x| Json(x.into())))
+        .and_then(|res| res.map(|x| with_etag(x.generation, Project::from(x))))
         .responder()
 }
 
@@ -237,6 +795,7 @@ pub fn create_project(
 pub struct Project {
     id: Uuid,
     name: String,
+    archived: bool,
 }
 
 /// Domain Project to DTO Project
@@ -245,44 +804,386 @@ impl From<project::Project> for Project {
         Self {
             id: p.id.into(),
             name: p.name,
+            archived: p.archived,
+        }
+    }
+}
+
+/// Read model Project to DTO Project
+impl From<ProjectSummary> for Project {
+    fn from(p: ProjectSummary) -> Self {
+        Self {
+            id: p.id.into(),
+            name: p.name,
+            archived: p.archived,
         }
     }
 }
 
 pub fn list_project(
     (id, state): (Path<ProjectId>, State<AppState>),
-) -> impl Future<Item = Json<Project>, Error = AppError> {
+) -> impl Future<Item = HttpResponse, Error = AppError> {
     state
         .executor
         .send(ListProject { id: *id })
         .from_err()
-        .and_then(|res| res.map(|x| Json(x.into())))
+        .and_then(|res| res.map(|x| with_etag(x.generation, Project::from(x))))
+        .responder()
+}
+
+/// Projects listed when no limit is given.
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ListProjectsQuery {
+    pub name: Option<String>,
+    pub sort: Option<ProjectSort>,
+    pub order: Option<SortOrder>,
+    pub after: Option<Uuid>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Projects {
+    projects: Vec<Project>,
+    next: Option<Uuid>,
+}
+
+/// Domain ProjectPage to DTO Projects
+impl From<ProjectPage> for Projects {
+    fn from(page: ProjectPage) -> Self {
+        Self {
+            projects: page.projects.into_iter().map(Project::from).collect(),
+            next: page.next.map(Uuid::from),
+        }
+    }
+}
+
+struct ListProjects {
+    query: ListProjectsQuery,
+}
+
+impl Message for ListProjects {
+    type Result = Result<ProjectPage, AppError>;
+}
+
+impl Handler<ListProjects> for Executor {
+    type Result = Result<ProjectPage, AppError>;
+
+    fn handle(&mut self, msg: ListProjects, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let projects = &DieselProjects { db };
+                let handler = &ListProjectsHandler { projects };
+
+                let page = handler
+                    .handle(project::ListProjects {
+                        query: ProjectQuery {
+                            name: msg.query.name,
+                            sort: msg.query.sort.unwrap_or_default(),
+                            order: msg.query.order.unwrap_or_default(),
+                        },
+                        after: msg.query.after.map(ProjectId::from),
+                        limit: msg.query.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(page)
+            })
+        })
+    }
+}
+
+pub fn list_projects(
+    (query, state): (Query<ListProjectsQuery>, State<AppState>),
+) -> impl Future<Item = Json<Projects>, Error = AppError> {
+    state
+        .executor
+        .send(ListProjects {
+            query: query.into_inner(),
+        })
+        .from_err()
+        .and_then(|res| res.map(|page| Json(Projects::from(page))))
+        .responder()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenameProject {
+    pub name: String,
+}
+
+struct UpdateProject {
+    id: ProjectId,
+    action: ProjectAction,
+    expected_generation: Option<Generation>,
+    metadata: Metadata,
+}
+
+impl Message for UpdateProject {
+    type Result = Result<project::Project, AppError>;
+}
+
+impl Handler<UpdateProject> for Executor {
+    type Result = Result<project::Project, AppError>;
+
+    fn handle(&mut self, msg: UpdateProject, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let projects = &DieselProjects { db };
+                let repository = &mut DieselEventStore::<_, project::Project>::new(db);
+                let handler = &mut UpdateProjectHandler {
+                    projects,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let project = handler
+                    .handle(project::UpdateProject {
+                        id: msg.id,
+                        action: msg.action,
+                        expected_generation: msg.expected_generation,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(project)
+            })
+        })
+    }
+}
+
+fn update_project(
+    id: ProjectId,
+    req: &HttpRequest<AppState>,
+    action: ProjectAction,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let executor = req.state().executor.clone();
+    let metadata = request_metadata(req);
+    future::result(if_match(req))
+        .and_then(move |expected_generation| {
+            executor
+                .send(UpdateProject {
+                    id,
+                    action,
+                    expected_generation,
+                    metadata,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation, Project::from(x))))
         .responder()
 }
 
+pub fn rename_project(
+    (id, body, req): (Path<ProjectId>, Json<RenameProject>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_project(*id, &req, ProjectAction::Rename(body.into_inner().name))
+}
+
+pub fn archive_project(
+    (id, req): (Path<ProjectId>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_project(*id, &req, ProjectAction::Archive)
+}
+
+pub fn restore_project(
+    (id, req): (Path<ProjectId>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_project(*id, &req, ProjectAction::Restore)
+}
+
+/// Pool of connections to the database, with every Projection
+/// caught up on events stored before it was added or rebuilt.
+fn pool<C>(
+    builder: Builder<ConnectionManager<C>>,
+    database_url: &str,
+) -> Result<Pool<ConnectionManager<C>>, Error>
+where
+    C: Writer + 'static,
+{
+    let pool = builder.build(ConnectionManager::<C>::new(database_url))?;
+    let db = &pool.get()?;
+    db.write_locked(|| db.catch_up())?;
+    Ok(pool)
+}
+
+/// Executors running commands on the database at `database_url`.
+pub fn start_executor(database_url: &str) -> Result<Addr<Executor>, Error> {
+    let db = match Backend::from_url(database_url) {
+        Backend::Sqlite => {
+            let builder = Pool::builder().connection_customizer(Box::new(BusyTimeout));
+            Database::Sqlite(pool(builder, database_url)?)
+        }
+        #[cfg(feature = "postgres")]
+        Backend::Postgres => Database::Postgres(pool(Pool::builder(), database_url)?),
+        #[cfg(not(feature = "postgres"))]
+        backend => return Err(UnsupportedBackend(backend).into()),
+    };
+    Ok(SyncArbiter::start(3, move || Executor { db: db.clone() }))
+}
+
 pub fn create(
-    db_path: &str,
+    database_url: &str,
 ) -> Result<HttpServer<App<AppState>, impl Fn() -> App<AppState> + Clone>, Error> {
-    let manager = ConnectionManager::<SqliteConnection>::new(db_path);
-    let pool = Pool::builder().build(manager)?;
-    let executor = SyncArbiter::start(3, move || Executor { db: pool.clone() });
+    let executor = start_executor(database_url)?;
+    Scheduler::new(executor.clone()).start();
+    Ok(http_server(executor))
+}
 
-    Ok(server::new(move || {
+/// The service's HTTP API, sending commands to `executor`.
+pub fn http_server(
+    executor: Addr<Executor>,
+) -> HttpServer<App<AppState>, impl Fn() -> App<AppState> + Clone> {
+    server::new(move || {
         App::with_state(AppState {
             executor: executor.clone(),
         })
         .middleware(Logger::default())
+        .resource("/projects", |r| {
+            r.method(Method::GET).with_async(list_projects)
+        })
         .resource("/projects/create", |r| {
             r.method(Method::POST).with_async(create_project)
         })
         .resource("/projects/{id}", |r| {
             r.method(Method::GET).with_async(list_project)
         })
-    }))
+        .resource("/projects/{id}/rename", |r| {
+            r.method(Method::POST).with_async(rename_project)
+        })
+        .resource("/projects/{id}/archive", |r| {
+            r.method(Method::POST).with_async(archive_project)
+        })
+        .resource("/projects/{id}/restore", |r| {
+            r.method(Method::POST).with_async(restore_project)
+        })
+        .resource("/projects/{id}/history", |r| {
+            r.method(Method::GET)
+                .with_async(history::get_project_history)
+        })
+        .resource("/projects/{project_id}/environments/create", |r| {
+            r.method(Method::POST)
+                .with_async(environment::create_environment)
+        })
+        .resource("/projects/{project_id}/environments/{id}", |r| {
+            r.method(Method::GET)
+                .with_async(environment::get_environment)
+        })
+        .resource("/projects/{project_id}/environments/{id}/rename", |r| {
+            r.method(Method::POST)
+                .with_async(environment::rename_environment)
+        })
+        .resource("/projects/{project_id}/environments/{id}/archive", |r| {
+            r.method(Method::POST)
+                .with_async(environment::archive_environment)
+        })
+        .resource("/projects/{project_id}/environments/{id}/toggles", |r| {
+            r.method(Method::GET)
+                .with_async(environment::list_environment_toggles)
+        })
+        .resource("/projects/{project_id}/segments/create", |r| {
+            r.method(Method::POST).with_async(segment::create_segment)
+        })
+        .resource("/projects/{project_id}/segments/{id}", |r| {
+            r.method(Method::GET).with_async(segment::get_segment)
+        })
+        .resource("/projects/{project_id}/segments/{id}/rename", |r| {
+            r.method(Method::POST).with_async(segment::rename_segment)
+        })
+        .resource("/projects/{project_id}/segments/{id}/included", |r| {
+            r.method(Method::POST).with_async(segment::change_included)
+        })
+        .resource("/projects/{project_id}/segments/{id}/excluded", |r| {
+            r.method(Method::POST).with_async(segment::change_excluded)
+        })
+        .resource("/projects/{project_id}/segments/{id}/rules", |r| {
+            r.method(Method::POST).with_async(segment::change_rules)
+        })
+        .resource("/projects/{project_id}/toggles/create", |r| {
+            r.method(Method::POST).with_async(toggle::create_toggle)
+        })
+        .resource("/projects/{project_id}/toggles/{id}", |r| {
+            r.method(Method::GET).with_async(toggle::get_toggle)
+        })
+        .resource(
+            "/projects/{project_id}/toggles/{id}/environments/{environment_id}/enable",
+            |r| r.method(Method::POST).with_async(toggle::enable_toggle),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{id}/environments/{environment_id}/disable",
+            |r| r.method(Method::POST).with_async(toggle::disable_toggle),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{id}/environments/{environment_id}/rollout",
+            |r| r.method(Method::POST).with_async(toggle::change_rollout),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{id}/environments/{environment_id}/rules",
+            |r| r.method(Method::POST).with_async(toggle::change_rules),
+        )
+        .resource("/projects/{project_id}/toggles/{id}/prerequisites", |r| {
+            r.method(Method::POST)
+                .with_async(toggle::change_prerequisites)
+        })
+        .resource("/projects/{project_id}/toggles/{id}/schedules", |r| {
+            r.method(Method::GET).with_async(toggle::list_schedules)
+        })
+        .resource(
+            "/projects/{project_id}/toggles/{id}/schedules/create",
+            |r| r.method(Method::POST).with_async(toggle::schedule_change),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{id}/schedules/{schedule_id}/cancel",
+            |r| r.method(Method::POST).with_async(toggle::cancel_schedule),
+        )
+        .resource("/projects/{project_id}/toggles/{id}/retire", |r| {
+            r.method(Method::POST).with_async(toggle::retire_toggle)
+        })
+        .resource("/projects/{project_id}/toggles/{id}/revive", |r| {
+            r.method(Method::POST).with_async(toggle::revive_toggle)
+        })
+        .resource("/projects/{project_id}/toggles/{id}/revert", |r| {
+            r.method(Method::POST).with_async(toggle::revert_toggle)
+        })
+        .resource("/projects/{project_id}/toggles/{id}/history", |r| {
+            r.method(Method::GET)
+                .with_async(history::get_toggle_history)
+        })
+        .resource("/projects/{project_id}/toggles/{toggle_id}/variants", |r| {
+            r.method(Method::GET).with_async(variant::list_variants)
+        })
+        .resource(
+            "/projects/{project_id}/toggles/{toggle_id}/variants/create",
+            |r| r.method(Method::POST).with_async(variant::create_variant),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{toggle_id}/variants/{id}",
+            |r| r.method(Method::GET).with_async(variant::get_variant),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{toggle_id}/variants/{id}/rename",
+            |r| r.method(Method::POST).with_async(variant::rename_variant),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{toggle_id}/variants/{id}/retire",
+            |r| r.method(Method::POST).with_async(variant::retire_variant),
+        )
+        .resource(
+            "/projects/{project_id}/toggles/{toggle_id}/variants/{id}/revive",
+            |r| r.method(Method::POST).with_async(variant::revive_variant),
+        )
+        .resource("/events", |r| {
+            r.method(Method::GET).with_async(event::read_events)
+        })
+        .resource("/projections/{name}/rebuild", |r| {
+            r.method(Method::POST)
+                .with_async(projection::rebuild_projection)
+        })
+        .resource("/evaluate", |r| {
+            r.method(Method::POST).with_async(evaluation::evaluate)
+        })
+    })
 }
 
 // Failure usage: https://github.com/rust-console/cargo-n64/blob/a4c93f9bb145f3ee8ac6d09e05e8ff4554b68a2d/src/lib.rs#L108-L137
-
 #[cfg(test)]
 mod test {
     use std::fs;
@@ -293,17 +1194,26 @@ mod test {
     use actix_web::http::{Method, StatusCode};
     use actix_web::test::TestServer;
     use actix_web::HttpResponse;
+    #[cfg(feature = "postgres")]
+    use diesel::pg::PgConnection;
     use diesel::prelude::*;
     use diesel::r2d2::{ConnectionManager, Pool};
     use diesel::sqlite::SqliteConnection;
     use failure::Error;
     use tempdir::TempDir;
+    #[cfg(feature = "postgres")]
+    use uuid::Uuid;
 
     use crate::database::models::{Event, NewEvent};
     use crate::database::schema;
     use crate::database::schema::events::dsl::*;
 
-    use super::{create_project, AppState, CreateProject, Executor};
+    #[cfg(feature = "postgres")]
+    use super::projection::RebuiltProjection;
+    use super::toggle::NewToggle;
+    use super::{
+        create_project, AppState, CreateProject, Executor, Project, Projects, RenameProject,
+    };
 
     #[test]
     fn test_create_project() -> Result<(), Error> {
@@ -357,6 +1267,9 @@ mod test {
             created_at: "2019-01-01T12:34:56+00:00",
             type_: "Created",
             data: "{\"Created\":{\"id\":\"936da01f-9abd-4d9d-80c7-02af85c822a8\",\"name\":\"test\"}}",
+            aggregate_type: "project",
+            metadata: "{}",
+            version: 1,
         };
         diesel::insert_into(schema::events::table)
             .values(&event)
@@ -380,7 +1293,262 @@ mod test {
             .send()?;
 
         assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"0\"")),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_project_lifecycle() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8096").unwrap().start();
+            tx.send("127.0.0.1:8096").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: " test".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+
+        let project: Project = client
+            .post(&format!("http://{}/projects/{}/rename", addr, project.id))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .json(&RenameProject {
+                name: "renamed".to_owned(),
+            })
+            .send()?
+            .json()?;
+        assert_eq!(project.name, "renamed");
+
+        let response = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "renamed".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+        let response = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let project: Project = client
+            .post(&format!("http://{}/projects/{}/archive", addr, project.id))
+            .send()?
+            .json()?;
+        assert!(project.archived);
+
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let response = client
+            .post(&format!("http://{}/projects/{}/rename", addr, project.id))
+            .json(&RenameProject {
+                name: "archived".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let project: Project = client
+            .post(&format!("http://{}/projects/{}/restore", addr, project.id))
+            .send()?
+            .json()?;
+        assert!(!project.archived);
+        let response = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_projects() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8097").unwrap().start();
+            tx.send("127.0.0.1:8097").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        for name in &["checkout", "search", "Checkout v2"] {
+            let response = client
+                .post(&format!("http://{}/projects/create", addr))
+                .json(&CreateProject {
+                    name: (*name).to_owned(),
+                })
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+
+        let names = |projects: &Projects| -> Vec<String> {
+            projects.projects.iter().map(|p| p.name.clone()).collect()
+        };
+        let page: Projects = client
+            .get(&format!("http://{}/projects", addr))
+            .query(&[("limit", "2")])
+            .send()?
+            .json()?;
+        assert_eq!(names(&page), vec!["Checkout v2", "checkout"]);
+        let next = page.next.expect("there is a next page").to_string();
+        let page: Projects = client
+            .get(&format!("http://{}/projects", addr))
+            .query(&[("limit", "2"), ("after", &next)])
+            .send()?
+            .json()?;
+        assert_eq!(names(&page), vec!["search"]);
+        assert_eq!(page.next, None);
+
+        let page: Projects = client
+            .get(&format!("http://{}/projects", addr))
+            .query(&[("name", "checkout"), ("sort", "created"), ("order", "desc")])
+            .send()?
+            .json()?;
+        assert_eq!(names(&page), vec!["Checkout v2", "checkout"]);
+
+        let response = client
+            .get(&format!("http://{}/projects", addr))
+            .query(&[("after", "936da01f-9abd-4d9d-80c7-02af85c822a8")])
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    /// The whole service on the database at POSTGRES_TEST_URL, in a schema of
+    /// its own that's dropped once the test is done. Skipped when it isn't set.
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_postgres() -> Result<(), Error> {
+        let url = match std::env::var("POSTGRES_TEST_URL") {
+            Ok(url) => url,
+            Err(_) => return Ok(()),
+        };
+        let schema = format!("test_{}", Uuid::new_v4().to_simple());
+        let db = PgConnection::establish(&url)?;
+        db.execute(&format!("CREATE SCHEMA {}", schema))?;
+        let separator = if url.contains('?') { '&' } else { '?' };
+        let schema_url = format!("{}{}options=-csearch_path%3D{}", url, separator, schema);
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations_postgres");
+        diesel_migrations::run_pending_migrations_in_directory(
+            &PgConnection::establish(&schema_url)?,
+            &migrations,
+            &mut std::io::sink(),
+        )?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::create(&schema_url).unwrap();
+            server.bind("127.0.0.1:8107").unwrap().start();
+            tx.send("127.0.0.1:8107").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        for name in &["checkout", "search", "Checkout v2"] {
+            let response = client
+                .post(&format!("http://{}/projects/create", addr))
+                .json(&CreateProject {
+                    name: (*name).to_owned(),
+                })
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+        let response = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "search".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        // Sorted and filtered as on SQLite
+        let names = |projects: &Projects| -> Vec<String> {
+            projects.projects.iter().map(|p| p.name.clone()).collect()
+        };
+        let page: Projects = client
+            .get(&format!("http://{}/projects", addr))
+            .send()?
+            .json()?;
+        assert_eq!(names(&page), vec!["Checkout v2", "checkout", "search"]);
+        let page: Projects = client
+            .get(&format!("http://{}/projects", addr))
+            .query(&[("name", "CHECKOUT"), ("sort", "created"), ("order", "desc")])
+            .send()?
+            .json()?;
+        assert_eq!(names(&page), vec!["Checkout v2", "checkout"]);
+
+        let rebuilt: RebuiltProjection = client
+            .post(&format!("http://{}/projections/projects/rebuild", addr))
+            .send()?
+            .json()?;
+        assert_eq!(rebuilt.events, 3);
 
+        db.execute(&format!("DROP SCHEMA {} CASCADE", schema))?;
         Ok(())
     }
 }
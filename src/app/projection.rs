@@ -0,0 +1,176 @@
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, Json, Path, State};
+use diesel::sqlite::SqliteConnection;
+use diesel::Connection;
+use futures::Future;
+use serde::{Deserialize, Serialize};
+
+use crate::project::list::DieselProjects;
+use crate::projection::audit::DieselAuditLog;
+use crate::projection::{Projection, Projector};
+use crate::toggle::list::DieselToggles;
+use crate::toggle::schedule::DieselSchedules;
+
+use super::{AppError, AppState, Executor, Writer};
+
+/// A connection to a database holding the app's Projections.
+pub trait Projections: Connection {
+    /// Every Projection kept up to date by the app.
+    fn projections(&self) -> Vec<Box<dyn Projection + '_>>;
+
+    /// Apply events not yet applied to every Projection.
+    fn catch_up(&self) -> Result<(), AppError>;
+
+    /// Start the Projection again from the first event, returning how many events it applied.
+    fn rebuild(&self, projection: &mut dyn Projection) -> Result<usize, AppError>;
+}
+
+/// Implements Projections for a kind of diesel connection.
+macro_rules! impl_projections {
+    ($connection:ty) => {
+        impl Projections for $connection {
+            fn projections(&self) -> Vec<Box<dyn Projection + '_>> {
+                vec![
+                    Box::new(DieselProjects { db: self }),
+                    Box::new(DieselToggles { db: self }),
+                    Box::new(DieselSchedules { db: self }),
+                    Box::new(DieselAuditLog { db: self }),
+                ]
+            }
+
+            fn catch_up(&self) -> Result<(), AppError> {
+                let projector = Projector { db: self };
+                for mut projection in self.projections() {
+                    projector.catch_up(projection.as_mut())?;
+                }
+                Ok(())
+            }
+
+            fn rebuild(&self, projection: &mut dyn Projection) -> Result<usize, AppError> {
+                Ok(Projector { db: self }.rebuild(projection)?)
+            }
+        }
+    };
+}
+
+impl_projections!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_projections!(diesel::pg::PgConnection);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RebuiltProjection {
+    pub name: String,
+    /// Number of events applied.
+    pub events: usize,
+}
+
+struct RebuildProjection {
+    name: String,
+}
+
+impl Message for RebuildProjection {
+    type Result = Result<RebuiltProjection, AppError>;
+}
+
+impl Handler<RebuildProjection> for Executor {
+    type Result = Result<RebuiltProjection, AppError>;
+
+    fn handle(&mut self, msg: RebuildProjection, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.write_locked(|| {
+                let mut projection = db
+                    .projections()
+                    .into_iter()
+                    .find(|projection| projection.name() == msg.name)
+                    .ok_or_else(|| AppError::ProjectionNotFound {
+                        name: msg.name.clone(),
+                    })?;
+                let events = db.rebuild(projection.as_mut())?;
+                Ok(RebuiltProjection {
+                    name: msg.name,
+                    events,
+                })
+            })
+        })
+    }
+}
+
+pub fn rebuild_projection(
+    (name, state): (Path<String>, State<AppState>),
+) -> impl Future<Item = Json<RebuiltProjection>, Error = AppError> {
+    state
+        .executor
+        .send(RebuildProjection {
+            name: name.into_inner(),
+        })
+        .from_err()
+        .and_then(|res| res.map(Json))
+        .responder()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::CreateProject;
+    use super::RebuiltProjection;
+
+    #[test]
+    fn test_rebuild_projection() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8098").unwrap().start();
+            tx.send("127.0.0.1:8098").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        for name in &["checkout", "search"] {
+            let response = client
+                .post(&format!("http://{}/projects/create", addr))
+                .json(&CreateProject {
+                    name: (*name).to_owned(),
+                })
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+
+        let rebuilt: RebuiltProjection = client
+            .post(&format!("http://{}/projections/projects/rebuild", addr))
+            .send()?
+            .json()?;
+        assert_eq!(rebuilt.events, 2);
+        let response = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "search".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        let response = client
+            .post(&format!("http://{}/projections/unknown/rebuild", addr))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+}
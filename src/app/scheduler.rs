@@ -0,0 +1,475 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use actix::{fut, Actor, ActorFuture, Addr, AsyncContext, Context, Handler, Message, WrapFuture};
+use chrono::Utc;
+use failure::Fail;
+use log::error;
+
+use crate::domain::Metadata;
+use crate::environment::Environment;
+use crate::event_store::DieselEventStore;
+use crate::projection::error::ProjectionError;
+use crate::toggle;
+use crate::toggle::error::UpdateToggleHandlerError;
+use crate::toggle::schedule::{DieselSchedules, ScheduleId, Schedules};
+use crate::toggle::{ToggleAction, UpdateToggleHandler};
+use crate::variant::{DieselVariantNames, Variant};
+
+use super::{write_transaction, AppError, Executor};
+
+/// Actor in the Metadata of the changes the Scheduler makes.
+const SCHEDULER: &str = "scheduler";
+
+/// How often to look for ScheduledChanges that are due.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest a stuck ScheduledChange is left alone before being tried again.
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// Background actor making the ScheduledChanges of every Toggle once they
+/// are due. The changes themselves are made by the Executor, so that they
+/// go through the same database connections as any other command.
+pub struct Scheduler {
+    executor: Addr<Executor>,
+    // Whether changes are still being made since an earlier tick, which a
+    // tick leaves alone rather than have two Executors make the same change
+    running: bool,
+    // ScheduledChanges that could neither be made nor failed, with when
+    // to try them again and how long to wait should they still be stuck
+    backoff: HashMap<ScheduleId, (Instant, Duration)>,
+}
+
+impl Scheduler {
+    pub fn new(executor: Addr<Executor>) -> Self {
+        Self {
+            executor,
+            running: false,
+            backoff: HashMap::new(),
+        }
+    }
+
+    /// Back off from the `stuck` ScheduledChanges, twice as long each time
+    /// they are tried in vain, and forget those that got unstuck.
+    fn back_off(&mut self, skipped: &HashSet<ScheduleId>, stuck: Vec<ScheduleId>) {
+        let now = Instant::now();
+        let mut backoff = HashMap::new();
+        for id in stuck {
+            let delay = self
+                .backoff
+                .get(&id)
+                .map_or(INTERVAL, |&(_, delay)| delay * 2)
+                .min(MAX_BACKOFF);
+            backoff.insert(id, (now + delay, delay));
+        }
+        for id in skipped {
+            if let Some(&entry) = self.backoff.get(id) {
+                backoff.insert(*id, entry);
+            }
+        }
+        self.backoff = backoff;
+    }
+}
+
+impl Actor for Scheduler {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(INTERVAL, |scheduler, ctx| {
+            if scheduler.running {
+                return;
+            }
+            scheduler.running = true;
+            let now = Instant::now();
+            let skip: HashSet<_> = scheduler
+                .backoff
+                .iter()
+                .filter(|(_, &(until, _))| until > now)
+                .map(|(&id, _)| id)
+                .collect();
+            ctx.spawn(
+                scheduler
+                    .executor
+                    .send(ExecuteSchedules { skip: skip.clone() })
+                    .into_actor(scheduler)
+                    .then(move |result, scheduler, _| {
+                        scheduler.running = false;
+                        match result {
+                            Ok(Err(e)) => error!("failed to execute scheduled changes: {}", e),
+                            Err(e) => error!("failed to execute scheduled changes: {}", e),
+                            Ok(Ok(executed)) => scheduler.back_off(&skip, executed.stuck),
+                        }
+                        fut::ok(())
+                    }),
+            );
+        });
+    }
+}
+
+/// Make every ScheduledChange that is due, other than those to `skip`.
+pub struct ExecuteSchedules {
+    pub skip: HashSet<ScheduleId>,
+}
+
+/// What came of executing the ScheduledChanges that were due.
+#[derive(Debug, Default)]
+pub struct Executed {
+    /// How many changes were made.
+    pub made: usize,
+    /// Changes that could neither be made nor failed, such as those of a
+    /// Toggle whose events can't be read, which are left pending.
+    pub stuck: Vec<ScheduleId>,
+}
+
+impl Message for ExecuteSchedules {
+    type Result = Result<Executed, AppError>;
+}
+
+/// Whether a change that failed with `e` could be made if tried again,
+/// rather than failing the same way on every tick.
+fn is_transient(e: &AppError) -> bool {
+    match e {
+        AppError::DatabasePoolError(_) | AppError::DatabaseError(_) | AppError::MailboxError(_) => {
+            true
+        }
+        AppError::ProjectionError(e) => matches!(e, ProjectionError::DatabaseError(_)),
+        AppError::UpdateToggleError(e) => match e {
+            UpdateToggleHandlerError::ConcurrencyConflict { .. }
+            | UpdateToggleHandlerError::VariantNamesError(_) => true,
+            UpdateToggleHandlerError::EnvironmentRepositoryError(e) => e.is_transient(),
+            UpdateToggleHandlerError::VariantRepositoryError(e) => e.is_transient(),
+            UpdateToggleHandlerError::RepositoryError(e) => e.is_transient(),
+            UpdateToggleHandlerError::NotFoundError | UpdateToggleHandlerError::ToggleError(_) => {
+                false
+            }
+        },
+        _ => false,
+    }
+}
+
+impl Handler<ExecuteSchedules> for Executor {
+    type Result = Result<Executed, AppError>;
+
+    fn handle(&mut self, msg: ExecuteSchedules, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            // Update a Toggle on behalf of one of its schedules
+            let update = |project_id, toggle_id, schedule_id: ScheduleId, action| {
+                let environments = &DieselEventStore::<_, Environment>::new(db);
+                let names = &DieselVariantNames { db };
+                let variants = &DieselEventStore::<_, Variant>::new(db);
+                let repository = &mut DieselEventStore::<_, toggle::Toggle>::new(db);
+                let handler = &mut UpdateToggleHandler {
+                    environments,
+                    names,
+                    variants,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                handler
+                    .handle(toggle::UpdateToggle {
+                        project_id,
+                        id: toggle_id,
+                        action,
+                        expected_generation: None,
+                        // Made because of the schedule, not by whoever scheduled it
+                        metadata: Metadata {
+                            actor: Some(SCHEDULER.to_owned()),
+                            causation_id: Some(schedule_id.into()),
+                            ..Metadata::default()
+                        },
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(())
+            };
+
+            let due = DieselSchedules { db }.due(Utc::now())?;
+            let mut executed = Executed::default();
+            for (project_id, toggle_id, schedule_id) in due {
+                if msg.skip.contains(&schedule_id) {
+                    continue;
+                }
+                // A transaction each, so one failing change doesn't hold back the others
+                let result = write_transaction(db, || {
+                    let action = ToggleAction::ExecuteSchedule(schedule_id);
+                    update(project_id, toggle_id, schedule_id, action)
+                });
+                match result {
+                    Ok(()) => executed.made += 1,
+                    // Left pending, to be tried again on the next tick
+                    Err(e) if is_transient(&e) => error!(
+                        "failed to execute scheduled change {}: {}",
+                        schedule_id.to_string(),
+                        e
+                    ),
+                    // The change can't be made, such as enabling a Toggle retired
+                    // since, so it is failed rather than tried again every tick
+                    Err(e) => {
+                        let reason = (&e as &dyn Fail).find_root_cause().to_string();
+                        error!(
+                            "scheduled change {} can no longer be made: {}",
+                            schedule_id.to_string(),
+                            reason
+                        );
+                        let failed = write_transaction(db, || {
+                            let action = ToggleAction::FailSchedule(schedule_id, reason);
+                            update(project_id, toggle_id, schedule_id, action)
+                        });
+                        if let Err(e) = failed {
+                            error!(
+                                "failed to fail scheduled change {}: {}",
+                                schedule_id.to_string(),
+                                e
+                            );
+                            if !is_transient(&e) {
+                                executed.stuck.push(schedule_id);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(executed)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::sync::mpsc;
+
+    use chrono::{Timelike, Utc};
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use diesel::RunQueryDsl;
+    use failure::Error;
+    use futures::Future;
+    use tempdir::TempDir;
+
+    use crate::toggle::schedule::{Change, ScheduledChange};
+
+    use super::super::environment::{Environment, NewEnvironment};
+    use super::super::toggle::{NewSchedule, NewToggle, Toggle};
+    use super::super::{CreateProject, Project};
+    use super::{ExecuteSchedules, Scheduler, INTERVAL};
+
+    #[test]
+    fn test_scheduled_changes() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        // No Scheduler, the test telling the Executor when to make the changes
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let executor = super::super::start_executor(db_path.to_str().unwrap()).unwrap();
+            let server = super::super::http_server(executor.clone());
+            server.bind("127.0.0.1:8095").unwrap().start();
+            tx.send(("127.0.0.1:8095", executor)).unwrap();
+            let _ = sys.run();
+        });
+
+        let (addr, executor) = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let environment: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .json(&NewEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let url = format!(
+            "http://{}/projects/{}/toggles/{}",
+            addr, project.id, toggle.id
+        );
+        let schedule = |seconds: i64, change: Change| NewSchedule {
+            environment_id: environment.id.into(),
+            at: Utc::now() + chrono::Duration::seconds(seconds),
+            change,
+        };
+
+        let response = client
+            .post(&format!("{}/schedules/create", url))
+            .json(&schedule(-1, Change::Enable))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        for (seconds, change) in &[(1, Change::Enable), (3600, Change::Disable)] {
+            let response = client
+                .post(&format!("{}/schedules/create", url))
+                .json(&schedule(*seconds, *change))
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+        let schedules: Vec<ScheduledChange> =
+            client.get(&format!("{}/schedules", url)).send()?.json()?;
+        assert_eq!(schedules.len(), 2);
+
+        let response = client
+            .post(&format!(
+                "{}/schedules/{}/cancel",
+                url,
+                schedules[1].id.to_string()
+            ))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        // Due by the next whole second, schedules being looked up by the second
+        let due = schedules[0].at.with_nanosecond(0).unwrap() + chrono::Duration::seconds(1);
+        std::thread::sleep((due - Utc::now()).to_std().unwrap_or_default());
+        assert_eq!(
+            executor
+                .send(ExecuteSchedules {
+                    skip: HashSet::new()
+                })
+                .wait()??
+                .made,
+            1
+        );
+        assert_eq!(
+            executor
+                .send(ExecuteSchedules {
+                    skip: HashSet::new()
+                })
+                .wait()??
+                .made,
+            0
+        );
+
+        let toggle: Toggle = client.get(&url).send()?.json()?;
+        assert!(toggle.schedules.is_empty());
+        assert!(toggle.environments[&environment.id].enabled);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stuck_schedules() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let executor = super::super::start_executor(db_path.to_str().unwrap()).unwrap();
+            let server = super::super::http_server(executor.clone());
+            server.bind("127.0.0.1:8108").unwrap().start();
+            tx.send(("127.0.0.1:8108", executor)).unwrap();
+            let _ = sys.run();
+        });
+
+        let (addr, executor) = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let environment: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .json(&NewEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let url = format!(
+            "http://{}/projects/{}/toggles/{}",
+            addr, project.id, toggle.id
+        );
+        let response = client
+            .post(&format!("{}/schedules/create", url))
+            .json(&NewSchedule {
+                environment_id: environment.id.into(),
+                at: Utc::now() + chrono::Duration::seconds(1),
+                change: Change::Enable,
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let schedules: Vec<ScheduledChange> =
+            client.get(&format!("{}/schedules", url)).send()?.json()?;
+        let id = schedules[0].id;
+
+        // Neither the change nor failing it can be stored without reading the Toggle
+        diesel::sql_query("UPDATE events SET data = 'garbage' WHERE aggregate_type = 'toggle'")
+            .execute(&db)?;
+
+        let due = schedules[0].at.with_nanosecond(0).unwrap() + chrono::Duration::seconds(1);
+        std::thread::sleep((due - Utc::now()).to_std().unwrap_or_default());
+        let executed = executor
+            .send(ExecuteSchedules {
+                skip: HashSet::new(),
+            })
+            .wait()??;
+        assert_eq!(executed.made, 0);
+        assert_eq!(executed.stuck, vec![id]);
+
+        let skip: HashSet<_> = vec![id].into_iter().collect();
+        let executed = executor
+            .send(ExecuteSchedules { skip: skip.clone() })
+            .wait()??;
+        assert!(executed.stuck.is_empty());
+
+        // Backed off twice as long each time it is still stuck, and
+        // neither forgotten while skipped nor kept once unstuck
+        let mut scheduler = Scheduler::new(executor);
+        scheduler.back_off(&HashSet::new(), vec![id]);
+        assert_eq!(scheduler.backoff[&id].1, INTERVAL);
+        scheduler.back_off(&HashSet::new(), vec![id]);
+        assert_eq!(scheduler.backoff[&id].1, INTERVAL * 2);
+        scheduler.back_off(&skip, vec![]);
+        assert_eq!(scheduler.backoff[&id].1, INTERVAL * 2);
+        scheduler.back_off(&HashSet::new(), vec![]);
+        assert!(scheduler.backoff.is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,386 @@
+use std::collections::BTreeSet;
+
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, HttpRequest, HttpResponse, Json, Path, State};
+use chrono::Utc;
+use diesel::Connection;
+use futures::{future, Future};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{Generation, Metadata};
+use crate::event_store::DieselEventStore;
+use crate::project::{Project, ProjectId};
+use crate::segment;
+use crate::segment::{
+    CreateSegmentHandler, GetSegmentHandler, SegmentAction, SegmentId, SegmentRule,
+    UpdateSegmentHandler,
+};
+
+use super::{
+    if_match, request_metadata, with_etag, write_transaction, AppError, AppState, Executor,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewSegment {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenameSegment {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SegmentKeys {
+    pub keys: BTreeSet<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SegmentRules {
+    pub rules: Vec<SegmentRule>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Segment {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub included: BTreeSet<String>,
+    pub excluded: BTreeSet<String>,
+    pub rules: Vec<SegmentRule>,
+}
+
+/// Domain Segment to DTO Segment
+impl From<segment::Segment> for Segment {
+    fn from(s: segment::Segment) -> Self {
+        Self {
+            id: s.id.into(),
+            project_id: s.project_id.into(),
+            name: s.name,
+            included: s.included,
+            excluded: s.excluded,
+            rules: s.rules,
+        }
+    }
+}
+
+struct CreateSegment {
+    project_id: ProjectId,
+    name: String,
+    metadata: Metadata,
+}
+
+impl Message for CreateSegment {
+    type Result = Result<segment::Segment, AppError>;
+}
+
+impl Handler<CreateSegment> for Executor {
+    type Result = Result<segment::Segment, AppError>;
+
+    fn handle(&mut self, msg: CreateSegment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let projects = &DieselEventStore::<_, Project>::new(db);
+                let repository = &mut DieselEventStore::<_, segment::Segment>::new(db);
+                let handler = &mut CreateSegmentHandler {
+                    projects,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let segment = handler
+                    .handle(segment::CreateSegment {
+                        id: Uuid::new_v4(),
+                        project_id: msg.project_id,
+                        name: msg.name,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(segment)
+            })
+        })
+    }
+}
+
+struct GetSegment {
+    project_id: ProjectId,
+    id: SegmentId,
+}
+
+impl Message for GetSegment {
+    type Result = Result<segment::Segment, AppError>;
+}
+
+impl Handler<GetSegment> for Executor {
+    type Result = Result<segment::Segment, AppError>;
+
+    fn handle(&mut self, msg: GetSegment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let repository = &DieselEventStore::<_, segment::Segment>::new(db);
+                let handler = &GetSegmentHandler { repository };
+
+                let segment = handler
+                    .handle(segment::GetSegment {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(segment)
+            })
+        })
+    }
+}
+
+struct UpdateSegment {
+    project_id: ProjectId,
+    id: SegmentId,
+    action: SegmentAction,
+    expected_generation: Option<Generation>,
+    metadata: Metadata,
+}
+
+impl Message for UpdateSegment {
+    type Result = Result<segment::Segment, AppError>;
+}
+
+impl Handler<UpdateSegment> for Executor {
+    type Result = Result<segment::Segment, AppError>;
+
+    fn handle(&mut self, msg: UpdateSegment, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let repository = &mut DieselEventStore::<_, segment::Segment>::new(db);
+                let handler = &mut UpdateSegmentHandler {
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let segment = handler
+                    .handle(segment::UpdateSegment {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                        action: msg.action,
+                        expected_generation: msg.expected_generation,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(segment)
+            })
+        })
+    }
+}
+
+pub fn create_segment(
+    (project_id, body, req): (Path<ProjectId>, Json<NewSegment>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    req.state()
+        .executor
+        .send(CreateSegment {
+            project_id: *project_id,
+            name: body.name.clone(),
+            metadata: request_metadata(&req),
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Segment::from(x))))
+        .responder()
+}
+
+pub fn get_segment(
+    (path, state): (Path<(ProjectId, SegmentId)>, State<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    state
+        .executor
+        .send(GetSegment {
+            project_id: path.0,
+            id: path.1,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Segment::from(x))))
+        .responder()
+}
+
+fn update_segment(
+    path: &(ProjectId, SegmentId),
+    req: &HttpRequest<AppState>,
+    action: SegmentAction,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let (project_id, id) = *path;
+    let executor = req.state().executor.clone();
+    let metadata = request_metadata(req);
+    future::result(if_match(req))
+        .and_then(move |expected_generation| {
+            executor
+                .send(UpdateSegment {
+                    project_id,
+                    id,
+                    action,
+                    expected_generation,
+                    metadata,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation, Segment::from(x))))
+        .responder()
+}
+
+pub fn rename_segment(
+    (path, body, req): (
+        Path<(ProjectId, SegmentId)>,
+        Json<RenameSegment>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_segment(&path, &req, SegmentAction::Rename(body.into_inner().name))
+}
+
+pub fn change_included(
+    (path, body, req): (
+        Path<(ProjectId, SegmentId)>,
+        Json<SegmentKeys>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_segment(
+        &path,
+        &req,
+        SegmentAction::ChangeIncluded(body.into_inner().keys),
+    )
+}
+
+pub fn change_excluded(
+    (path, body, req): (
+        Path<(ProjectId, SegmentId)>,
+        Json<SegmentKeys>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_segment(
+        &path,
+        &req,
+        SegmentAction::ChangeExcluded(body.into_inner().keys),
+    )
+}
+
+pub fn change_rules(
+    (path, body, req): (
+        Path<(ProjectId, SegmentId)>,
+        Json<SegmentRules>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_segment(
+        &path,
+        &req,
+        SegmentAction::ChangeRules(body.into_inner().rules),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::{CreateProject, Project};
+    use super::{NewSegment, Segment, SegmentKeys, SegmentRules};
+    use crate::segment::SegmentRule;
+    use crate::toggle::rule::{Clause, Operator};
+
+    #[test]
+    fn test_segment_lifecycle() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8094").unwrap().start();
+            tx.send("127.0.0.1:8094").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+
+        let segment: Segment = client
+            .post(&format!(
+                "http://{}/projects/{}/segments/create",
+                addr, project.id
+            ))
+            .json(&NewSegment {
+                name: "beta testers".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let url = format!(
+            "http://{}/projects/{}/segments/{}",
+            addr, project.id, segment.id
+        );
+
+        let response = client
+            .post(&format!("{}/included", url))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .json(&SegmentKeys {
+                keys: vec!["alice".to_owned(), "bob".to_owned()]
+                    .into_iter()
+                    .collect(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .post(&format!("{}/excluded", url))
+            .json(&SegmentKeys {
+                keys: vec!["bob".to_owned()].into_iter().collect(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .post(&format!("{}/rules", url))
+            .json(&SegmentRules {
+                rules: vec![SegmentRule {
+                    clauses: vec![Clause {
+                        attribute: "user_id".to_owned(),
+                        operator: Operator::InSegment,
+                        values: vec![segment.id.to_string()],
+                    }],
+                }],
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let mut response = client.get(&url).send()?;
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"2\"")),
+        );
+        let segment: Segment = response.json()?;
+        assert_eq!(segment.name, "beta testers");
+        assert!(segment.included.contains("alice"));
+        assert!(segment.excluded.contains("bob"));
+        assert!(segment.rules.is_empty());
+
+        Ok(())
+    }
+}
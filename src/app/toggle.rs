@@ -0,0 +1,790 @@
+use std::collections::HashMap;
+
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, HttpRequest, HttpResponse, Json, Path, Query, State};
+use chrono::{DateTime, Utc};
+use diesel::Connection;
+use futures::{future, Future};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{Aggregate, Generation, Metadata, PointInTime};
+use crate::environment::{Environment, EnvironmentId};
+use crate::event_store::DieselEventStore;
+use crate::project::{Project, ProjectId};
+use crate::toggle;
+use crate::toggle::rule::Rule;
+use crate::toggle::schedule::{Change, ScheduleId, ScheduledChange};
+use crate::toggle::{
+    CreateToggleHandler, GetToggleHandler, Prerequisite, ToggleAction, ToggleId,
+    UpdateToggleHandler,
+};
+use crate::variant::{DieselVariantNames, Variant};
+
+use super::{
+    if_match, request_metadata, with_etag, write_transaction, AppError, AppState, Executor,
+    PointInTimeQuery,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewToggle {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rollout {
+    pub percentage: u8,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Prerequisites {
+    pub prerequisites: Vec<Prerequisite>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewSchedule {
+    pub environment_id: EnvironmentId,
+    pub at: DateTime<Utc>,
+    pub change: Change,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Revert {
+    pub generation: i32,
+    /// Only revert the Toggle within this Environment.
+    pub environment_id: Option<EnvironmentId>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Toggle {
+    pub id: Uuid,
+    pub project_id: Uuid,
+    pub name: String,
+    pub environments: HashMap<Uuid, ToggleEnvironment>,
+    pub prerequisites: Vec<Prerequisite>,
+    pub schedules: Vec<ScheduledChange>,
+    pub retired: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ToggleEnvironment {
+    pub enabled: bool,
+    pub rollout: Option<u8>,
+    pub rules: Vec<Rule>,
+}
+
+/// Domain Toggle to DTO Toggle
+impl From<toggle::Toggle> for Toggle {
+    fn from(t: toggle::Toggle) -> Self {
+        Self {
+            id: (*t.id()).into(),
+            project_id: t.project_id().into(),
+            name: t.name().to_owned(),
+            environments: t
+                .environments()
+                .iter()
+                .map(|(id, state)| {
+                    let environment = ToggleEnvironment {
+                        enabled: state.enabled,
+                        rollout: state.rollout,
+                        rules: state.rules.clone(),
+                    };
+                    ((*id).into(), environment)
+                })
+                .collect(),
+            prerequisites: t.prerequisites().to_vec(),
+            schedules: t.schedules().to_vec(),
+            retired: t.retired(),
+        }
+    }
+}
+
+struct CreateToggle {
+    project_id: ProjectId,
+    name: String,
+    metadata: Metadata,
+}
+
+impl Message for CreateToggle {
+    type Result = Result<toggle::Toggle, AppError>;
+}
+
+impl Handler<CreateToggle> for Executor {
+    type Result = Result<toggle::Toggle, AppError>;
+
+    fn handle(&mut self, msg: CreateToggle, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let projects = &DieselEventStore::<_, Project>::new(db);
+                let repository = &mut DieselEventStore::<_, toggle::Toggle>::new(db);
+                let handler = &mut CreateToggleHandler {
+                    projects,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let toggle = handler
+                    .handle(toggle::CreateToggle {
+                        id: Uuid::new_v4(),
+                        project_id: msg.project_id,
+                        name: msg.name,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(toggle)
+            })
+        })
+    }
+}
+
+struct GetToggle {
+    project_id: ProjectId,
+    id: ToggleId,
+    as_of: Option<PointInTime>,
+}
+
+impl Message for GetToggle {
+    type Result = Result<toggle::Toggle, AppError>;
+}
+
+impl Handler<GetToggle> for Executor {
+    type Result = Result<toggle::Toggle, AppError>;
+
+    fn handle(&mut self, msg: GetToggle, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let repository = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let handler = &GetToggleHandler { repository };
+
+                let toggle = handler
+                    .handle(toggle::GetToggle {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                        as_of: msg.as_of,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(toggle)
+            })
+        })
+    }
+}
+
+struct UpdateToggle {
+    project_id: ProjectId,
+    id: ToggleId,
+    action: ToggleAction,
+    expected_generation: Option<Generation>,
+    metadata: Metadata,
+}
+
+impl Message for UpdateToggle {
+    type Result = Result<toggle::Toggle, AppError>;
+}
+
+impl Handler<UpdateToggle> for Executor {
+    type Result = Result<toggle::Toggle, AppError>;
+
+    fn handle(&mut self, msg: UpdateToggle, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let environments = &DieselEventStore::<_, Environment>::new(db);
+                let names = &DieselVariantNames { db };
+                let variants = &DieselEventStore::<_, Variant>::new(db);
+                let repository = &mut DieselEventStore::<_, toggle::Toggle>::new(db);
+                let handler = &mut UpdateToggleHandler {
+                    environments,
+                    names,
+                    variants,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let toggle = handler
+                    .handle(toggle::UpdateToggle {
+                        project_id: msg.project_id,
+                        id: msg.id,
+                        action: msg.action,
+                        expected_generation: msg.expected_generation,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(toggle)
+            })
+        })
+    }
+}
+
+pub fn create_toggle(
+    (project_id, body, req): (Path<ProjectId>, Json<NewToggle>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    req.state()
+        .executor
+        .send(CreateToggle {
+            project_id: *project_id,
+            name: body.name.clone(),
+            metadata: request_metadata(&req),
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation(), Toggle::from(x))))
+        .responder()
+}
+
+pub fn get_toggle(
+    (path, query, state): (
+        Path<(ProjectId, ToggleId)>,
+        Query<PointInTimeQuery>,
+        State<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let (project_id, id) = *path;
+    future::result(query.point_in_time())
+        .and_then(move |as_of| {
+            state
+                .executor
+                .send(GetToggle {
+                    project_id,
+                    id,
+                    as_of,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation(), Toggle::from(x))))
+        .responder()
+}
+
+pub fn list_schedules(
+    (path, state): (Path<(ProjectId, ToggleId)>, State<AppState>),
+) -> impl Future<Item = Json<Vec<ScheduledChange>>, Error = AppError> {
+    state
+        .executor
+        .send(GetToggle {
+            project_id: path.0,
+            id: path.1,
+            as_of: None,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| Json(x.schedules().to_vec())))
+        .responder()
+}
+
+fn update_toggle(
+    path: &(ProjectId, ToggleId),
+    req: &HttpRequest<AppState>,
+    action: ToggleAction,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle_with(path, req, action, request_metadata(req))
+}
+
+fn update_toggle_with(
+    path: &(ProjectId, ToggleId),
+    req: &HttpRequest<AppState>,
+    action: ToggleAction,
+    metadata: Metadata,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let (project_id, id) = *path;
+    let executor = req.state().executor.clone();
+    future::result(if_match(req))
+        .and_then(move |expected_generation| {
+            executor
+                .send(UpdateToggle {
+                    project_id,
+                    id,
+                    action,
+                    expected_generation,
+                    metadata,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation(), Toggle::from(x))))
+        .responder()
+}
+
+pub fn enable_toggle(
+    (path, req): (
+        Path<(ProjectId, ToggleId, EnvironmentId)>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(&(path.0, path.1), &req, ToggleAction::Enable(path.2))
+}
+
+pub fn disable_toggle(
+    (path, req): (
+        Path<(ProjectId, ToggleId, EnvironmentId)>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(&(path.0, path.1), &req, ToggleAction::Disable(path.2))
+}
+
+pub fn change_rollout(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId, EnvironmentId)>,
+        Json<Rollout>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(
+        &(path.0, path.1),
+        &req,
+        ToggleAction::ChangeRollout(path.2, body.percentage),
+    )
+}
+
+pub fn change_rules(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId, EnvironmentId)>,
+        Json<Rules>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(
+        &(path.0, path.1),
+        &req,
+        ToggleAction::ChangeRules(path.2, body.into_inner().rules),
+    )
+}
+
+pub fn change_prerequisites(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId)>,
+        Json<Prerequisites>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(
+        &path,
+        &req,
+        ToggleAction::ChangePrerequisites(body.into_inner().prerequisites),
+    )
+}
+
+pub fn schedule_change(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId)>,
+        Json<NewSchedule>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let schedule = ScheduledChange {
+        id: ScheduleId::from(Uuid::new_v4()),
+        environment_id: body.environment_id,
+        at: body.at,
+        change: body.change,
+    };
+    update_toggle(&path, &req, ToggleAction::ScheduleChange(schedule))
+}
+
+pub fn cancel_schedule(
+    (path, req): (
+        Path<(ProjectId, ToggleId, ScheduleId)>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(
+        &(path.0, path.1),
+        &req,
+        ToggleAction::CancelSchedule(path.2),
+    )
+}
+
+pub fn retire_toggle(
+    (path, req): (Path<(ProjectId, ToggleId)>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(&path, &req, ToggleAction::Retire)
+}
+
+pub fn revive_toggle(
+    (path, req): (Path<(ProjectId, ToggleId)>, HttpRequest<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_toggle(&path, &req, ToggleAction::Revive)
+}
+
+pub fn revert_toggle(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId)>,
+        Json<Revert>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let mut metadata = request_metadata(&req);
+    // Reverts always say why, in the client's own words when it gives any
+    metadata.reason = body
+        .reason
+        .clone()
+        .or(metadata.reason)
+        .or_else(|| Some(format!("revert to generation {}", body.generation)));
+    update_toggle_with(
+        &path,
+        &req,
+        ToggleAction::Revert(body.generation.into(), body.environment_id),
+        metadata,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use super::super::environment::{Environment, NewEnvironment};
+    use super::super::{CreateProject, Project};
+    use crate::history::HistoryEntry;
+    use crate::toggle::rule::{Clause, Operator, Rule};
+    use crate::toggle::Prerequisite;
+
+    use super::{NewToggle, Prerequisites, Revert, Rollout, Rules, Toggle};
+
+    /// A server on a database of its own, with a Project that has
+    /// a staging and a production Environment and a Toggle.
+    struct Fixture {
+        // Removed along with the database once the test is done
+        _tmpdir: TempDir,
+        addr: &'static str,
+        client: reqwest::Client,
+        project: Project,
+        staging: Environment,
+        production: Environment,
+        toggle: Toggle,
+    }
+
+    impl Fixture {
+        fn start(addr: &'static str) -> Result<Self, Error> {
+            let tmpdir = TempDir::new("db")?;
+
+            let db_path = tmpdir.path().join("db.sqlite");
+            let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+            let pool = Pool::builder().build(manager)?;
+            let db = pool.get()?;
+            diesel_migrations::run_pending_migrations(&db)?;
+
+            let (tx, rx) = mpsc::channel();
+
+            std::thread::spawn(move || {
+                let sys = actix::System::new("test-feature-toggler");
+                let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+                server.bind(addr).unwrap().start();
+                tx.send(addr).unwrap();
+                let _ = sys.run();
+            });
+
+            let addr = rx.recv()?;
+
+            let client = reqwest::Client::new();
+            let project: Project = client
+                .post(&format!("http://{}/projects/create", addr))
+                .json(&CreateProject {
+                    name: "test".to_owned(),
+                })
+                .send()?
+                .json()?;
+            let environment = |name: &str| -> Result<Environment, Error> {
+                Ok(client
+                    .post(&format!(
+                        "http://{}/projects/{}/environments/create",
+                        addr, project.id
+                    ))
+                    .json(&NewEnvironment {
+                        name: name.to_owned(),
+                    })
+                    .send()?
+                    .json()?)
+            };
+            let staging = environment("staging")?;
+            let production = environment("production")?;
+            let toggle: Toggle = client
+                .post(&format!(
+                    "http://{}/projects/{}/toggles/create",
+                    addr, project.id
+                ))
+                .json(&NewToggle {
+                    name: "test".to_owned(),
+                })
+                .send()?
+                .json()?;
+            assert!(toggle.environments.is_empty());
+
+            Ok(Fixture {
+                _tmpdir: tmpdir,
+                addr,
+                client,
+                project,
+                staging,
+                production,
+                toggle,
+            })
+        }
+
+        /// URL of the Toggle, followed by `path`.
+        fn url(&self, path: &str) -> String {
+            format!(
+                "http://{}/projects/{}/toggles/{}{}",
+                self.addr, self.project.id, self.toggle.id, path
+            )
+        }
+
+        /// URL of the Toggle within an Environment, followed by `path`.
+        fn environment_url(&self, environment: &Environment, path: &str) -> String {
+            self.url(&format!("/environments/{}{}", environment.id, path))
+        }
+
+        /// Enable the Toggle in staging, then change its rollout and rules in
+        /// production, taking it to generations 1, 2 and 3.
+        fn change(&self) -> Result<(), Error> {
+            let responses = vec![
+                self.client
+                    .post(&self.environment_url(&self.staging, "/enable"))
+                    .send()?,
+                self.client
+                    .post(&self.environment_url(&self.production, "/rollout"))
+                    .json(&Rollout { percentage: 25 })
+                    .send()?,
+                self.client
+                    .post(&self.environment_url(&self.production, "/rules"))
+                    .json(&Rules {
+                        rules: vec![rule("@ourcorp\\.com$")],
+                    })
+                    .send()?,
+            ];
+            for response in responses {
+                assert_eq!(response.status(), reqwest::StatusCode::OK);
+            }
+            Ok(())
+        }
+    }
+
+    fn rule(pattern: &str) -> Rule {
+        Rule {
+            clauses: vec![Clause {
+                attribute: "email".to_owned(),
+                operator: Operator::Matches,
+                values: vec![pattern.to_owned()],
+            }],
+            variant: "on".to_owned(),
+            variant_id: None,
+        }
+    }
+
+    #[test]
+    fn test_enable_and_disable() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8090")?;
+        let client = &fixture.client;
+        let (staging, production) = (&fixture.staging, &fixture.production);
+
+        let response = client
+            .post(&fixture.environment_url(staging, "/enable"))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let mut response = client.get(&fixture.url("")).send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"1\"")),
+        );
+        let toggle: Toggle = response.json()?;
+        assert!(toggle.environments[&staging.id].enabled);
+        assert!(!toggle.environments.contains_key(&production.id));
+
+        let response = client
+            .post(&fixture.environment_url(staging, "/disable"))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::PRECONDITION_FAILED);
+
+        let response = client
+            .post(&fixture.environment_url(staging, "/enable"))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let response = client
+            .post(&fixture.url(&format!("/environments/{}/enable", uuid::Uuid::new_v4())))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollout() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8102")?;
+        let client = &fixture.client;
+        let production = &fixture.production;
+        let rollout = fixture.environment_url(production, "/rollout");
+
+        let response = client
+            .post(&rollout)
+            .json(&Rollout { percentage: 101 })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let toggle: Toggle = client
+            .post(&rollout)
+            .json(&Rollout { percentage: 25 })
+            .send()?
+            .json()?;
+        assert_eq!(toggle.environments[&production.id].rollout, Some(25));
+        assert!(!toggle.environments[&production.id].enabled);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rules() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8103")?;
+        let client = &fixture.client;
+        let production = &fixture.production;
+        let rules = fixture.environment_url(production, "/rules");
+
+        let response = client
+            .post(&rules)
+            .json(&Rules {
+                rules: vec![rule("(")],
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let toggle: Toggle = client
+            .post(&rules)
+            .json(&Rules {
+                rules: vec![rule("@ourcorp\\.com$")],
+            })
+            .send()?
+            .json()?;
+        assert_eq!(
+            toggle.environments[&production.id].rules,
+            vec![rule("@ourcorp\\.com$")],
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_point_in_time() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8104")?;
+        let client = &fixture.client;
+        let production = &fixture.production;
+        fixture.change()?;
+
+        let url = fixture.url("");
+        let mut response = client.get(&format!("{}?generation=2", url)).send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"2\"")),
+        );
+        let before: Toggle = response.json()?;
+        assert_eq!(before.environments[&production.id].rollout, Some(25));
+        assert!(before.environments[&production.id].rules.is_empty());
+        let response = client.get(&format!("{}?generation=9", url)).send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        let response = client
+            .get(&format!("{}?as_of=2000-01-01T00:00:00Z", url))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        let response = client
+            .get(&format!("{}?as_of=2000-01-01T00:00:00Z&generation=2", url))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8105")?;
+        let client = &fixture.client;
+        let (staging, production) = (&fixture.staging, &fixture.production);
+        fixture.change()?;
+
+        let revert = |generation| Revert {
+            generation,
+            environment_id: None,
+            reason: None,
+        };
+        let reverted: Toggle = client
+            .post(&fixture.url("/revert"))
+            .json(&revert(2))
+            .send()?
+            .json()?;
+        assert!(reverted.environments[&production.id].rules.is_empty());
+        assert_eq!(reverted.environments[&production.id].rollout, Some(25));
+        assert!(reverted.environments[&staging.id].enabled);
+        let entries: Vec<HistoryEntry> = client.get(&fixture.url("/history")).send()?.json()?;
+        let reason = entries
+            .last()
+            .and_then(|entry| entry.metadata.reason.clone());
+        assert_eq!(reason, Some("revert to generation 2".to_owned()));
+        let response = client
+            .post(&fixture.url("/revert"))
+            .json(&revert(4))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prerequisites() -> Result<(), Error> {
+        let fixture = Fixture::start("127.0.0.1:8106")?;
+        let client = &fixture.client;
+        let (addr, project, toggle) = (fixture.addr, &fixture.project, &fixture.toggle);
+
+        let child: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "child".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let requires = |toggle: &Toggle| Prerequisites {
+            prerequisites: vec![Prerequisite {
+                toggle_id: toggle.id.into(),
+                variant: "on".to_owned(),
+                variant_id: None,
+            }],
+        };
+        let child: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/{}/prerequisites",
+                addr, project.id, child.id
+            ))
+            .json(&requires(toggle))
+            .send()?
+            .json()?;
+        assert_eq!(child.prerequisites, requires(toggle).prerequisites);
+
+        let response = client
+            .post(&fixture.url("/prerequisites"))
+            .json(&requires(&child))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        // A Toggle that doesn't exist can't be required
+        let response = client
+            .post(&fixture.url("/prerequisites"))
+            .json(&Prerequisites {
+                prerequisites: vec![Prerequisite {
+                    toggle_id: uuid::Uuid::new_v4().into(),
+                    variant: "on".to_owned(),
+                    variant_id: None,
+                }],
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,556 @@
+use actix::{Handler, Message};
+use actix_web::{AsyncResponder, HttpRequest, HttpResponse, Json, Path, State};
+use chrono::Utc;
+use diesel::Connection;
+use futures::{future, Future};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{Generation, Metadata};
+use crate::event_store::DieselEventStore;
+use crate::project::ProjectId;
+use crate::toggle;
+use crate::toggle::ToggleId;
+use crate::variant;
+use crate::variant::{
+    CreateVariantHandler, DieselVariantNames, GetVariantHandler, ListVariantsHandler,
+    UpdateVariantHandler, VariantAction, VariantId,
+};
+
+use super::{
+    if_match, request_metadata, with_etag, write_transaction, AppError, AppState, Executor,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewVariant {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenameVariant {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Variant {
+    pub id: Uuid,
+    pub toggle_id: Uuid,
+    pub name: String,
+    pub retired: bool,
+}
+
+/// Domain Variant to DTO Variant
+impl From<variant::Variant> for Variant {
+    fn from(v: variant::Variant) -> Self {
+        Self {
+            id: v.id.into(),
+            toggle_id: v.toggle_id.into(),
+            name: v.name,
+            retired: v.retired,
+        }
+    }
+}
+
+struct CreateVariant {
+    project_id: ProjectId,
+    toggle_id: ToggleId,
+    name: String,
+    metadata: Metadata,
+}
+
+impl Message for CreateVariant {
+    type Result = Result<variant::Variant, AppError>;
+}
+
+impl Handler<CreateVariant> for Executor {
+    type Result = Result<variant::Variant, AppError>;
+
+    fn handle(&mut self, msg: CreateVariant, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let toggles = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let names = &mut DieselVariantNames { db };
+                let repository = &mut DieselEventStore::<_, variant::Variant>::new(db);
+                let handler = &mut CreateVariantHandler {
+                    toggles,
+                    names,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let variant = handler
+                    .handle(variant::CreateVariant {
+                        id: Uuid::new_v4(),
+                        project_id: msg.project_id,
+                        toggle_id: msg.toggle_id,
+                        name: msg.name,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(variant)
+            })
+        })
+    }
+}
+
+struct GetVariant {
+    project_id: ProjectId,
+    toggle_id: ToggleId,
+    id: VariantId,
+}
+
+impl Message for GetVariant {
+    type Result = Result<variant::Variant, AppError>;
+}
+
+impl Handler<GetVariant> for Executor {
+    type Result = Result<variant::Variant, AppError>;
+
+    fn handle(&mut self, msg: GetVariant, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let toggles = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let repository = &DieselEventStore::<_, variant::Variant>::new(db);
+                let handler = &GetVariantHandler {
+                    toggles,
+                    repository,
+                };
+
+                let variant = handler
+                    .handle(variant::GetVariant {
+                        project_id: msg.project_id,
+                        toggle_id: msg.toggle_id,
+                        id: msg.id,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(variant)
+            })
+        })
+    }
+}
+
+struct ListVariants {
+    project_id: ProjectId,
+    toggle_id: ToggleId,
+}
+
+impl Message for ListVariants {
+    type Result = Result<Vec<variant::Variant>, AppError>;
+}
+
+impl Handler<ListVariants> for Executor {
+    type Result = Result<Vec<variant::Variant>, AppError>;
+
+    fn handle(&mut self, msg: ListVariants, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            db.transaction::<_, AppError, _>(|| {
+                let toggles = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let names = &DieselVariantNames { db };
+                let repository = &DieselEventStore::<_, variant::Variant>::new(db);
+                let handler = &ListVariantsHandler {
+                    toggles,
+                    names,
+                    repository,
+                };
+
+                let variants = handler
+                    .handle(variant::ListVariants {
+                        project_id: msg.project_id,
+                        toggle_id: msg.toggle_id,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(variants)
+            })
+        })
+    }
+}
+
+struct UpdateVariant {
+    project_id: ProjectId,
+    toggle_id: ToggleId,
+    id: VariantId,
+    action: VariantAction,
+    expected_generation: Option<Generation>,
+    metadata: Metadata,
+}
+
+impl Message for UpdateVariant {
+    type Result = Result<variant::Variant, AppError>;
+}
+
+impl Handler<UpdateVariant> for Executor {
+    type Result = Result<variant::Variant, AppError>;
+
+    fn handle(&mut self, msg: UpdateVariant, _: &mut Self::Context) -> Self::Result {
+        with_connection!(&self.db, |db| {
+            write_transaction(db, || {
+                let toggles = &DieselEventStore::<_, toggle::Toggle>::new(db);
+                let names = &mut DieselVariantNames { db };
+                let repository = &mut DieselEventStore::<_, variant::Variant>::new(db);
+                let handler = &mut UpdateVariantHandler {
+                    toggles,
+                    names,
+                    repository,
+                    utc_now: Utc::now,
+                };
+
+                let variant = handler
+                    .handle(variant::UpdateVariant {
+                        project_id: msg.project_id,
+                        toggle_id: msg.toggle_id,
+                        id: msg.id,
+                        action: msg.action,
+                        expected_generation: msg.expected_generation,
+                        metadata: msg.metadata,
+                    })
+                    .map_err(|e| -> AppError { e.into() })?;
+                Ok(variant)
+            })
+        })
+    }
+}
+
+pub fn create_variant(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId)>,
+        Json<NewVariant>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    req.state()
+        .executor
+        .send(CreateVariant {
+            project_id: path.0,
+            toggle_id: path.1,
+            name: body.name.clone(),
+            metadata: request_metadata(&req),
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Variant::from(x))))
+        .responder()
+}
+
+pub fn list_variants(
+    (path, state): (Path<(ProjectId, ToggleId)>, State<AppState>),
+) -> impl Future<Item = Json<Vec<Variant>>, Error = AppError> {
+    state
+        .executor
+        .send(ListVariants {
+            project_id: path.0,
+            toggle_id: path.1,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| Json(x.into_iter().map(Variant::from).collect())))
+        .responder()
+}
+
+pub fn get_variant(
+    (path, state): (Path<(ProjectId, ToggleId, VariantId)>, State<AppState>),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    state
+        .executor
+        .send(GetVariant {
+            project_id: path.0,
+            toggle_id: path.1,
+            id: path.2,
+        })
+        .from_err()
+        .and_then(|res| res.map(|x| with_etag(x.generation, Variant::from(x))))
+        .responder()
+}
+
+fn update_variant(
+    path: &(ProjectId, ToggleId, VariantId),
+    req: &HttpRequest<AppState>,
+    action: VariantAction,
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    let (project_id, toggle_id, id) = *path;
+    let executor = req.state().executor.clone();
+    let metadata = request_metadata(req);
+    future::result(if_match(req))
+        .and_then(move |expected_generation| {
+            executor
+                .send(UpdateVariant {
+                    project_id,
+                    toggle_id,
+                    id,
+                    action,
+                    expected_generation,
+                    metadata,
+                })
+                .from_err()
+        })
+        .and_then(|res| res.map(|x| with_etag(x.generation, Variant::from(x))))
+        .responder()
+}
+
+pub fn rename_variant(
+    (path, body, req): (
+        Path<(ProjectId, ToggleId, VariantId)>,
+        Json<RenameVariant>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_variant(&path, &req, VariantAction::Rename(body.into_inner().name))
+}
+
+pub fn retire_variant(
+    (path, req): (
+        Path<(ProjectId, ToggleId, VariantId)>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_variant(&path, &req, VariantAction::Retire)
+}
+
+pub fn revive_variant(
+    (path, req): (
+        Path<(ProjectId, ToggleId, VariantId)>,
+        HttpRequest<AppState>,
+    ),
+) -> impl Future<Item = HttpResponse, Error = AppError> {
+    update_variant(&path, &req, VariantAction::Revive)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use tempdir::TempDir;
+
+    use crate::evaluation::{Context, Evaluation, Reason};
+    use crate::toggle::rule::{Clause, Operator, Rule};
+
+    use super::super::environment::{Environment, NewEnvironment};
+    use super::super::evaluation::Evaluate;
+    use super::super::toggle::{NewToggle, Rules, Toggle};
+    use super::super::{CreateProject, Project};
+    use super::{NewVariant, RenameVariant, Variant};
+
+    #[test]
+    fn test_variant_lifecycle() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8093").unwrap().start();
+            tx.send("127.0.0.1:8093").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let variants = format!(
+            "http://{}/projects/{}/toggles/{}/variants",
+            addr, project.id, toggle.id
+        );
+
+        for name in &["blue", "green"] {
+            let response = client
+                .post(&format!("{}/create", variants))
+                .json(&NewVariant {
+                    name: (*name).to_owned(),
+                })
+                .send()?;
+            assert_eq!(response.status(), reqwest::StatusCode::OK);
+        }
+        let response = client
+            .post(&format!("{}/create", variants))
+            .json(&NewVariant {
+                name: "blue".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+
+        let listed: Vec<Variant> = client.get(&variants).send()?.json()?;
+        assert_eq!(
+            listed.iter().map(|v| v.name.as_str()).collect::<Vec<_>>(),
+            vec!["blue", "green"],
+        );
+
+        let blue = &listed[0];
+        let response = client
+            .post(&format!("{}/{}/retire", variants, blue.id))
+            .header(reqwest::header::IF_MATCH, "\"0\"")
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let response = client
+            .post(&format!("{}/{}/rename", variants, blue.id))
+            .json(&RenameVariant {
+                name: "red".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+        let mut response = client.get(&format!("{}/{}", variants, blue.id)).send()?;
+        assert_eq!(
+            response.headers().get(reqwest::header::ETAG),
+            Some(&reqwest::header::HeaderValue::from_static("\"1\"")),
+        );
+        let variant: Variant = response.json()?;
+        assert_eq!(variant.name, "blue");
+        assert!(variant.retired);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_variants() -> Result<(), Error> {
+        let tmpdir = TempDir::new("db")?;
+
+        let db_path = tmpdir.path().join("db.sqlite");
+        let manager = ConnectionManager::<SqliteConnection>::new(db_path.to_str().unwrap());
+        let pool = Pool::builder().build(manager)?;
+        let db = pool.get()?;
+        diesel_migrations::run_pending_migrations(&db)?;
+
+        let (tx, rx) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            let sys = actix::System::new("test-feature-toggler");
+            let server = super::super::create(db_path.clone().to_str().unwrap()).unwrap();
+            server.bind("127.0.0.1:8101").unwrap().start();
+            tx.send("127.0.0.1:8101").unwrap();
+            let _ = sys.run();
+        });
+
+        let addr = rx.recv()?;
+
+        let client = reqwest::Client::new();
+        let project: Project = client
+            .post(&format!("http://{}/projects/create", addr))
+            .json(&CreateProject {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let environment: Environment = client
+            .post(&format!(
+                "http://{}/projects/{}/environments/create",
+                addr, project.id
+            ))
+            .json(&NewEnvironment {
+                name: "production".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let toggle: Toggle = client
+            .post(&format!(
+                "http://{}/projects/{}/toggles/create",
+                addr, project.id
+            ))
+            .json(&NewToggle {
+                name: "test".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let url = format!(
+            "http://{}/projects/{}/toggles/{}",
+            addr, project.id, toggle.id
+        );
+        let blue: Variant = client
+            .post(&format!("{}/variants/create", url))
+            .json(&NewVariant {
+                name: "blue".to_owned(),
+            })
+            .send()?
+            .json()?;
+        let response = client
+            .post(&format!("{}/environments/{}/enable", url, environment.id))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+        let rules = |variant: &str| Rules {
+            rules: vec![Rule {
+                clauses: vec![Clause {
+                    attribute: "country".to_owned(),
+                    operator: Operator::In,
+                    values: vec!["GB".to_owned()],
+                }],
+                variant: variant.to_owned(),
+                variant_id: None,
+            }],
+        };
+        let rules_url = format!("{}/environments/{}/rules", url, environment.id);
+        let response = client.post(&rules_url).json(&rules("purple")).send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let toggle: Toggle = client
+            .post(&rules_url)
+            .json(&rules("blue"))
+            .send()?
+            .json()?;
+        let rule = &toggle.environments[&environment.id].rules[0];
+        assert_eq!(rule.variant_id.map(uuid::Uuid::from), Some(blue.id));
+
+        let evaluate = || -> Result<Evaluation, Error> {
+            Ok(client
+                .post(&format!("http://{}/evaluate", addr))
+                .json(&Evaluate {
+                    project_id: project.id.into(),
+                    environment_id: environment.id.into(),
+                    toggle_id: toggle.id.into(),
+                    context: Context {
+                        user_id: Some("user".to_owned()),
+                        attributes: vec![("country".to_owned(), "GB".to_owned())]
+                            .into_iter()
+                            .collect(),
+                    },
+                })
+                .send()?
+                .json()?)
+        };
+        assert_eq!(evaluate()?.variant, "blue");
+
+        // The Rule follows the Variant through a rename
+        let response = client
+            .post(&format!("{}/variants/{}/rename", url, blue.id))
+            .json(&RenameVariant {
+                name: "navy".to_owned(),
+            })
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(evaluate()?.variant, "navy");
+
+        // and is skipped once the Variant is retired
+        let response = client
+            .post(&format!("{}/variants/{}/retire", url, blue.id))
+            .send()?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let evaluation = evaluate()?;
+        assert_eq!(evaluation.variant, "on");
+        assert_eq!(evaluation.reason, Reason::On);
+
+        Ok(())
+    }
+}
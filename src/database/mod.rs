@@ -1,2 +1,173 @@
 pub mod models;
 pub mod schema;
+
+use failure_derive::Fail;
+
+/// Kind of database to connect to, told apart by the URL given for it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Backend {
+    /// Path to the database file, or `:memory:`.
+    Sqlite,
+    /// A `postgres://` or `postgresql://` URL, with
+    /// the schema from the `migrations_postgres` directory.
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Self {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            Backend::Postgres
+        } else {
+            Backend::Sqlite
+        }
+    }
+}
+
+/// Raised for a Backend the service was built without.
+#[derive(Debug, Fail)]
+#[fail(display = "{:?} can't be used as the service's database", _0)]
+pub struct UnsupportedBackend(pub Backend);
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::path::Path;
+
+    use diesel::dsl::sql;
+    use diesel::sql_types::Text;
+    use diesel::sqlite::SqliteConnection;
+    use diesel::{Connection, RunQueryDsl};
+    use failure::Error;
+    use tempdir::TempDir;
+    use uuid::Uuid;
+
+    use crate::project::list::DieselProjects;
+    use crate::projection::Projector;
+
+    use super::Backend;
+
+    /// Run the migrations up to and including `last`, as on a database
+    /// last migrated back then.
+    fn migrate_until(db: &SqliteConnection, last: &str) -> Result<(), Error> {
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations");
+        let until = TempDir::new("migrations")?;
+        for entry in fs::read_dir(&migrations)? {
+            let name = entry?.file_name();
+            if name.to_str().map_or(false, |name| name <= last) {
+                fs::create_dir(until.path().join(&name))?;
+                for file in &["up.sql", "down.sql"] {
+                    fs::copy(
+                        migrations.join(&name).join(file),
+                        until.path().join(&name).join(file),
+                    )?;
+                }
+            }
+        }
+        diesel_migrations::run_pending_migrations_in_directory(
+            db,
+            until.path(),
+            &mut std::io::sink(),
+        )?;
+        Ok(())
+    }
+
+    /// Store an event the way it was before events had a type of aggregate.
+    fn insert_event(
+        db: &SqliteConnection,
+        aggregate_id: &str,
+        generation: i32,
+        type_: &str,
+        data: &str,
+    ) -> Result<(), Error> {
+        db.execute(&format!(
+            "INSERT INTO events (id, aggregate_id, generation, created_at, type, data) \
+             VALUES ('{}', '{}', {}, '2019-01-01T12:34:56+00:00', '{}', '{}')",
+            Uuid::new_v4(),
+            aggregate_id,
+            generation,
+            type_,
+            data
+        ))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_backfill_project_names() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        migrate_until(db, "2019-04-27-120000_create_scheduled_changes")?;
+        let created =
+            |id: &str, name: &str| format!(r#"{{"Created":{{"id":"{}","name":"{}"}}}}"#, id, name);
+        insert_event(db, "a", 0, "Created", &created("a", "alpha"))?;
+        insert_event(db, "b", 0, "Created", &created("b", "beta"))?;
+        insert_event(db, "b", 1, "Renamed", r#"{"Renamed":{"name":"gamma"}}"#)?;
+        insert_event(db, "c", 0, "Created", &created("c", "alpha"))?;
+        insert_event(
+            db,
+            "t",
+            0,
+            "Created",
+            r#"{"Created":{"id":"t","project_id":"a","name":"toggle"}}"#,
+        )?;
+        insert_event(
+            db,
+            "v",
+            0,
+            "Created",
+            r#"{"Created":{"id":"v","toggle_id":"t","name":"variant"}}"#,
+        )?;
+
+        migrate_until(db, "2019-05-04-120000_create_project_names")?;
+
+        let names = sql::<(Text, Text)>("SELECT id, name FROM project_names ORDER BY id").load::<(
+            String,
+            String,
+        )>(
+            db
+        )?;
+        assert_eq!(
+            names,
+            vec![
+                ("a".to_owned(), "alpha".to_owned()),
+                ("b".to_owned(), "gamma".to_owned()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_backend_from_url() {
+        assert_eq!(Backend::from_url("db.sqlite"), Backend::Sqlite);
+        assert_eq!(Backend::from_url(":memory:"), Backend::Sqlite);
+        assert_eq!(
+            Backend::from_url("postgres://localhost/toggles"),
+            Backend::Postgres
+        );
+        assert_eq!(
+            Backend::from_url("postgresql://user@localhost:5432/toggles"),
+            Backend::Postgres
+        );
+    }
+
+    #[test]
+    fn test_type_project_streams() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        migrate_until(db, "2019-03-18-210307_create_events")?;
+        let created =
+            |id: &str, name: &str| format!(r#"{{"Created":{{"id":"{}","name":"{}"}}}}"#, id, name);
+        // Read back as Projects this time, so with ids that parse as such
+        let (a, b) = (&Uuid::new_v4().to_string(), &Uuid::new_v4().to_string());
+        insert_event(db, a, 0, "Created", &created(a, "alpha"))?;
+        insert_event(db, b, 0, "Created", &created(b, "beta"))?;
+
+        diesel_migrations::run_pending_migrations(db)?;
+
+        let types = sql::<Text>("SELECT DISTINCT aggregate_type FROM events").load::<String>(db)?;
+        assert_eq!(types, vec!["project".to_owned()]);
+
+        let projects = &mut DieselProjects { db };
+        Projector { db }.catch_up(projects)?;
+        let names = sql::<Text>("SELECT name FROM projects ORDER BY name").load::<String>(db)?;
+        assert_eq!(names, vec!["alpha".to_owned(), "beta".to_owned()]);
+        Ok(())
+    }
+}
@@ -1,6 +1,9 @@
 use diesel::{Insertable, Queryable};
 
-use super::schema::events;
+use super::schema::{
+    audit_log, events, projection_checkpoints, projects, scheduled_changes, snapshots,
+    toggle_environments, toggles, variant_names,
+};
 
 #[derive(Clone, Debug, Eq, PartialEq, Queryable)]
 pub struct Event {
@@ -10,6 +13,11 @@ pub struct Event {
     pub created_at: String,
     pub type_: String,
     pub data: String,
+    pub aggregate_type: String,
+    pub position: i64,
+    pub metadata: String,
+    /// Version of the shape of `data`.
+    pub version: i32,
 }
 
 #[derive(Debug, Insertable)]
@@ -21,4 +29,98 @@ pub struct NewEvent<'a> {
     pub created_at: &'a str,
     pub type_: &'a str,
     pub data: &'a str,
+    pub aggregate_type: &'a str,
+    pub metadata: &'a str,
+    pub version: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "audit_log"]
+pub struct NewAuditLogEntry<'a> {
+    pub position: i64,
+    pub event_id: &'a str,
+    pub aggregate_type: &'a str,
+    pub aggregate_id: &'a str,
+    pub project_id: Option<&'a str>,
+    pub generation: i32,
+    pub created_at: &'a str,
+    pub type_: &'a str,
+    pub data: &'a str,
+    pub metadata: &'a str,
+    pub version: i32,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "projection_checkpoints"]
+pub struct NewProjectionCheckpoint<'a> {
+    pub name: &'a str,
+    pub position: i64,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Queryable)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    pub archived: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "projects"]
+pub struct NewProject<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub archived: bool,
+    pub created_at: &'a str,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Queryable)]
+pub struct ScheduledChange {
+    pub id: String,
+    pub project_id: String,
+    pub toggle_id: String,
+    pub at: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "scheduled_changes"]
+pub struct NewScheduledChange<'a> {
+    pub id: &'a str,
+    pub project_id: &'a str,
+    pub toggle_id: &'a str,
+    pub at: i64,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "snapshots"]
+pub struct NewSnapshot<'a> {
+    pub aggregate_id: &'a str,
+    pub generation: i32,
+    pub data: &'a str,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "toggles"]
+pub struct NewToggle<'a> {
+    pub id: &'a str,
+    pub project_id: &'a str,
+    pub name: &'a str,
+    pub retired: bool,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "toggle_environments"]
+pub struct NewToggleEnvironment<'a> {
+    pub toggle_id: &'a str,
+    pub environment_id: &'a str,
+    pub enabled: bool,
+    pub rollout: Option<i32>,
+}
+
+#[derive(Debug, Insertable)]
+#[table_name = "variant_names"]
+pub struct NewVariantName<'a> {
+    pub id: &'a str,
+    pub toggle_id: &'a str,
+    pub name: &'a str,
 }
@@ -1,5 +1,22 @@
 table! {
-    events (id) {
+    audit_log (position) {
+        position -> BigInt,
+        event_id -> Text,
+        aggregate_type -> Text,
+        aggregate_id -> Text,
+        project_id -> Nullable<Text>,
+        generation -> Integer,
+        created_at -> Text,
+        #[sql_name = "type"]
+        type_ -> Text,
+        data -> Text,
+        metadata -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    events (position) {
         id -> Text,
         aggregate_id -> Text,
         generation -> Integer,
@@ -7,5 +24,80 @@ table! {
         #[sql_name = "type"]
         type_ -> Text,
         data -> Text,
+        aggregate_type -> Text,
+        position -> BigInt,
+        metadata -> Text,
+        version -> Integer,
+    }
+}
+
+table! {
+    projection_checkpoints (name) {
+        name -> Text,
+        position -> BigInt,
+    }
+}
+
+table! {
+    projects (id) {
+        id -> Text,
+        name -> Text,
+        archived -> Bool,
+        created_at -> Text,
+    }
+}
+
+table! {
+    scheduled_changes (id) {
+        id -> Text,
+        project_id -> Text,
+        toggle_id -> Text,
+        at -> BigInt,
+    }
+}
+
+table! {
+    snapshots (aggregate_id, generation) {
+        aggregate_id -> Text,
+        generation -> Integer,
+        data -> Text,
+    }
+}
+
+table! {
+    toggle_environments (toggle_id, environment_id) {
+        toggle_id -> Text,
+        environment_id -> Text,
+        enabled -> Bool,
+        rollout -> Nullable<Integer>,
+    }
+}
+
+table! {
+    toggles (id) {
+        id -> Text,
+        project_id -> Text,
+        name -> Text,
+        retired -> Bool,
+    }
+}
+
+table! {
+    variant_names (id) {
+        id -> Text,
+        toggle_id -> Text,
+        name -> Text,
     }
 }
+
+allow_tables_to_appear_in_same_query!(
+    audit_log,
+    events,
+    projection_checkpoints,
+    projects,
+    scheduled_changes,
+    snapshots,
+    toggle_environments,
+    toggles,
+    variant_names,
+);
@@ -23,10 +23,34 @@ impl From<Generation> for i32 {
     }
 }
 
+impl From<i32> for Generation {
+    fn from(generation: i32) -> Self {
+        Generation(generation)
+    }
+}
+
+/// Point in an Aggregate's history to read it as it was at.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PointInTime {
+    Generation(Generation),
+    Time(DateTime<Utc>),
+}
+
 pub trait Aggregate {
     type Id: Debug + Eq + PartialEq;
     type Event: Debug + Eq + PartialEq;
     type Err;
+    /// Name to tell the events of this kind of Aggregate apart
+    /// from those of others in the event store.
+    const TYPE: &'static str;
+    /// Events between snapshots of the Aggregate's state, so it can be
+    /// hydrated from the latest snapshot rather than its first event.
+    /// None for Aggregates that don't take snapshots.
+    const SNAPSHOT_INTERVAL: Option<i32> = None;
+    /// Version of the shape events of this kind of Aggregate are stored in.
+    /// Raised whenever the shape changes, along with an upcaster bringing
+    /// events stored in the previous version up to it.
+    const EVENT_VERSION: i32 = 1;
 
     fn id(&self) -> &Self::Id;
     fn generation(&self) -> Generation;
@@ -39,7 +63,15 @@ pub trait Aggregate {
     where
         Self: Sized,
     {
-        let mut state = None;
+        Self::hydrate_from(None, events)
+    }
+
+    /// Apply the events that followed on from `state`, such as a snapshot.
+    fn hydrate_from(state: Option<Self>, events: &[Self::Event]) -> Result<Option<Self>, Self::Err>
+    where
+        Self: Sized,
+    {
+        let mut state = state;
         for event in events {
             state = Some(Self::apply_event(state, event)?);
         }
@@ -47,6 +79,12 @@ pub trait Aggregate {
     }
 }
 
+/// Events that can be stored need a name to
+/// identify them by in the event store.
+pub trait AggregateEvent {
+    fn type_(&self) -> String;
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct DomainEventId(Uuid);
 
@@ -60,11 +98,37 @@ impl DomainEventId {
     }
 }
 
+/// Who made a change and how, stored alongside each event the change
+/// resulted in. Everything is optional, as not every change has it all,
+/// and left out when stored if missing.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Metadata {
+    /// User the change was made by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    /// API key the change was made with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<String>,
+    /// Shared by every event resulting from the same request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<Uuid>,
+    /// The event or command the change was made because of.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub causation_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    /// Why the change was made, in the user's own words.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct DomainEvent<T: Aggregate> {
     pub id: DomainEventId,
     pub aggregate_id: <T as Aggregate>::Id,
     pub created_at: DateTime<Utc>,
+    pub metadata: Metadata,
     pub event: <T as Aggregate>::Event,
 }
 
@@ -76,6 +140,20 @@ pub trait Repository {
         &self,
         id: <<Self as Repository>::Aggregate as Aggregate>::Id,
     ) -> Result<Self::Aggregate, Self::Err>;
+    /// The Aggregate as it was at the given generation,
+    /// as if none of the events after it had happened.
+    fn get_at(
+        &self,
+        id: <<Self as Repository>::Aggregate as Aggregate>::Id,
+        generation: Generation,
+    ) -> Result<Self::Aggregate, Self::Err>;
+    /// The Aggregate as it was at the given time,
+    /// from only the events stored by then.
+    fn get_as_of(
+        &self,
+        id: <<Self as Repository>::Aggregate as Aggregate>::Id,
+        at: DateTime<Utc>,
+    ) -> Result<Self::Aggregate, Self::Err>;
     fn persist(
         &mut self,
         generation: Generation,
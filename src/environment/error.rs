@@ -0,0 +1,97 @@
+use failure_derive::Fail;
+
+use crate::domain::Generation;
+use crate::event_store::error::EventStoreError;
+use crate::project::error::ProjectError;
+
+#[derive(Debug, Fail)]
+pub enum EnvironmentIdParseError {
+    #[fail(display = "fail to parse uuid")]
+    UuidParseError(#[cause] uuid::parser::ParseError),
+}
+
+impl From<uuid::parser::ParseError> for EnvironmentIdParseError {
+    fn from(e: uuid::parser::ParseError) -> EnvironmentIdParseError {
+        EnvironmentIdParseError::UuidParseError(e)
+    }
+}
+
+#[derive(Debug, Eq, Fail, PartialEq)]
+pub enum EnvironmentError {
+    #[fail(display = "invalid environment name: {}", name)]
+    InvalidName { name: String },
+    #[fail(display = "invalid event `{}` applied to state `{}", event, state)]
+    InvalidStateEvent { state: String, event: String },
+}
+
+#[derive(Debug, Fail)]
+pub enum CreateEnvironmentHandlerError {
+    #[fail(display = "environment error")]
+    EnvironmentError(#[cause] EnvironmentError),
+    #[fail(display = "project repository error")]
+    ProjectRepositoryError(#[cause] EventStoreError<ProjectError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<EnvironmentError>),
+}
+
+impl From<EnvironmentError> for CreateEnvironmentHandlerError {
+    fn from(e: EnvironmentError) -> Self {
+        CreateEnvironmentHandlerError::EnvironmentError(e)
+    }
+}
+
+impl From<EventStoreError<ProjectError>> for CreateEnvironmentHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        CreateEnvironmentHandlerError::ProjectRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<EnvironmentError>> for CreateEnvironmentHandlerError {
+    fn from(e: EventStoreError<EnvironmentError>) -> Self {
+        CreateEnvironmentHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum GetEnvironmentHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<EnvironmentError>),
+}
+
+impl From<EventStoreError<EnvironmentError>> for GetEnvironmentHandlerError {
+    fn from(e: EventStoreError<EnvironmentError>) -> Self {
+        GetEnvironmentHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum UpdateEnvironmentHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
+    #[fail(display = "environment error")]
+    EnvironmentError(#[cause] EnvironmentError),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<EnvironmentError>),
+}
+
+impl From<EnvironmentError> for UpdateEnvironmentHandlerError {
+    fn from(e: EnvironmentError) -> Self {
+        UpdateEnvironmentHandlerError::EnvironmentError(e)
+    }
+}
+
+impl From<EventStoreError<EnvironmentError>> for UpdateEnvironmentHandlerError {
+    fn from(e: EventStoreError<EnvironmentError>) -> Self {
+        UpdateEnvironmentHandlerError::RepositoryError(e)
+    }
+}
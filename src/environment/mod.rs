@@ -0,0 +1,429 @@
+pub mod error;
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, Repository,
+};
+use crate::project::{Project, ProjectId};
+
+use self::error::{
+    CreateEnvironmentHandlerError, EnvironmentError, EnvironmentIdParseError,
+    GetEnvironmentHandlerError, UpdateEnvironmentHandlerError,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct EnvironmentId(Uuid);
+
+impl EnvironmentId {
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl FromStr for EnvironmentId {
+    type Err = EnvironmentIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(Self(id))
+    }
+}
+
+impl From<EnvironmentId> for Uuid {
+    fn from(id: EnvironmentId) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for EnvironmentId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+/// A deployment target of a Project, such as staging or
+/// production, that Toggles are switched on and off in.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Environment {
+    pub id: EnvironmentId,
+    pub project_id: ProjectId,
+    pub generation: Generation,
+    pub name: String,
+    pub archived: bool,
+}
+
+impl Environment {
+    pub fn create(
+        id: EnvironmentId,
+        project_id: ProjectId,
+        name: String,
+    ) -> Result<Vec<EnvironmentEvent>, EnvironmentError> {
+        if name.trim().is_empty() {
+            return Err(EnvironmentError::InvalidName { name });
+        }
+        Ok(vec![EnvironmentEvent::Created {
+            id,
+            project_id,
+            name,
+        }])
+    }
+
+    pub fn rename(&self, name: String) -> Result<Vec<EnvironmentEvent>, EnvironmentError> {
+        if name.trim().is_empty() {
+            return Err(EnvironmentError::InvalidName { name });
+        }
+        self.transition(EnvironmentEvent::Renamed { name })
+    }
+
+    pub fn archive(&self) -> Result<Vec<EnvironmentEvent>, EnvironmentError> {
+        self.transition(EnvironmentEvent::Archived)
+    }
+
+    fn transition(
+        &self,
+        event: EnvironmentEvent,
+    ) -> Result<Vec<EnvironmentEvent>, EnvironmentError> {
+        Self::apply_event(Some(self.clone()), &event)?;
+        Ok(vec![event])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum EnvironmentEvent {
+    Created {
+        id: EnvironmentId,
+        project_id: ProjectId,
+        name: String,
+    },
+    Renamed {
+        name: String,
+    },
+    Archived,
+}
+
+impl AggregateEvent for EnvironmentEvent {
+    fn type_(&self) -> String {
+        match self {
+            EnvironmentEvent::Created { .. } => "Created".to_owned(),
+            EnvironmentEvent::Renamed { .. } => "Renamed".to_owned(),
+            EnvironmentEvent::Archived => "Archived".to_owned(),
+        }
+    }
+}
+
+impl Aggregate for Environment {
+    type Id = EnvironmentId;
+    type Event = EnvironmentEvent;
+    type Err = EnvironmentError;
+    const TYPE: &'static str = "environment";
+
+    fn id(&self) -> &EnvironmentId {
+        &self.id
+    }
+
+    fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    fn apply_event(
+        environment: Option<Self>,
+        event: &EnvironmentEvent,
+    ) -> Result<Self, EnvironmentError> {
+        match (&environment, event) {
+            (
+                None,
+                EnvironmentEvent::Created {
+                    id,
+                    project_id,
+                    name,
+                },
+            ) => Ok(Environment {
+                id: *id,
+                project_id: *project_id,
+                generation: Generation::first(),
+                name: name.clone(),
+                archived: false,
+            }),
+            (Some(environment), EnvironmentEvent::Renamed { name }) if !environment.archived => {
+                Ok(Environment {
+                    generation: environment.generation.next(),
+                    name: name.clone(),
+                    ..environment.clone()
+                })
+            }
+            (Some(environment), EnvironmentEvent::Archived) if !environment.archived => {
+                Ok(Environment {
+                    generation: environment.generation.next(),
+                    archived: true,
+                    ..environment.clone()
+                })
+            }
+            _ => Err(EnvironmentError::InvalidStateEvent {
+                state: format!("{:?}", environment),
+                event: format!("{:?}", event),
+            }),
+        }
+    }
+}
+
+pub struct CreateEnvironment {
+    pub id: Uuid,
+    pub project_id: ProjectId,
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+pub struct CreateEnvironmentHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Environment, Err = E>,
+{
+    pub projects: &'a P,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, PE, P, E, R> CreateEnvironmentHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Environment, Err = E>,
+    CreateEnvironmentHandlerError: From<PE> + From<E>,
+{
+    pub fn handle(
+        &mut self,
+        command: CreateEnvironment,
+    ) -> Result<Environment, CreateEnvironmentHandlerError> {
+        let project = self.projects.get(command.project_id)?;
+        let environment_id = EnvironmentId(command.id);
+        let events = Environment::create(environment_id, project.id, command.name)?;
+        let environment = Environment::hydrate(&events)?.expect("Environment is not None");
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Environment>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: environment_id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(Generation::first(), &events)?;
+        Ok(environment)
+    }
+}
+
+pub struct GetEnvironment {
+    pub project_id: ProjectId,
+    pub id: EnvironmentId,
+}
+
+pub struct GetEnvironmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Environment, Err = E>,
+{
+    pub repository: &'a R,
+}
+
+impl<'a, E, R> GetEnvironmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Environment, Err = E>,
+    GetEnvironmentHandlerError: From<E>,
+{
+    pub fn handle(
+        &self,
+        command: GetEnvironment,
+    ) -> Result<Environment, GetEnvironmentHandlerError> {
+        let environment = self.repository.get(command.id)?;
+        if environment.project_id != command.project_id {
+            return Err(GetEnvironmentHandlerError::NotFoundError);
+        }
+        Ok(environment)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum EnvironmentAction {
+    Rename(String),
+    Archive,
+}
+
+pub struct UpdateEnvironment {
+    pub project_id: ProjectId,
+    pub id: EnvironmentId,
+    pub action: EnvironmentAction,
+    pub expected_generation: Option<Generation>,
+    pub metadata: Metadata,
+}
+
+pub struct UpdateEnvironmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Environment, Err = E>,
+{
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, E, R> UpdateEnvironmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Environment, Err = E>,
+    UpdateEnvironmentHandlerError: From<E>,
+{
+    pub fn handle(
+        &mut self,
+        command: UpdateEnvironment,
+    ) -> Result<Environment, UpdateEnvironmentHandlerError> {
+        let mut environment = self.repository.get(command.id)?;
+        if environment.project_id != command.project_id {
+            return Err(UpdateEnvironmentHandlerError::NotFoundError);
+        }
+        if let Some(expected) = command.expected_generation {
+            if expected != environment.generation {
+                return Err(UpdateEnvironmentHandlerError::ConcurrencyConflict {
+                    expected,
+                    actual: environment.generation,
+                });
+            }
+        }
+        let events = match command.action {
+            EnvironmentAction::Rename(name) => environment.rename(name)?,
+            EnvironmentAction::Archive => environment.archive()?,
+        };
+        let generation = environment.generation.next();
+        for event in &events {
+            environment = Environment::apply_event(Some(environment), event)?;
+        }
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Environment>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: environment.id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(generation, &events)?;
+        Ok(environment)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::event_store::error::EventStoreError;
+    use crate::event_store::memory::InMemoryRepository;
+    use crate::project::{Project, ProjectEvent, ProjectId};
+
+    use super::error::{CreateEnvironmentHandlerError, EnvironmentError};
+    use super::{
+        CreateEnvironment, CreateEnvironmentHandler, Environment, EnvironmentEvent, EnvironmentId,
+    };
+
+    fn created() -> Result<Environment, Error> {
+        let id = EnvironmentId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = Environment::create(id, project_id, "staging".to_owned())?;
+        Ok(Environment::hydrate(&events)?.expect("Environment is not None"))
+    }
+
+    #[test]
+    fn test_create() -> Result<(), Error> {
+        let environment = created()?;
+        assert_eq!(environment.name, "staging");
+        assert!(!environment.archived);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename() -> Result<(), Error> {
+        let environment = created()?;
+        assert_eq!(
+            environment.rename("production".to_owned())?,
+            vec![EnvironmentEvent::Renamed {
+                name: "production".to_owned()
+            }],
+        );
+        assert_eq!(
+            environment.rename("".to_owned()),
+            Err(EnvironmentError::InvalidName {
+                name: "".to_owned()
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_archived() -> Result<(), Error> {
+        let environment = Environment::apply_event(Some(created()?), &EnvironmentEvent::Archived)?;
+        match environment.rename("production".to_owned()) {
+            Err(EnvironmentError::InvalidStateEvent { .. }) => Ok(()),
+            result => panic!("expected invalid state event, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_create_handler() -> Result<(), Error> {
+        let mut projects = InMemoryRepository::<Project>::new();
+        let mut repository = InMemoryRepository::<Environment>::new();
+        let project_id = ProjectId::from(Uuid::new_v4());
+        let command = || CreateEnvironment {
+            id: Uuid::new_v4(),
+            project_id,
+            name: "staging".to_owned(),
+            metadata: Metadata {
+                actor: Some("alice".to_owned()),
+                ..Metadata::default()
+            },
+        };
+
+        let result = CreateEnvironmentHandler {
+            projects: &projects,
+            repository: &mut repository,
+            utc_now: Utc::now,
+        }
+        .handle(command());
+        match result {
+            Err(CreateEnvironmentHandlerError::ProjectRepositoryError(
+                EventStoreError::NotFoundError,
+            )) => (),
+            result => panic!("expected project not found, got {:?}", result),
+        }
+
+        projects.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: project_id,
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: ProjectEvent::Created {
+                    id: project_id,
+                    name: "checkout".to_owned(),
+                },
+            }],
+        )?;
+        let environment = CreateEnvironmentHandler {
+            projects: &projects,
+            repository: &mut repository,
+            utc_now: Utc::now,
+        }
+        .handle(command())?;
+
+        assert_eq!(repository.get(environment.id)?, environment);
+        let events = repository.events(environment.id);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].metadata.actor, Some("alice".to_owned()));
+        Ok(())
+    }
+}
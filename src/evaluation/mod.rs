@@ -0,0 +1,676 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::Aggregate;
+use crate::environment::EnvironmentId;
+use crate::segment::{Segment, SegmentId};
+use crate::toggle::rule::{Clause, Operator, Rule};
+use crate::toggle::{Toggle, ToggleId};
+use crate::variant::{self, VariantId, Variants};
+
+/// Name of the variant served while a boolean Toggle is on.
+pub const ON: &str = "on";
+/// Name of the variant served while a boolean Toggle is off.
+pub const OFF: &str = "off";
+
+/// Number of buckets users are spread across for rollouts,
+/// giving rollouts a resolution of a hundredth of a percent.
+const BUCKETS: u32 = 10_000;
+
+/// Who a Toggle is being evaluated for.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Context {
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub attributes: HashMap<String, String>,
+}
+
+impl Context {
+    /// Value of an attribute, where `user_id` refers to the user id.
+    fn attribute(&self, name: &str) -> Option<&str> {
+        match name {
+            "user_id" => self.user_id.as_ref().map(String::as_str),
+            _ => self.attributes.get(name).map(String::as_str),
+        }
+    }
+}
+
+/// Why an Evaluation resolved to its value.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Reason {
+    Retired,
+    Off,
+    On,
+    Rollout,
+    // Index of the targeting Rule that selected the variant
+    RuleMatch(usize),
+    // Prerequisite Toggle that didn't serve the required variant
+    PrerequisiteFailed(ToggleId),
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub value: bool,
+    pub variant: String,
+    // Id of the Variant served, unless it is one of the built-in variants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant_id: Option<VariantId>,
+    pub reason: Reason,
+}
+
+impl Evaluation {
+    fn new(value: bool, reason: Reason) -> Self {
+        Self {
+            value,
+            variant: if value { ON } else { OFF }.to_owned(),
+            variant_id: None,
+            reason,
+        }
+    }
+}
+
+/// Segments that targeting Rules may refer to, by id.
+pub type Segments = HashMap<SegmentId, Segment>;
+
+/// Toggles that prerequisites may refer to, by id.
+pub type Toggles = HashMap<ToggleId, Toggle>;
+
+/// Whether the attribute, as a key, is in the Segment. Unknown
+/// Segments are treated as having no members.
+fn in_segment(id: &str, key: Option<&str>, context: &Context, segments: &Segments) -> bool {
+    let segment = match id.parse().ok().and_then(|id| segments.get(&id)) {
+        Some(segment) => segment,
+        None => return false,
+    };
+    if let Some(key) = key {
+        if segment.excluded.contains(key) {
+            return false;
+        }
+        if segment.included.contains(key) {
+            return true;
+        }
+    }
+    segment.rules.iter().any(|rule| {
+        rule.clauses
+            .iter()
+            .all(|clause| clause_matches(clause, context, segments))
+    })
+}
+
+fn clause_matches(clause: &Clause, context: &Context, segments: &Segments) -> bool {
+    let attribute = context.attribute(&clause.attribute);
+    match clause.operator {
+        Operator::InSegment => clause
+            .values
+            .iter()
+            .any(|id| in_segment(id, attribute, context, segments)),
+        Operator::NotInSegment => !clause
+            .values
+            .iter()
+            .any(|id| in_segment(id, attribute, context, segments)),
+        _ => attribute.map_or(false, |attribute| clause.matches(attribute)),
+    }
+}
+
+fn matches(rule: &Rule, context: &Context, segments: &Segments) -> bool {
+    rule.clauses
+        .iter()
+        .all(|clause| clause_matches(clause, context, segments))
+}
+
+/// Stable bucket in `0..BUCKETS` for a user of a Toggle. The Toggle id is
+/// hashed in so a user doesn't land in the same bucket for every Toggle.
+fn bucket(toggle_id: ToggleId, key: &str) -> u32 {
+    // 32 bit FNV-1a, which unlike std's hashers is guaranteed
+    // to give the same result across releases and platforms
+    let mut hash: u32 = 0x811c_9dc5;
+    let bytes = toggle_id.to_string().into_bytes();
+    for byte in bytes.iter().chain(b":").chain(key.as_bytes()) {
+        hash ^= u32::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash % BUCKETS
+}
+
+/// Resolve a Toggle for a Context within a single Environment. Enabled
+/// Toggles are off unless every prerequisite serves its variant, and
+/// otherwise serve the variant of the first matching Rule, falling
+/// through to the rollout when none match. Variants are served by their
+/// current name, and Rules whose Variant has been retired are skipped.
+pub fn evaluate(
+    toggle: &Toggle,
+    environment_id: EnvironmentId,
+    context: &Context,
+    segments: &Segments,
+    toggles: &Toggles,
+    variants: &Variants,
+) -> Evaluation {
+    if toggle.retired() {
+        return Evaluation::new(false, Reason::Retired);
+    }
+    match toggle.environments().get(&environment_id) {
+        Some(state) if state.enabled => {
+            // Prerequisites can't form cycles, so this recursion ends
+            for prerequisite in toggle.prerequisites() {
+                let served = toggles.get(&prerequisite.toggle_id).map(|other| {
+                    evaluate(other, environment_id, context, segments, toggles, variants)
+                });
+                let passed = served.map_or(false, |served| match prerequisite.variant_id {
+                    Some(variant_id) => served.variant_id == Some(variant_id),
+                    None => served.variant == prerequisite.variant,
+                });
+                if !passed {
+                    return Evaluation::new(
+                        false,
+                        Reason::PrerequisiteFailed(prerequisite.toggle_id),
+                    );
+                }
+            }
+            let served = state
+                .rules
+                .iter()
+                .enumerate()
+                .filter(|(_, rule)| matches(rule, context, segments))
+                .find_map(|(index, rule)| {
+                    variant::resolve(*toggle.id(), rule.variant_id, &rule.variant, variants)
+                        .map(|served| (index, served))
+                });
+            if let Some((index, (variant_id, variant))) = served {
+                return Evaluation {
+                    value: variant != OFF,
+                    variant,
+                    variant_id,
+                    reason: Reason::RuleMatch(index),
+                };
+            }
+            match state.rollout {
+                Some(percentage) => {
+                    // Users can only be bucketed by a key, so
+                    // anonymous contexts are left out of rollouts
+                    let value = context.user_id.as_ref().map_or(false, |user_id| {
+                        bucket(*toggle.id(), user_id) < u32::from(percentage) * BUCKETS / 100
+                    });
+                    Evaluation::new(value, Reason::Rollout)
+                }
+                None => Evaluation::new(true, Reason::On),
+            }
+        }
+        _ => Evaluation::new(false, Reason::Off),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::Aggregate;
+    use crate::environment::EnvironmentId;
+    use crate::project::ProjectId;
+    use crate::segment::{Segment, SegmentEvent, SegmentId, SegmentRule};
+    use crate::toggle::rule::{Clause, Comparison, Operator, Rule};
+    use crate::toggle::{Event, Prerequisite, Toggle, ToggleId};
+    use crate::variant::{Variant, VariantEvent, VariantId};
+
+    use super::{bucket, evaluate, Context, Evaluation, Reason, Segments, Toggles, Variants};
+
+    fn toggle(events: &[Event]) -> Result<Toggle, Error> {
+        let id = ToggleId::from(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let mut toggle = Toggle::hydrate(&Toggle::create(id, project_id, "test".to_owned())?)?;
+        for event in events {
+            toggle = Some(Toggle::apply_event(toggle, event)?);
+        }
+        Ok(toggle.expect("Toggle is not None"))
+    }
+
+    fn environment_id() -> Result<EnvironmentId, Error> {
+        Ok(EnvironmentId::from(Uuid::parse_str(
+            "6ba7b810-9dad-11d1-80b4-00c04fd430c8",
+        )?))
+    }
+
+    fn enabled() -> Result<Event, Error> {
+        Ok(Event::Enabled {
+            environment_id: environment_id()?,
+        })
+    }
+
+    #[test]
+    fn test_evaluate_off() -> Result<(), Error> {
+        assert_eq!(
+            evaluate(
+                &toggle(&[])?,
+                environment_id()?,
+                &Context::default(),
+                &Segments::new(),
+                &Toggles::new(),
+                &Variants::new(),
+            ),
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::Off,
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_on() -> Result<(), Error> {
+        assert_eq!(
+            evaluate(
+                &toggle(&[enabled()?])?,
+                environment_id()?,
+                &Context::default(),
+                &Segments::new(),
+                &Toggles::new(),
+                &Variants::new(),
+            ),
+            Evaluation {
+                value: true,
+                variant: "on".to_owned(),
+                variant_id: None,
+                reason: Reason::On,
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_other_environment() -> Result<(), Error> {
+        assert_eq!(
+            evaluate(
+                &toggle(&[enabled()?])?,
+                EnvironmentId::from(Uuid::new_v4()),
+                &Context::default(),
+                &Segments::new(),
+                &Toggles::new(),
+                &Variants::new(),
+            ),
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::Off,
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_retired() -> Result<(), Error> {
+        assert_eq!(
+            evaluate(
+                &toggle(&[enabled()?, Event::Retired])?,
+                environment_id()?,
+                &Context::default(),
+                &Segments::new(),
+                &Toggles::new(),
+                &Variants::new(),
+            ),
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::Retired,
+            },
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_rollout() -> Result<(), Error> {
+        let ramp = |percentage| -> Result<Vec<bool>, Error> {
+            let toggle = toggle(&[
+                enabled()?,
+                Event::RolloutChanged {
+                    environment_id: environment_id()?,
+                    percentage,
+                },
+            ])?;
+            let environment_id = environment_id()?;
+            Ok((0..1000)
+                .map(|user| Context {
+                    user_id: Some(user.to_string()),
+                    ..Context::default()
+                })
+                .map(|context| {
+                    evaluate(
+                        &toggle,
+                        environment_id,
+                        &context,
+                        &Segments::new(),
+                        &Toggles::new(),
+                        &Variants::new(),
+                    )
+                    .value
+                })
+                .collect())
+        };
+        let ten = ramp(10)?;
+        let twenty = ramp(20)?;
+
+        // Roughly the right share of users, and ramping
+        // up keeps everyone who already had the Toggle
+        let count = |values: &[bool]| values.iter().filter(|value| **value).count();
+        assert!(count(&ten) > 50 && count(&ten) < 150);
+        assert!(count(&twenty) > 150 && count(&twenty) < 250);
+        assert!(ten.iter().zip(&twenty).all(|(ten, twenty)| !ten || *twenty));
+        assert_eq!(count(&ramp(0)?), 0);
+        assert_eq!(count(&ramp(100)?), 1000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bucket_is_stable() -> Result<(), Error> {
+        let id = ToggleId::from(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        assert_eq!(bucket(id, "user"), bucket(id, "user"));
+        assert_ne!(
+            bucket(id, "user"),
+            bucket(ToggleId::from(Uuid::new_v4()), "user")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_rules() -> Result<(), Error> {
+        let toggle_id = ToggleId::from(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let variant = |name: &str| -> Result<Variant, Error> {
+            let events = Variant::create(VariantId::from(Uuid::new_v4()), toggle_id, name.into())?;
+            Ok(Variant::hydrate(&events)?.expect("Variant is not None"))
+        };
+        let blue = variant("blue")?;
+        let green = variant("green")?;
+        let rule = |attribute: &str, operator, value: &str, variant: &str| Rule {
+            clauses: vec![Clause {
+                attribute: attribute.to_owned(),
+                operator,
+                values: vec![value.to_owned()],
+            }],
+            variant: variant.to_owned(),
+            variant_id: None,
+        };
+        let toggle = toggle(&[
+            enabled()?,
+            Event::RolloutChanged {
+                environment_id: environment_id()?,
+                percentage: 0,
+            },
+            Event::RulesChanged {
+                environment_id: environment_id()?,
+                rules: vec![
+                    Rule {
+                        variant_id: Some(blue.id),
+                        ..rule("email", Operator::EndsWith, "@ourcorp.com", "blue")
+                    },
+                    rule("country", Operator::In, "GB", "off"),
+                    // Referring to the Variant by name only, as older Rules do
+                    rule(
+                        "app_version",
+                        Operator::Semver(Comparison::GreaterThanOrEqual),
+                        "2.3.0",
+                        "green",
+                    ),
+                ],
+            },
+        ])?;
+        let context = |attributes: &[(&str, &str)]| Context {
+            user_id: Some("user".to_owned()),
+            attributes: attributes
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), (*value).to_owned()))
+                .collect(),
+        };
+        let variants = |variants: &[&Variant]| -> Variants {
+            variants
+                .iter()
+                .map(|variant| (variant.id, (*variant).clone()))
+                .collect()
+        };
+        let live = variants(&[&blue, &green]);
+
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("email", "me@ourcorp.com"), ("country", "GB")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &live,
+            ),
+            Evaluation {
+                value: true,
+                variant: "blue".to_owned(),
+                variant_id: Some(blue.id),
+                reason: Reason::RuleMatch(0),
+            },
+        );
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("country", "GB"), ("app_version", "2.3.0")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &live,
+            ),
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::RuleMatch(1),
+            },
+        );
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("app_version", "2.10.1")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &live,
+            )
+            .variant_id,
+            Some(green.id),
+        );
+        // Falls through to the rollout
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("app_version", "2.2.0")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &live,
+            ),
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::Rollout,
+            },
+        );
+
+        // Renamed Variants are served by their new name, and Rules
+        // serving retired Variants are skipped
+        let navy = Variant::apply_event(
+            Some(blue.clone()),
+            &VariantEvent::Renamed {
+                name: "navy".to_owned(),
+            },
+        )?;
+        let retired = Variant::apply_event(Some(green.clone()), &VariantEvent::Retired)?;
+        let changed = variants(&[&navy, &retired]);
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("email", "me@ourcorp.com")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &changed,
+            )
+            .variant,
+            "navy",
+        );
+        assert_eq!(
+            evaluate(
+                &toggle,
+                environment_id()?,
+                &context(&[("app_version", "2.10.1")]),
+                &Segments::new(),
+                &Toggles::new(),
+                &changed,
+            )
+            .reason,
+            Reason::Rollout,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_segments() -> Result<(), Error> {
+        let segment_id = SegmentId::from(Uuid::new_v4());
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let keys = |keys: &[&str]| keys.iter().map(|key| (*key).to_owned()).collect();
+        let segment = Segment::hydrate(&[
+            SegmentEvent::Created {
+                id: segment_id,
+                project_id,
+                name: "staff".to_owned(),
+            },
+            SegmentEvent::IncludedChanged {
+                keys: keys(&["alice"]),
+            },
+            SegmentEvent::ExcludedChanged {
+                keys: keys(&["bob"]),
+            },
+            SegmentEvent::RulesChanged {
+                rules: vec![SegmentRule {
+                    clauses: vec![Clause {
+                        attribute: "email".to_owned(),
+                        operator: Operator::EndsWith,
+                        values: vec!["@ourcorp.com".to_owned()],
+                    }],
+                }],
+            },
+        ])?
+        .expect("Segment is not None");
+        let segments: Segments = vec![(segment_id, segment)].into_iter().collect();
+        let toggle = toggle(&[
+            enabled()?,
+            Event::RolloutChanged {
+                environment_id: environment_id()?,
+                percentage: 0,
+            },
+            Event::RulesChanged {
+                environment_id: environment_id()?,
+                rules: vec![Rule {
+                    clauses: vec![Clause {
+                        attribute: "user_id".to_owned(),
+                        operator: Operator::InSegment,
+                        values: vec![segment_id.to_string()],
+                    }],
+                    variant: "on".to_owned(),
+                    variant_id: None,
+                }],
+            },
+        ])?;
+        let value = |user_id: &str, email: &str| -> Result<bool, Error> {
+            let context = Context {
+                user_id: Some(user_id.to_owned()),
+                attributes: vec![("email".to_owned(), email.to_owned())]
+                    .into_iter()
+                    .collect(),
+            };
+            Ok(evaluate(
+                &toggle,
+                environment_id()?,
+                &context,
+                &segments,
+                &Toggles::new(),
+                &Variants::new(),
+            )
+            .value)
+        };
+
+        assert!(value("alice", "alice@gmail.com")?);
+        assert!(value("carol", "carol@ourcorp.com")?);
+        assert!(!value("bob", "bob@ourcorp.com")?);
+        assert!(!value("dave", "dave@gmail.com")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluate_prerequisites() -> Result<(), Error> {
+        let parent = toggle(&[enabled()?])?;
+        let child = Toggle::hydrate(&[
+            Event::Created {
+                id: ToggleId::from(Uuid::new_v4()),
+                project_id: parent.project_id(),
+                name: "child".to_owned(),
+            },
+            enabled()?,
+            Event::PrerequisitesChanged {
+                prerequisites: vec![Prerequisite {
+                    toggle_id: *parent.id(),
+                    variant: "on".to_owned(),
+                    variant_id: None,
+                }],
+            },
+        ])?
+        .expect("Toggle is not None");
+        let evaluate_child = |parent: Toggle| -> Result<Evaluation, Error> {
+            let toggles: Toggles = vec![(*parent.id(), parent)].into_iter().collect();
+            Ok(evaluate(
+                &child,
+                environment_id()?,
+                &Context::default(),
+                &Segments::new(),
+                &toggles,
+                &Variants::new(),
+            ))
+        };
+
+        assert_eq!(
+            evaluate_child(parent.clone())?,
+            Evaluation {
+                value: true,
+                variant: "on".to_owned(),
+                variant_id: None,
+                reason: Reason::On,
+            },
+        );
+        let disabled = Toggle::apply_event(
+            Some(parent),
+            &Event::Disabled {
+                environment_id: environment_id()?,
+            },
+        )?;
+        assert_eq!(
+            evaluate_child(disabled.clone())?,
+            Evaluation {
+                value: false,
+                variant: "off".to_owned(),
+                variant_id: None,
+                reason: Reason::PrerequisiteFailed(*disabled.id()),
+            },
+        );
+        assert_eq!(
+            evaluate(
+                &child,
+                environment_id()?,
+                &Context::default(),
+                &Segments::new(),
+                &Toggles::new(),
+                &Variants::new(),
+            )
+            .reason,
+            Reason::PrerequisiteFailed(*disabled.id()),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use failure_derive::Fail;
+
+use crate::domain::Generation;
+
+#[derive(Debug, Fail)]
+pub enum DomainEventError {
+    #[fail(display = "failed to parse uuid")]
+    UuidParseError(#[cause] uuid::parser::ParseError),
+    #[fail(display = "failed to parse datetime")]
+    DateTimeParseError(#[cause] chrono::format::ParseError),
+    #[fail(display = "failed to parse JSON data")]
+    JsonParseError(#[cause] serde_json::error::Error),
+}
+
+impl From<uuid::parser::ParseError> for DomainEventError {
+    fn from(e: uuid::parser::ParseError) -> Self {
+        DomainEventError::UuidParseError(e)
+    }
+}
+
+impl From<chrono::format::ParseError> for DomainEventError {
+    fn from(e: chrono::format::ParseError) -> Self {
+        DomainEventError::DateTimeParseError(e)
+    }
+}
+
+impl From<serde_json::error::Error> for DomainEventError {
+    fn from(e: serde_json::error::Error) -> Self {
+        DomainEventError::JsonParseError(e)
+    }
+}
+
+/// Errors raised by the event store, generic over the
+/// error type of the Aggregate being stored.
+#[derive(Debug, Fail)]
+pub enum EventStoreError<E: failure::Fail> {
+    #[fail(display = "database error")]
+    DatabaseError(#[cause] diesel::result::Error),
+    #[fail(display = "domain event error")]
+    DomainEventError(#[cause] DomainEventError),
+    #[fail(display = "aggregate error")]
+    AggregateError(#[cause] E),
+    #[fail(display = "json format error")]
+    JsonFormatError(#[cause] serde_json::error::Error),
+    #[fail(display = "not found error")]
+    NotFoundError,
+    /// The events were to be written at generation `expected`
+    /// but the stored stream is already at generation `actual`.
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
+}
+
+impl<E: failure::Fail> EventStoreError<E> {
+    /// Whether the same call could succeed if made again, such as once the
+    /// database is reachable or the conflicting write has been read.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            EventStoreError::DatabaseError(_) | EventStoreError::ConcurrencyConflict { .. }
+        )
+    }
+}
+
+impl<E: failure::Fail> From<diesel::result::Error> for EventStoreError<E> {
+    fn from(e: diesel::result::Error) -> Self {
+        EventStoreError::DatabaseError(e)
+    }
+}
+
+impl<E: failure::Fail> From<DomainEventError> for EventStoreError<E> {
+    fn from(e: DomainEventError) -> Self {
+        EventStoreError::DomainEventError(e)
+    }
+}
+
+impl<E: failure::Fail> From<serde_json::error::Error> for EventStoreError<E> {
+    fn from(e: serde_json::error::Error) -> Self {
+        EventStoreError::JsonFormatError(e)
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use failure::Fail;
+use uuid::Uuid;
+
+use crate::domain::{Aggregate, DomainEvent, Generation, Repository};
+
+use super::error::EventStoreError;
+
+/// Event sourced Repository for any Aggregate that keeps its events in
+/// memory, for testing handlers and for tools that don't need them to
+/// outlive the process. Raises the same errors as the stores on a database.
+pub struct InMemoryRepository<A: Aggregate> {
+    streams: HashMap<Uuid, Vec<DomainEvent<A>>>,
+}
+
+impl<A: Aggregate> InMemoryRepository<A> {
+    pub fn new() -> Self {
+        Self {
+            streams: HashMap::new(),
+        }
+    }
+}
+
+impl<A: Aggregate> Default for InMemoryRepository<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A> InMemoryRepository<A>
+where
+    A: Aggregate,
+    A::Id: Copy + Into<Uuid>,
+{
+    /// Every event stored for the aggregate, in the order they were stored.
+    pub fn events(&self, id: A::Id) -> &[DomainEvent<A>] {
+        self.streams
+            .get(&id.into())
+            .map_or(&[], |stream| stream.as_slice())
+    }
+
+    /// The Aggregate from its `events`, in the order they were stored.
+    fn hydrate<'e, I>(events: I) -> Result<A, EventStoreError<A::Err>>
+    where
+        A: 'e,
+        A::Err: Fail,
+        I: IntoIterator<Item = &'e DomainEvent<A>>,
+    {
+        let mut state = None;
+        for event in events {
+            state =
+                Some(A::apply_event(state, &event.event).map_err(EventStoreError::AggregateError)?);
+        }
+        state.ok_or_else(|| EventStoreError::NotFoundError)
+    }
+}
+
+impl<A> Repository for InMemoryRepository<A>
+where
+    A: Aggregate,
+    A::Id: Copy + Into<Uuid>,
+    A::Event: Clone,
+    A::Err: Fail,
+{
+    type Aggregate = A;
+    type Err = EventStoreError<A::Err>;
+
+    fn get(&self, id: A::Id) -> Result<A, Self::Err> {
+        Self::hydrate(self.events(id))
+    }
+
+    fn get_at(&self, id: A::Id, generation: Generation) -> Result<A, Self::Err> {
+        // Each event is stored at the generation of its index in the stream
+        let count = (i32::from(generation) + 1).max(0) as usize;
+        let aggregate = Self::hydrate(self.events(id).iter().take(count))?;
+        // The Aggregate never got that far
+        if aggregate.generation() != generation {
+            return Err(EventStoreError::NotFoundError);
+        }
+        Ok(aggregate)
+    }
+
+    fn get_as_of(&self, id: A::Id, at: DateTime<Utc>) -> Result<A, Self::Err> {
+        Self::hydrate(
+            self.events(id)
+                .iter()
+                .take_while(|event| event.created_at <= at),
+        )
+    }
+
+    fn persist(
+        &mut self,
+        generation: Generation,
+        events: &[DomainEvent<A>],
+    ) -> Result<(), Self::Err> {
+        let expected = generation;
+        if let Some(event) = events.first() {
+            let stored = self.events(event.aggregate_id).len() as i32;
+            let actual = Generation::from(stored);
+            if actual != expected {
+                return Err(EventStoreError::ConcurrencyConflict { expected, actual });
+            }
+        }
+
+        for event in events {
+            self.streams
+                .entry(event.aggregate_id.into())
+                .or_default()
+                .push(DomainEvent {
+                    id: event.id,
+                    aggregate_id: event.aggregate_id,
+                    created_at: event.created_at,
+                    metadata: event.metadata.clone(),
+                    event: event.event.clone(),
+                });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::{Duration, Utc};
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::environment::EnvironmentId;
+    use crate::event_store::error::EventStoreError;
+    use crate::project::{Project, ProjectEvent, ProjectId};
+    use crate::toggle::{self, Toggle, ToggleId};
+
+    use super::InMemoryRepository;
+
+    #[test]
+    fn test_get_not_found() {
+        let repository = InMemoryRepository::<Project>::new();
+
+        match repository.get(ProjectId::from(Uuid::new_v4())) {
+            Err(EventStoreError::NotFoundError) => (),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_persist_conflict() -> Result<(), Error> {
+        let mut repository = InMemoryRepository::<Project>::new();
+        let id = ProjectId::from(Uuid::new_v4());
+        let event = |event| DomainEvent {
+            id: DomainEventId::new(Uuid::new_v4()),
+            aggregate_id: id,
+            created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+            metadata: Metadata::default(),
+            event,
+        };
+        let created = ProjectEvent::Created {
+            id,
+            name: "test".into(),
+        };
+
+        repository.persist(Generation::first(), &[event(created.clone())])?;
+
+        match repository.persist(Generation::first(), &[event(created)]) {
+            Err(EventStoreError::ConcurrencyConflict { expected, actual }) => {
+                assert_eq!(expected, Generation::first());
+                assert_eq!(actual, Generation::first().next());
+            }
+            result => panic!("expected concurrency conflict, got {:?}", result),
+        }
+        // Nothing is stored for a conflicting write
+        assert_eq!(repository.events(id).len(), 1);
+
+        repository.persist(Generation::first().next(), &[event(ProjectEvent::Archived)])?;
+        let project = repository.get(id)?;
+        assert!(project.archived);
+        assert_eq!(project.generation, Generation::first().next());
+        Ok(())
+    }
+
+    #[test]
+    fn test_point_in_time() -> Result<(), Error> {
+        let mut repository = InMemoryRepository::<Toggle>::new();
+        let id = ToggleId::from(Uuid::new_v4());
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let mut events = vec![toggle::Event::Created {
+            id,
+            project_id: ProjectId::from(Uuid::new_v4()),
+            name: "test".to_owned(),
+        }];
+        for percentage in 1..=10 {
+            events.push(toggle::Event::RolloutChanged {
+                environment_id,
+                percentage: percentage * 10,
+            });
+        }
+        let start = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let domain_events = events
+            .iter()
+            .enumerate()
+            .map(|(i, event)| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: id,
+                created_at: start + Duration::minutes(i as i64),
+                metadata: Metadata::default(),
+                event: event.clone(),
+            })
+            .collect::<Vec<_>>();
+        repository.persist(Generation::first(), &domain_events)?;
+
+        assert_eq!(Some(repository.get(id)?), Toggle::hydrate(&events)?);
+        let expected = Toggle::hydrate(&events[..=4])?.expect("Toggle is not None");
+        assert_eq!(repository.get_at(id, Generation::from(4))?, expected);
+        let toggle = repository.get_as_of(id, start + Duration::seconds(4 * 60 + 30))?;
+        assert_eq!(toggle, expected);
+
+        for generation in &[-1, 11] {
+            match repository.get_at(id, Generation::from(*generation)) {
+                Err(EventStoreError::NotFoundError) => (),
+                result => panic!("expected not found, got {:?}", result),
+            }
+        }
+        match repository.get_as_of(id, start - Duration::minutes(1)) {
+            Err(EventStoreError::NotFoundError) => Ok(()),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+}
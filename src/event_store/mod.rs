@@ -0,0 +1,644 @@
+pub mod error;
+pub mod memory;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod upcast;
+
+use std::marker::PhantomData;
+
+use chrono::{DateTime, Utc};
+use diesel;
+use diesel::result::DatabaseErrorKind;
+use diesel::result::Error::DatabaseError;
+use diesel::sqlite::SqliteConnection;
+use diesel::{Connection, RunQueryDsl};
+use failure::Fail;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use uuid::Uuid;
+
+use crate::database::models::{Event, NewEvent, NewSnapshot};
+use crate::database::schema;
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Repository,
+};
+
+use self::error::{DomainEventError, EventStoreError};
+use self::upcast::{upcast, UPCASTERS};
+
+impl<A> DomainEvent<A>
+where
+    A: Aggregate,
+    A::Id: From<Uuid>,
+    A::Event: DeserializeOwned,
+{
+    pub fn from_event(event: Event) -> Result<Self, DomainEventError> {
+        Ok(Self {
+            id: DomainEventId::new(Uuid::parse_str(&event.id)?),
+            aggregate_id: Uuid::parse_str(&event.aggregate_id)?.into(),
+            created_at: event.created_at.parse::<DateTime<Utc>>()?,
+            metadata: serde_json::from_str(&event.metadata)?,
+            event: serde_json::from_value(upcast(
+                UPCASTERS,
+                &event.aggregate_type,
+                &event.type_,
+                event.version,
+                serde_json::from_str(&event.data)?,
+            ))?,
+        })
+    }
+}
+
+impl Event {
+    /// The event as one of an `A`, or None if it belongs to another kind of Aggregate.
+    pub fn decode<A>(&self) -> Result<Option<DomainEvent<A>>, DomainEventError>
+    where
+        A: Aggregate,
+        A::Id: From<Uuid>,
+        A::Event: DeserializeOwned,
+    {
+        if self.aggregate_type != A::TYPE {
+            return Ok(None);
+        }
+        DomainEvent::from_event(self.clone()).map(Some)
+    }
+}
+
+/// The log of events of every aggregate, in the order they were stored.
+/// Every event has a position in the log, greater than that of every
+/// event stored before it.
+pub trait EventStore {
+    type Err;
+
+    /// Events stored after position `from`, at most `limit` of them.
+    /// Positions start at 1, so from 0 reads from the first event.
+    fn read_all(&self, from: i64, limit: usize) -> Result<Vec<Event>, Self::Err>;
+}
+
+/// A connection to a database events are appended to.
+trait AppendLock: Connection {
+    /// Run `f` holding back every other append until the transaction it is
+    /// in ends, so events become visible in the order of their positions
+    /// and whoever has read up to a position never misses one before it.
+    fn with_append_lock<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>;
+}
+
+impl AppendLock for SqliteConnection {
+    // Writers to SQLite already take turns holding the lock on its file
+    fn with_append_lock<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        f()
+    }
+}
+
+/// Event sourced Repository for any Aggregate, storing every event in
+/// the shared `events` table of the database `C` is a connection to.
+pub struct DieselEventStore<'a, C, A> {
+    pub db: &'a C,
+    aggregate: PhantomData<A>,
+}
+
+impl<'a, C, A> DieselEventStore<'a, C, A> {
+    pub fn new(db: &'a C) -> Self {
+        Self {
+            db,
+            aggregate: PhantomData,
+        }
+    }
+}
+
+/// The log of events shared by every aggregate's DieselEventStore.
+pub struct DieselEventLog<'a, C> {
+    pub db: &'a C,
+}
+
+/// Implements the stores for a kind of diesel connection. The queries are
+/// the same for every database, but diesel checks them against each one.
+macro_rules! impl_event_store {
+    ($connection:ty) => {
+        impl<'a, A: Aggregate> DieselEventStore<'a, $connection, A> {
+            /// The generation the next event for the aggregate must be written at.
+            fn next_generation(&self, id: &str) -> Result<Generation, diesel::result::Error> {
+                use crate::database::schema::events::dsl::{
+                    aggregate_id, aggregate_type, events, generation,
+                };
+                use diesel::prelude::*;
+
+                let current = events
+                    .filter(aggregate_id.eq(id))
+                    .filter(aggregate_type.eq(A::TYPE))
+                    .select(diesel::dsl::max(generation))
+                    .first::<Option<i32>>(self.db)?;
+                Ok(current
+                    .map(|current| Generation::from(current).next())
+                    .unwrap_or_else(Generation::first))
+            }
+        }
+
+        impl<'a, A> DieselEventStore<'a, $connection, A>
+        where
+            A: Aggregate + DeserializeOwned + Serialize,
+            A::Id: Copy + Into<Uuid>,
+        {
+            /// The Aggregate as of its latest snapshot up to generation `until`, if it
+            /// takes snapshots and has one. A snapshot that no longer deserializes,
+            /// because the Aggregate has changed shape since it was taken, is passed
+            /// over for the events it was taken from.
+            fn latest_snapshot(
+                &self,
+                id: &str,
+                until: Option<Generation>,
+            ) -> Result<Option<A>, diesel::result::Error> {
+                use crate::database::schema::snapshots::dsl;
+                use diesel::prelude::*;
+
+                if A::SNAPSHOT_INTERVAL.is_none() {
+                    return Ok(None);
+                }
+                let data = dsl::snapshots
+                    .filter(dsl::aggregate_id.eq(id))
+                    .filter(dsl::generation.le(until.map_or(i32::MAX, i32::from)))
+                    .order(dsl::generation.desc())
+                    .select(dsl::data)
+                    .first::<String>(self.db)
+                    .optional()?;
+                Ok(data.and_then(|data| serde_json::from_str(&data).ok()))
+            }
+
+            fn save_snapshot(&self, aggregate: &A) -> Result<(), EventStoreError<A::Err>>
+            where
+                A::Err: Fail,
+            {
+                use crate::database::schema::snapshots::dsl;
+                use diesel::prelude::*;
+
+                let id: Uuid = (*aggregate.id()).into();
+                let id = id.to_string();
+                let generation = aggregate.generation().into();
+                // Replacing any taken at the same generation
+                diesel::delete(
+                    dsl::snapshots
+                        .filter(dsl::aggregate_id.eq(&id))
+                        .filter(dsl::generation.eq(generation)),
+                )
+                .execute(self.db)?;
+                diesel::insert_into(schema::snapshots::table)
+                    .values(&NewSnapshot {
+                        aggregate_id: &id,
+                        generation,
+                        data: &serde_json::to_string(aggregate)?,
+                    })
+                    .execute(self.db)?;
+                Ok(())
+            }
+
+            /// The Aggregate from `snapshot` and the events stored after it, up to
+            /// generation `until` and stopping at the first event `include` is false for.
+            fn hydrate<F>(
+                &self,
+                id: &str,
+                snapshot: Option<A>,
+                until: Option<Generation>,
+                include: F,
+            ) -> Result<A, EventStoreError<A::Err>>
+            where
+                A::Id: From<Uuid>,
+                A::Event: DeserializeOwned,
+                A::Err: Fail,
+                F: Fn(&DomainEvent<A>) -> bool,
+            {
+                use crate::database::schema::events::dsl::{
+                    aggregate_id, aggregate_type, events, generation,
+                };
+                use diesel::prelude::*;
+
+                let from = snapshot
+                    .as_ref()
+                    .map(|snapshot| snapshot.generation().next())
+                    .unwrap_or_else(Generation::first);
+                let until = until.map_or(i32::MAX, i32::from);
+                let mut results = vec![];
+                // Only the aggregate's own stream, an id of another kind being unknown
+                for event in events
+                    .filter(aggregate_id.eq(id))
+                    .filter(aggregate_type.eq(A::TYPE))
+                    .filter(generation.ge(i32::from(from)))
+                    .filter(generation.le(until))
+                    .order(generation.asc())
+                    .load::<Event>(self.db)?
+                {
+                    let event = DomainEvent::<A>::from_event(event)?;
+                    if !include(&event) {
+                        break;
+                    }
+                    results.push(event.event);
+                }
+                let aggregate =
+                    A::hydrate_from(snapshot, &results).map_err(EventStoreError::AggregateError)?;
+                aggregate.ok_or_else(|| EventStoreError::NotFoundError)
+            }
+        }
+
+        impl<'a, A> Repository for DieselEventStore<'a, $connection, A>
+        where
+            A: Aggregate + DeserializeOwned + Serialize,
+            A::Id: Copy + From<Uuid> + Into<Uuid>,
+            A::Event: AggregateEvent + DeserializeOwned + Serialize,
+            A::Err: Fail,
+        {
+            type Aggregate = A;
+            type Err = EventStoreError<A::Err>;
+
+            fn get(&self, id: A::Id) -> Result<A, Self::Err> {
+                let id: Uuid = id.into();
+                let id = id.to_string();
+                let snapshot = self.latest_snapshot(&id, None)?;
+                self.hydrate(&id, snapshot, None, |_| true)
+            }
+
+            fn get_at(&self, id: A::Id, generation: Generation) -> Result<A, Self::Err> {
+                let id: Uuid = id.into();
+                let id = id.to_string();
+                let snapshot = self.latest_snapshot(&id, Some(generation))?;
+                let aggregate = self.hydrate(&id, snapshot, Some(generation), |_| true)?;
+                // The Aggregate never got that far
+                if aggregate.generation() != generation {
+                    return Err(EventStoreError::NotFoundError);
+                }
+                Ok(aggregate)
+            }
+
+            fn get_as_of(&self, id: A::Id, at: DateTime<Utc>) -> Result<A, Self::Err> {
+                let id: Uuid = id.into();
+                let id = id.to_string();
+                // Snapshots don't say when they were taken, so every event is replayed
+                self.hydrate(&id, None, None, |event| event.created_at <= at)
+            }
+
+            fn persist(
+                &mut self,
+                generation: Generation,
+                events: &[DomainEvent<A>],
+            ) -> Result<(), Self::Err> {
+                self.db.with_append_lock(|| {
+                    let expected = generation;
+                    if let Some(event) = events.first() {
+                        let id: Uuid = event.aggregate_id.into();
+                        let actual = self.next_generation(&id.to_string())?;
+                        if actual != expected {
+                            return Err(EventStoreError::ConcurrencyConflict { expected, actual });
+                        }
+                    }
+
+                    let mut generation = generation;
+                    for event in events {
+                        let id: Uuid = event.aggregate_id.into();
+                        let id = id.to_string();
+                        let new = NewEvent {
+                            id: &event.id.to_string(),
+                            aggregate_id: &id,
+                            generation: generation.into(),
+                            created_at: &event.created_at.to_rfc3339(),
+                            type_: &event.event.type_(),
+                            data: &serde_json::to_string(&event.event)?,
+                            aggregate_type: A::TYPE,
+                            metadata: &serde_json::to_string(&event.metadata)?,
+                            version: A::EVENT_VERSION,
+                        };
+                        // A failed statement aborts the whole of a PostgreSQL transaction,
+                        // so the insert gets a savepoint of its own to roll back to
+                        let result = self.db.transaction(|| {
+                            diesel::insert_into(schema::events::table)
+                                .values(&new)
+                                .execute(self.db)
+                        });
+                        match result {
+                            // Another writer got in between checking and inserting
+                            Err(DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                                let actual = self.next_generation(&id)?;
+                                return Err(EventStoreError::ConcurrencyConflict {
+                                    expected,
+                                    actual,
+                                });
+                            }
+                            result => result?,
+                        };
+                        generation = generation.next();
+                    }
+
+                    if let (Some(interval), Some(event)) = (A::SNAPSHOT_INTERVAL, events.first()) {
+                        // Whenever the stream reaches another multiple of the interval
+                        let before = i32::from(expected) / interval;
+                        let after = i32::from(generation) / interval;
+                        if after > before {
+                            self.save_snapshot(&self.get(event.aggregate_id)?)?;
+                        }
+                    }
+
+                    Ok(())
+                })
+            }
+        }
+
+        impl<'a> EventStore for DieselEventLog<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn read_all(&self, from: i64, limit: usize) -> Result<Vec<Event>, Self::Err> {
+                use crate::database::schema::events::dsl::{events, position};
+                use diesel::prelude::*;
+
+                events
+                    .filter(position.gt(from))
+                    .order(position.asc())
+                    .limit(limit as i64)
+                    .load::<Event>(self.db)
+            }
+        }
+    };
+}
+
+impl_event_store!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_event_store!(diesel::pg::PgConnection);
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, Metadata, Repository};
+    use crate::environment::EnvironmentId;
+    use crate::project::{Project, ProjectEvent, ProjectId};
+    use crate::toggle::{self, Toggle, ToggleId};
+
+    use super::error::EventStoreError;
+    use super::{
+        DieselEventLog, DieselEventStore, DomainEvent, DomainEventId, EventStore, Generation,
+    };
+
+    #[test]
+    fn test_get_not_found() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let repository = DieselEventStore::<_, Project>::new(db);
+        let id: ProjectId = "936da01f-9abd-4d9d-80c7-02af85c822a8".parse()?;
+
+        match repository.get(id) {
+            Err(EventStoreError::NotFoundError) => Ok(()),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_get_other_aggregate() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let id = Uuid::new_v4();
+        DieselEventStore::<_, Toggle>::new(db).persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: ToggleId::from(id),
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: toggle::Event::Created {
+                    id: ToggleId::from(id),
+                    project_id: ProjectId::from(Uuid::new_v4()),
+                    name: "checkout".into(),
+                },
+            }],
+        )?;
+
+        // A Toggle's stream isn't a Project's, whatever its id
+        match DieselEventStore::<_, Project>::new(db).get(ProjectId::from(id)) {
+            Err(EventStoreError::NotFoundError) => Ok(()),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_persist_conflict() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let id: ProjectId = "936da01f-9abd-4d9d-80c7-02af85c822a8".parse()?;
+        let event = || DomainEvent {
+            id: DomainEventId::new(Uuid::new_v4()),
+            aggregate_id: id,
+            created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+            metadata: Metadata::default(),
+            event: ProjectEvent::Created {
+                id,
+                name: "test".into(),
+            },
+        };
+
+        repository.persist(Generation::first(), &[event()])?;
+
+        match repository.persist(Generation::first(), &[event()]) {
+            Err(EventStoreError::ConcurrencyConflict { expected, actual }) => {
+                assert_eq!(expected, Generation::first());
+                assert_eq!(actual, Generation::first().next());
+                Ok(())
+            }
+            result => panic!("expected concurrency conflict, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_read_all() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let a = ProjectId::from(Uuid::new_v4());
+        let b = ProjectId::from(Uuid::new_v4());
+        let event = |id, event| DomainEvent {
+            id: DomainEventId::new(Uuid::new_v4()),
+            aggregate_id: id,
+            created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+            metadata: Metadata::default(),
+            event,
+        };
+        let created = |id| ProjectEvent::Created {
+            id,
+            name: id.to_string(),
+        };
+
+        repository.persist(Generation::first(), &[event(a, created(a))])?;
+        repository.persist(Generation::first(), &[event(b, created(b))])?;
+        repository.persist(
+            Generation::first().next(),
+            &[event(a, ProjectEvent::Archived)],
+        )?;
+
+        let log = DieselEventLog { db };
+        let events = log.read_all(0, 10)?;
+        let stored = events
+            .iter()
+            .map(|e| (e.position, e.aggregate_id.clone(), e.type_.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stored,
+            vec![
+                (1, a.to_string(), "Created"),
+                (2, b.to_string(), "Created"),
+                (3, a.to_string(), "Archived"),
+            ],
+        );
+        assert_eq!(log.read_all(1, 1)?, vec![events[1].clone()]);
+        assert!(log.read_all(3, 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_metadata() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let id = ProjectId::from(Uuid::new_v4());
+        let metadata = Metadata {
+            actor: Some("alice".to_owned()),
+            correlation_id: Some(Uuid::new_v4()),
+            reason: Some("launch".to_owned()),
+            ..Metadata::default()
+        };
+        repository.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: id,
+                created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                metadata: metadata.clone(),
+                event: ProjectEvent::Created {
+                    id,
+                    name: "test".into(),
+                },
+            }],
+        )?;
+
+        let event = DieselEventLog { db }.read_all(0, 1)?.remove(0);
+        // Missing metadata is left out
+        assert!(!event.metadata.contains("api_key_id"));
+        let event = DomainEvent::<Project>::from_event(event)?;
+        assert_eq!(event.metadata, metadata);
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots() -> Result<(), Error> {
+        use crate::database::schema::snapshots::dsl;
+
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Toggle>::new(db);
+        let id = ToggleId::from(Uuid::new_v4());
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let mut events = vec![toggle::Event::Created {
+            id,
+            project_id: ProjectId::from(Uuid::new_v4()),
+            name: "test".to_owned(),
+        }];
+        for i in 1..250 {
+            events.push(if i % 2 == 1 {
+                toggle::Event::Enabled { environment_id }
+            } else {
+                toggle::Event::Disabled { environment_id }
+            });
+        }
+        let mut generation = Generation::first();
+        for event in &events {
+            repository.persist(
+                generation,
+                &[DomainEvent {
+                    id: DomainEventId::new(Uuid::new_v4()),
+                    aggregate_id: id,
+                    created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                    metadata: Metadata::default(),
+                    event: event.clone(),
+                }],
+            )?;
+            generation = generation.next();
+        }
+
+        let generations = dsl::snapshots
+            .select(dsl::generation)
+            .order(dsl::generation.asc())
+            .load::<i32>(db)?;
+        assert_eq!(generations, vec![99, 199]);
+        let expected = Toggle::hydrate(&events)?;
+        assert_eq!(Some(repository.get(id)?), expected);
+
+        // Snapshots that don't deserialize are passed over
+        diesel::update(dsl::snapshots.filter(dsl::generation.eq(199)))
+            .set(dsl::data.eq("{}"))
+            .execute(db)?;
+        assert_eq!(Some(repository.get(id)?), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_point_in_time() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Toggle>::new(db);
+        let id = ToggleId::from(Uuid::new_v4());
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let mut events = vec![toggle::Event::Created {
+            id,
+            project_id: ProjectId::from(Uuid::new_v4()),
+            name: "test".to_owned(),
+        }];
+        for percentage in 1..=150 {
+            events.push(toggle::Event::RolloutChanged {
+                environment_id,
+                percentage: (percentage % 100) as u8,
+            });
+        }
+        let start = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let mut generation = Generation::first();
+        for (i, event) in events.iter().enumerate() {
+            repository.persist(
+                generation,
+                &[DomainEvent {
+                    id: DomainEventId::new(Uuid::new_v4()),
+                    aggregate_id: id,
+                    created_at: start + Duration::minutes(i as i64),
+                    metadata: Metadata::default(),
+                    event: event.clone(),
+                }],
+            )?;
+            generation = generation.next();
+        }
+
+        // Before and after the snapshot taken at generation 99
+        for &at in &[42, 120] {
+            let expected = Toggle::hydrate(&events[..=at])?.expect("Toggle is not None");
+            let toggle = repository.get_at(id, Generation::from(at as i32))?;
+            assert_eq!(toggle, expected);
+            let toggle =
+                repository.get_as_of(id, start + Duration::seconds(at as i64 * 60 + 30))?;
+            assert_eq!(toggle, expected);
+        }
+
+        match repository.get_at(id, Generation::from(151)) {
+            Err(EventStoreError::NotFoundError) => (),
+            result => panic!("expected not found, got {:?}", result),
+        }
+        match repository.get_as_of(id, start - Duration::minutes(1)) {
+            Err(EventStoreError::NotFoundError) => (),
+            result => panic!("expected not found, got {:?}", result),
+        }
+        Ok(())
+    }
+}
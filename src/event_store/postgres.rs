@@ -0,0 +1,253 @@
+//! The stores on PostgreSQL, keeping events in the tables created by
+//! `migrations_postgres`. They raise the same errors as on SQLite,
+//! so handlers work with either.
+
+use diesel::pg::PgConnection;
+use diesel::Connection;
+
+use super::AppendLock;
+
+impl AppendLock for PgConnection {
+    // Positions are handed out as events are inserted but become visible as
+    // transactions commit, which without the lock could be in another order.
+    // EXCLUSIVE mode still lets the events be read meanwhile.
+    fn with_append_lock<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<diesel::result::Error>,
+    {
+        self.transaction(|| {
+            self.execute("LOCK TABLE events IN EXCLUSIVE MODE")?;
+            f()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use chrono::offset::TimeZone;
+    use chrono::{Duration, Utc};
+    use diesel::pg::PgConnection;
+    use diesel::prelude::*;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEventId, Metadata, Repository};
+    use crate::environment::EnvironmentId;
+    use crate::project::{Project, ProjectEvent, ProjectId};
+    use crate::toggle::{self, Toggle, ToggleId};
+
+    use super::super::error::EventStoreError;
+    use super::super::{DieselEventLog, DieselEventStore, EventStore};
+    use super::super::{DomainEvent, Generation};
+
+    /// A connection to the empty database at POSTGRES_TEST_URL, migrated
+    /// within a transaction that's rolled back once the test is done. None
+    /// when the variable isn't set, the tests then having nothing to run on.
+    fn connect() -> Result<Option<PgConnection>, Error> {
+        let url = match std::env::var("POSTGRES_TEST_URL") {
+            Ok(url) => url,
+            Err(_) => return Ok(None),
+        };
+        let db = PgConnection::establish(&url)?;
+        db.begin_test_transaction()?;
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("migrations_postgres");
+        diesel_migrations::run_pending_migrations_in_directory(
+            &db,
+            &migrations,
+            &mut std::io::sink(),
+        )?;
+        Ok(Some(db))
+    }
+
+    fn event<A: Aggregate>(
+        id: A::Id,
+        created_at: chrono::DateTime<Utc>,
+        event: A::Event,
+    ) -> DomainEvent<A> {
+        DomainEvent {
+            id: DomainEventId::new(Uuid::new_v4()),
+            aggregate_id: id,
+            created_at,
+            metadata: Metadata::default(),
+            event,
+        }
+    }
+
+    #[test]
+    fn test_get_not_found() -> Result<(), Error> {
+        let db = &match connect()? {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let repository = DieselEventStore::<_, Project>::new(db);
+        let id = ProjectId::from(Uuid::new_v4());
+
+        match repository.get(id) {
+            Err(EventStoreError::NotFoundError) => Ok(()),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_persist_conflict() -> Result<(), Error> {
+        let db = &match connect()? {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let id = ProjectId::from(Uuid::new_v4());
+        let created = || ProjectEvent::Created {
+            id,
+            name: "test".into(),
+        };
+        let at = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+
+        repository.persist(Generation::first(), &[event(id, at, created())])?;
+
+        match repository.persist(Generation::first(), &[event(id, at, created())]) {
+            Err(EventStoreError::ConcurrencyConflict { expected, actual }) => {
+                assert_eq!(expected, Generation::first());
+                assert_eq!(actual, Generation::first().next());
+            }
+            result => panic!("expected concurrency conflict, got {:?}", result),
+        }
+        // The transaction is still usable after the conflict
+        repository.persist(
+            Generation::first().next(),
+            &[event(id, at, ProjectEvent::Archived)],
+        )?;
+        assert!(repository.get(id)?.archived);
+        Ok(())
+    }
+
+    #[test]
+    fn test_persist_locks_appends() -> Result<(), Error> {
+        let db = &match connect()? {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let id = ProjectId::from(Uuid::new_v4());
+        let at = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let locked = || {
+            diesel::select(diesel::dsl::sql::<diesel::sql_types::Bool>(
+                "EXISTS (SELECT * FROM pg_locks WHERE relation = 'events'::regclass \
+                 AND mode = 'ExclusiveLock' AND granted AND pid = pg_backend_pid())",
+            ))
+            .get_result::<bool>(db)
+        };
+
+        assert!(!locked()?);
+        repository.persist(
+            Generation::first(),
+            &[event(
+                id,
+                at,
+                ProjectEvent::Created {
+                    id,
+                    name: "test".into(),
+                },
+            )],
+        )?;
+        // Held until the transaction the events were appended in ends
+        assert!(locked()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_all() -> Result<(), Error> {
+        let db = &match connect()? {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut repository = DieselEventStore::<_, Project>::new(db);
+        let a = ProjectId::from(Uuid::new_v4());
+        let b = ProjectId::from(Uuid::new_v4());
+        let at = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let created = |id| ProjectEvent::Created {
+            id,
+            name: id.to_string(),
+        };
+
+        repository.persist(Generation::first(), &[event(a, at, created(a))])?;
+        repository.persist(Generation::first(), &[event(b, at, created(b))])?;
+        repository.persist(
+            Generation::first().next(),
+            &[event(a, at, ProjectEvent::Archived)],
+        )?;
+
+        let log = DieselEventLog { db };
+        let events = log.read_all(0, 10)?;
+        let stored = events
+            .iter()
+            .map(|e| (e.position, e.aggregate_id.clone(), e.type_.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            stored,
+            vec![
+                (1, a.to_string(), "Created"),
+                (2, b.to_string(), "Created"),
+                (3, a.to_string(), "Archived"),
+            ],
+        );
+        assert_eq!(log.read_all(1, 1)?, vec![events[1].clone()]);
+        assert!(log.read_all(3, 10)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshots_and_point_in_time() -> Result<(), Error> {
+        use crate::database::schema::snapshots::dsl;
+
+        let db = &match connect()? {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+        let mut repository = DieselEventStore::<_, Toggle>::new(db);
+        let id = ToggleId::from(Uuid::new_v4());
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let mut events = vec![toggle::Event::Created {
+            id,
+            project_id: ProjectId::from(Uuid::new_v4()),
+            name: "test".to_owned(),
+        }];
+        for percentage in 1..=150 {
+            events.push(toggle::Event::RolloutChanged {
+                environment_id,
+                percentage: (percentage % 100) as u8,
+            });
+        }
+        let start = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        let mut generation = Generation::first();
+        for (i, e) in events.iter().enumerate() {
+            let at = start + Duration::minutes(i as i64);
+            repository.persist(generation, &[event(id, at, e.clone())])?;
+            generation = generation.next();
+        }
+
+        let generations = dsl::snapshots
+            .select(dsl::generation)
+            .order(dsl::generation.asc())
+            .load::<i32>(db)?;
+        assert_eq!(generations, vec![99]);
+        assert_eq!(Some(repository.get(id)?), Toggle::hydrate(&events)?);
+
+        // Before and after the snapshot taken at generation 99
+        for &at in &[42, 120] {
+            let expected = Toggle::hydrate(&events[..=at])?.expect("Toggle is not None");
+            let toggle = repository.get_at(id, Generation::from(at as i32))?;
+            assert_eq!(toggle, expected);
+            let toggle =
+                repository.get_as_of(id, start + Duration::seconds(at as i64 * 60 + 30))?;
+            assert_eq!(toggle, expected);
+        }
+
+        match repository.get_at(id, Generation::from(151)) {
+            Err(EventStoreError::NotFoundError) => Ok(()),
+            result => panic!("expected not found, got {:?}", result),
+        }
+    }
+}
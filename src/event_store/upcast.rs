@@ -0,0 +1,140 @@
+use serde_json::{json, Value};
+
+/// Brings the payload of an event of one kind of Aggregate from one
+/// version of its shape to the next, so that events stored before the
+/// shape changed can still be read.
+pub struct Upcaster {
+    pub aggregate_type: &'static str,
+    /// Version the payload is brought up from, to the one after it.
+    pub version: i32,
+    /// Takes the type of the event along with its payload.
+    pub upcast: fn(&str, Value) -> Value,
+}
+
+/// Every Upcaster, in order of version for each kind of Aggregate.
+pub const UPCASTERS: &[Upcaster] = &[Upcaster {
+    aggregate_type: "variant",
+    version: 1,
+    upcast: variant_renamed_name,
+}];
+
+/// The payload of an event stored at `version`, brought up to the current
+/// version by the `upcasters` for the kind of Aggregate it belongs to.
+pub fn upcast(
+    upcasters: &[Upcaster],
+    aggregate_type: &str,
+    type_: &str,
+    version: i32,
+    data: Value,
+) -> Value {
+    upcasters
+        .iter()
+        .filter(|upcaster| upcaster.aggregate_type == aggregate_type)
+        .filter(|upcaster| upcaster.version >= version)
+        .fold(data, |data, upcaster| (upcaster.upcast)(type_, data))
+}
+
+/// Variants were renamed with a bare name, `{"Renamed": "name"}`, before
+/// Renamed had a name field like the events of other Aggregates.
+fn variant_renamed_name(type_: &str, data: Value) -> Value {
+    match data.get("Renamed") {
+        Some(Value::String(name)) if type_ == "Renamed" => json!({ "Renamed": { "name": name } }),
+        _ => data,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use crate::database::models::NewEvent;
+    use crate::database::schema;
+    use crate::domain::{Aggregate, Repository};
+    use crate::toggle::ToggleId;
+    use crate::variant::{Variant, VariantId};
+
+    use super::super::DieselEventStore;
+    use super::{upcast, Upcaster, UPCASTERS};
+
+    #[test]
+    fn test_upcast_in_order() {
+        let upcasters = &[
+            Upcaster {
+                aggregate_type: "test",
+                version: 1,
+                upcast: |_, data| json!([data, 2]),
+            },
+            Upcaster {
+                aggregate_type: "other",
+                version: 1,
+                upcast: |_, data| json!([data, "other"]),
+            },
+            Upcaster {
+                aggregate_type: "test",
+                version: 2,
+                upcast: |_, data| json!([data, 3]),
+            },
+        ];
+        assert_eq!(
+            upcast(upcasters, "test", "Created", 1, json!(1)),
+            json!([[1, 2], 3])
+        );
+        assert_eq!(
+            upcast(upcasters, "test", "Created", 2, json!(2)),
+            json!([2, 3])
+        );
+        assert_eq!(upcast(upcasters, "test", "Created", 3, json!(3)), json!(3));
+    }
+
+    #[test]
+    fn test_upcasters_reach_event_version() {
+        let versions = UPCASTERS
+            .iter()
+            .filter(|u| u.aggregate_type == Variant::TYPE)
+            .map(|u| u.version)
+            .collect::<Vec<_>>();
+        assert_eq!(versions, (1..Variant::EVENT_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_variant_fixture_rows() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let id = VariantId::from(Uuid::new_v4());
+        let toggle_id = ToggleId::from(Uuid::new_v4());
+        // Rows as version 1 stored them
+        let rows = vec![
+            (
+                "Created",
+                json!({ "Created": { "id": id, "toggle_id": toggle_id, "name": "on" } }),
+            ),
+            ("Renamed", json!({ "Renamed": "enabled" })),
+            ("Retired", json!("Retired")),
+        ];
+        for (generation, (type_, data)) in rows.into_iter().enumerate() {
+            diesel::insert_into(schema::events::table)
+                .values(&NewEvent {
+                    id: &Uuid::new_v4().to_string(),
+                    aggregate_id: &Uuid::from(id).to_string(),
+                    generation: generation as i32,
+                    created_at: &Utc::now().to_rfc3339(),
+                    type_,
+                    data: &data.to_string(),
+                    aggregate_type: Variant::TYPE,
+                    metadata: "{}",
+                    version: 1,
+                })
+                .execute(db)?;
+        }
+
+        let variant = DieselEventStore::<_, Variant>::new(db).get(id)?;
+        assert_eq!(variant.name, "enabled");
+        assert!(variant.retired);
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use failure_derive::Fail;
+
+use crate::environment::error::EnvironmentError;
+use crate::event_store::error::DomainEventError;
+use crate::project::error::ProjectError;
+use crate::segment::error::SegmentError;
+use crate::toggle::error::ToggleError;
+use crate::variant::error::VariantError;
+
+#[derive(Debug, Fail)]
+pub enum HistoryHandlerError {
+    #[fail(display = "database error")]
+    DatabaseError(#[cause] diesel::result::Error),
+    #[fail(display = "domain event error")]
+    DomainEventError(#[cause] DomainEventError),
+    #[fail(display = "project error")]
+    ProjectError(#[cause] ProjectError),
+    #[fail(display = "environment error")]
+    EnvironmentError(#[cause] EnvironmentError),
+    #[fail(display = "segment error")]
+    SegmentError(#[cause] SegmentError),
+    #[fail(display = "toggle error")]
+    ToggleError(#[cause] ToggleError),
+    #[fail(display = "variant error")]
+    VariantError(#[cause] VariantError),
+    #[fail(display = "project not found")]
+    ProjectNotFound,
+    #[fail(display = "toggle not found")]
+    ToggleNotFound,
+}
+
+impl From<diesel::result::Error> for HistoryHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        HistoryHandlerError::DatabaseError(e)
+    }
+}
+
+impl From<DomainEventError> for HistoryHandlerError {
+    fn from(e: DomainEventError) -> Self {
+        HistoryHandlerError::DomainEventError(e)
+    }
+}
+
+impl From<ProjectError> for HistoryHandlerError {
+    fn from(e: ProjectError) -> Self {
+        HistoryHandlerError::ProjectError(e)
+    }
+}
+
+impl From<EnvironmentError> for HistoryHandlerError {
+    fn from(e: EnvironmentError) -> Self {
+        HistoryHandlerError::EnvironmentError(e)
+    }
+}
+
+impl From<SegmentError> for HistoryHandlerError {
+    fn from(e: SegmentError) -> Self {
+        HistoryHandlerError::SegmentError(e)
+    }
+}
+
+impl From<ToggleError> for HistoryHandlerError {
+    fn from(e: ToggleError) -> Self {
+        HistoryHandlerError::ToggleError(e)
+    }
+}
+
+impl From<VariantError> for HistoryHandlerError {
+    fn from(e: VariantError) -> Self {
+        HistoryHandlerError::VariantError(e)
+    }
+}
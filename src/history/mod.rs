@@ -0,0 +1,725 @@
+pub mod error;
+
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::database::models::Event;
+use crate::domain::{Aggregate, AggregateEvent, DomainEvent, Metadata};
+use crate::environment::{Environment, EnvironmentEvent, EnvironmentId};
+use crate::project::{Project, ProjectEvent, ProjectId};
+use crate::projection::audit::AuditLog;
+use crate::segment::{Segment, SegmentEvent, SegmentId};
+use crate::toggle::schedule::ScheduledChange;
+use crate::toggle::{self, Toggle, ToggleId};
+use crate::variant::{Variant, VariantEvent, VariantId};
+
+use self::error::HistoryHandlerError;
+
+/// A change to a Project or anything in it, as someone
+/// looking back at what happened would want to read it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HistoryEntry {
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+    pub metadata: Metadata,
+    pub aggregate_type: String,
+    pub aggregate_id: Uuid,
+    /// Name of what was changed, as it was after the change.
+    pub name: String,
+    /// Toggle the change was made to, for changes to Toggles and their Variants.
+    pub toggle_id: Option<ToggleId>,
+    /// Environment the change was limited to, if any.
+    pub environment_id: Option<EnvironmentId>,
+    pub change: String,
+    pub summary: String,
+    /// What was changed, before and after the change.
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+/// Which entries to keep, all of them when nothing is set.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryFilter {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub actor: Option<String>,
+    /// Changes made within the Environment, or to the Environment itself.
+    pub environment_id: Option<EnvironmentId>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.from.map_or(true, |from| entry.created_at >= from)
+            && self.to.map_or(true, |to| entry.created_at <= to)
+            && self
+                .actor
+                .as_ref()
+                .map_or(true, |actor| entry.metadata.actor.as_ref() == Some(actor))
+            && self.environment_id.map_or(true, |environment_id| {
+                entry.environment_id == Some(environment_id)
+                    || entry.aggregate_id == Uuid::from(environment_id)
+            })
+    }
+}
+
+/// What an event changed, worked out from the state
+/// of its aggregate before and after it.
+struct Change {
+    name: String,
+    toggle_id: Option<ToggleId>,
+    environment_id: Option<EnvironmentId>,
+    summary: String,
+    before: Option<serde_json::Value>,
+    after: Option<serde_json::Value>,
+}
+
+impl Change {
+    fn new(name: &str, summary: String) -> Self {
+        Change {
+            name: name.to_owned(),
+            toggle_id: None,
+            environment_id: None,
+            summary,
+            before: None,
+            after: None,
+        }
+    }
+
+    fn values(self, before: Option<serde_json::Value>, after: Option<serde_json::Value>) -> Self {
+        Change {
+            before,
+            after,
+            ..self
+        }
+    }
+
+    fn toggle(self, toggle_id: ToggleId) -> Self {
+        Change {
+            toggle_id: Some(toggle_id),
+            ..self
+        }
+    }
+
+    fn environment(self, environment_id: EnvironmentId) -> Self {
+        Change {
+            environment_id: Some(environment_id),
+            ..self
+        }
+    }
+}
+
+/// Applies the event to the state it was stored against,
+/// returning the state before and after it.
+fn apply<A>(
+    states: &mut HashMap<A::Id, A>,
+    event: &DomainEvent<A>,
+) -> Result<(Option<A>, A), A::Err>
+where
+    A: Aggregate + Clone,
+    A::Id: Copy + Hash,
+{
+    let before = states.get(&event.aggregate_id).cloned();
+    let after = A::apply_event(before.clone(), &event.event)?;
+    states.insert(event.aggregate_id, after.clone());
+    Ok((before, after))
+}
+
+/// Every aggregate of a Project, as of the last event replayed.
+#[derive(Default)]
+struct Replay {
+    projects: HashMap<ProjectId, Project>,
+    environments: HashMap<EnvironmentId, Environment>,
+    segments: HashMap<SegmentId, Segment>,
+    toggles: HashMap<ToggleId, Toggle>,
+    variants: HashMap<VariantId, Variant>,
+}
+
+impl Replay {
+    fn entry(&mut self, event: &Event) -> Result<Option<HistoryEntry>, HistoryHandlerError> {
+        let (aggregate_id, created_at, metadata, change) =
+            if let Some(event) = event.decode::<Project>()? {
+                let (before, after) = apply(&mut self.projects, &event)?;
+                let change = self.project(before, &after, &event.event);
+                (
+                    event.aggregate_id.into(),
+                    event.created_at,
+                    event.metadata,
+                    change,
+                )
+            } else if let Some(event) = event.decode::<Environment>()? {
+                let (before, after) = apply(&mut self.environments, &event)?;
+                let change = self.environment(before, &after, &event.event);
+                (
+                    event.aggregate_id.into(),
+                    event.created_at,
+                    event.metadata,
+                    change,
+                )
+            } else if let Some(event) = event.decode::<Segment>()? {
+                let (before, after) = apply(&mut self.segments, &event)?;
+                let change = self.segment(before, &after, &event.event);
+                (
+                    event.aggregate_id.into(),
+                    event.created_at,
+                    event.metadata,
+                    change,
+                )
+            } else if let Some(event) = event.decode::<Toggle>()? {
+                let (before, after) = apply(&mut self.toggles, &event)?;
+                let change = self.toggle(before, &after, &event.event);
+                (
+                    event.aggregate_id.into(),
+                    event.created_at,
+                    event.metadata,
+                    change,
+                )
+            } else if let Some(event) = event.decode::<Variant>()? {
+                let (before, after) = apply(&mut self.variants, &event)?;
+                let change = self.variant(before, &after, &event.event);
+                (
+                    event.aggregate_id.into(),
+                    event.created_at,
+                    event.metadata,
+                    change,
+                )
+            } else {
+                return Ok(None);
+            };
+        Ok(Some(HistoryEntry {
+            position: event.position,
+            created_at,
+            metadata,
+            aggregate_type: event.aggregate_type.clone(),
+            aggregate_id,
+            name: change.name,
+            toggle_id: change.toggle_id,
+            environment_id: change.environment_id,
+            change: event.type_.clone(),
+            summary: change.summary,
+            before: change.before,
+            after: change.after,
+        }))
+    }
+
+    /// Environment names read better than their ids,
+    /// the id is all there is for Environments not replayed.
+    fn environment_name(&self, environment_id: EnvironmentId) -> String {
+        self.environments
+            .get(&environment_id)
+            .map_or_else(|| environment_id.to_string(), |e| e.name.clone())
+    }
+
+    fn project(&self, before: Option<Project>, after: &Project, event: &ProjectEvent) -> Change {
+        match (before, event) {
+            (_, ProjectEvent::Created { name, .. }) => {
+                Change::new(name, format!("Created project \"{}\"", name))
+                    .values(None, Some(json!({ "name": name })))
+            }
+            (Some(before), ProjectEvent::Renamed { name }) => Change::new(
+                name,
+                format!("Renamed project \"{}\" to \"{}\"", before.name, name),
+            )
+            .values(
+                Some(json!({ "name": before.name })),
+                Some(json!({ "name": name })),
+            ),
+            (_, ProjectEvent::Archived) => {
+                Change::new(&after.name, format!("Archived project \"{}\"", after.name))
+                    .values(archived(false), archived(true))
+            }
+            (_, ProjectEvent::Restored) => {
+                Change::new(&after.name, format!("Restored project \"{}\"", after.name))
+                    .values(archived(true), archived(false))
+            }
+            (None, event) => Change::new(&after.name, event.type_()),
+        }
+    }
+
+    fn environment(
+        &self,
+        before: Option<Environment>,
+        after: &Environment,
+        event: &EnvironmentEvent,
+    ) -> Change {
+        match (before, event) {
+            (_, EnvironmentEvent::Created { name, .. }) => {
+                Change::new(name, format!("Created environment \"{}\"", name))
+                    .values(None, Some(json!({ "name": name })))
+            }
+            (Some(before), EnvironmentEvent::Renamed { name }) => Change::new(
+                name,
+                format!("Renamed environment \"{}\" to \"{}\"", before.name, name),
+            )
+            .values(
+                Some(json!({ "name": before.name })),
+                Some(json!({ "name": name })),
+            ),
+            (_, EnvironmentEvent::Archived) => Change::new(
+                &after.name,
+                format!("Archived environment \"{}\"", after.name),
+            )
+            .values(archived(false), archived(true)),
+            (None, event) => Change::new(&after.name, event.type_()),
+        }
+    }
+
+    fn segment(&self, before: Option<Segment>, after: &Segment, event: &SegmentEvent) -> Change {
+        let name = &after.name;
+        let before = match (before, event) {
+            (_, SegmentEvent::Created { .. }) => {
+                return Change::new(name, format!("Created segment \"{}\"", name))
+                    .values(None, Some(json!({ "name": name })));
+            }
+            (Some(before), _) => before,
+            (None, event) => return Change::new(name, event.type_()),
+        };
+        match event {
+            SegmentEvent::Renamed { .. } => Change::new(
+                name,
+                format!("Renamed segment \"{}\" to \"{}\"", before.name, name),
+            )
+            .values(
+                Some(json!({ "name": before.name })),
+                Some(json!({ "name": name })),
+            ),
+            SegmentEvent::IncludedChanged { keys } => Change::new(
+                name,
+                format!("Changed the keys included in segment \"{}\"", name),
+            )
+            .values(
+                Some(json!({ "included": before.included })),
+                Some(json!({ "included": keys })),
+            ),
+            SegmentEvent::ExcludedChanged { keys } => Change::new(
+                name,
+                format!("Changed the keys excluded from segment \"{}\"", name),
+            )
+            .values(
+                Some(json!({ "excluded": before.excluded })),
+                Some(json!({ "excluded": keys })),
+            ),
+            SegmentEvent::RulesChanged { rules } => {
+                Change::new(name, format!("Changed the rules of segment \"{}\"", name)).values(
+                    Some(json!({ "rules": before.rules })),
+                    Some(json!({ "rules": rules })),
+                )
+            }
+            SegmentEvent::Created { .. } => unreachable!("Created is handled above"),
+        }
+    }
+
+    fn toggle(&self, before: Option<Toggle>, after: &Toggle, event: &toggle::Event) -> Change {
+        let name = after.name();
+        let before = match (before, event) {
+            (_, toggle::Event::Created { .. }) => {
+                return Change::new(name, format!("Created toggle \"{}\"", name))
+                    .toggle(*after.id())
+                    .values(None, Some(json!({ "name": name })));
+            }
+            (Some(before), _) => before,
+            (None, event) => return Change::new(name, event.type_()).toggle(*after.id()),
+        };
+        // State in the Environment before the change
+        let state = |environment_id: EnvironmentId| {
+            before
+                .environments()
+                .get(&environment_id)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let change = match event {
+            toggle::Event::Enabled { environment_id } => Change::new(
+                name,
+                format!(
+                    "Enabled toggle \"{}\" in {}",
+                    name,
+                    self.environment_name(*environment_id)
+                ),
+            )
+            .environment(*environment_id)
+            .values(
+                Some(json!({ "enabled": state(*environment_id).enabled })),
+                Some(json!({ "enabled": true })),
+            ),
+            toggle::Event::Disabled { environment_id } => Change::new(
+                name,
+                format!(
+                    "Disabled toggle \"{}\" in {}",
+                    name,
+                    self.environment_name(*environment_id)
+                ),
+            )
+            .environment(*environment_id)
+            .values(
+                Some(json!({ "enabled": state(*environment_id).enabled })),
+                Some(json!({ "enabled": false })),
+            ),
+            toggle::Event::RolloutChanged {
+                environment_id,
+                percentage,
+            } => Change::new(
+                name,
+                format!(
+                    "Rolled toggle \"{}\" out to {}% in {}",
+                    name,
+                    percentage,
+                    self.environment_name(*environment_id)
+                ),
+            )
+            .environment(*environment_id)
+            .values(
+                Some(json!({ "rollout": state(*environment_id).rollout })),
+                Some(json!({ "rollout": percentage })),
+            ),
+            toggle::Event::RolloutCleared { environment_id } => Change::new(
+                name,
+                format!(
+                    "Cleared the rollout of toggle \"{}\" in {}",
+                    name,
+                    self.environment_name(*environment_id)
+                ),
+            )
+            .environment(*environment_id)
+            .values(
+                Some(json!({ "rollout": state(*environment_id).rollout })),
+                Some(json!({ "rollout": null })),
+            ),
+            toggle::Event::RulesChanged {
+                environment_id,
+                rules,
+            } => Change::new(
+                name,
+                format!(
+                    "Changed the rules of toggle \"{}\" in {}",
+                    name,
+                    self.environment_name(*environment_id)
+                ),
+            )
+            .environment(*environment_id)
+            .values(
+                Some(json!({ "rules": state(*environment_id).rules })),
+                Some(json!({ "rules": rules })),
+            ),
+            toggle::Event::PrerequisitesChanged { prerequisites } => Change::new(
+                name,
+                format!("Changed the prerequisites of toggle \"{}\"", name),
+            )
+            .values(
+                Some(json!({ "prerequisites": before.prerequisites() })),
+                Some(json!({ "prerequisites": prerequisites })),
+            ),
+            toggle::Event::ChangeScheduled { schedule } => Change::new(
+                name,
+                format!(
+                    "Scheduled a change to toggle \"{}\" in {} at {}",
+                    name,
+                    self.environment_name(schedule.environment_id),
+                    schedule.at.to_rfc3339()
+                ),
+            )
+            .environment(schedule.environment_id)
+            .values(None, Some(json!({ "schedule": schedule }))),
+            toggle::Event::ScheduleCancelled { schedule_id } => {
+                self.unscheduled(name, "Cancelled", before.schedule(*schedule_id))
+            }
+            toggle::Event::ScheduleExecuted { schedule_id } => {
+                self.unscheduled(name, "Made", before.schedule(*schedule_id))
+            }
+            toggle::Event::ScheduleFailed {
+                schedule_id,
+                reason,
+            } => self
+                .unscheduled(name, "Failed to make", before.schedule(*schedule_id))
+                .values(
+                    before
+                        .schedule(*schedule_id)
+                        .map(|s| json!({ "schedule": s })),
+                    Some(json!({ "reason": reason })),
+                ),
+            toggle::Event::Retired => Change::new(name, format!("Retired toggle \"{}\"", name))
+                .values(retired(false), retired(true)),
+            toggle::Event::Revived => Change::new(name, format!("Revived toggle \"{}\"", name))
+                .values(retired(true), retired(false)),
+            toggle::Event::Created { .. } => unreachable!("Created is handled above"),
+        };
+        change.toggle(*after.id())
+    }
+
+    /// A scheduled change no longer pending, whether it was made, cancelled or failed.
+    fn unscheduled(&self, name: &str, verb: &str, schedule: Option<&ScheduledChange>) -> Change {
+        match schedule {
+            Some(schedule) => Change::new(
+                name,
+                format!(
+                    "{} the change scheduled to toggle \"{}\" in {} at {}",
+                    verb,
+                    name,
+                    self.environment_name(schedule.environment_id),
+                    schedule.at.to_rfc3339()
+                ),
+            )
+            .environment(schedule.environment_id)
+            .values(Some(json!({ "schedule": schedule })), None),
+            None => Change::new(
+                name,
+                format!("{} a change scheduled to toggle \"{}\"", verb, name),
+            ),
+        }
+    }
+
+    fn variant(&self, before: Option<Variant>, after: &Variant, event: &VariantEvent) -> Change {
+        let name = &after.name;
+        let toggle = self
+            .toggles
+            .get(&after.toggle_id)
+            .map_or_else(|| after.toggle_id.to_string(), |t| t.name().to_owned());
+        let change = match (before, event) {
+            (_, VariantEvent::Created { .. }) => Change::new(
+                name,
+                format!("Created variant \"{}\" of toggle \"{}\"", name, toggle),
+            )
+            .values(None, Some(json!({ "name": name }))),
+            (Some(before), VariantEvent::Renamed { .. }) => Change::new(
+                name,
+                format!(
+                    "Renamed variant \"{}\" of toggle \"{}\" to \"{}\"",
+                    before.name, toggle, name
+                ),
+            )
+            .values(
+                Some(json!({ "name": before.name })),
+                Some(json!({ "name": name })),
+            ),
+            (_, VariantEvent::Retired) => Change::new(
+                name,
+                format!("Retired variant \"{}\" of toggle \"{}\"", name, toggle),
+            )
+            .values(retired(false), retired(true)),
+            (_, VariantEvent::Revived) => Change::new(
+                name,
+                format!("Revived variant \"{}\" of toggle \"{}\"", name, toggle),
+            )
+            .values(retired(true), retired(false)),
+            (None, event) => Change::new(name, event.type_()),
+        };
+        change.toggle(after.toggle_id)
+    }
+}
+
+fn archived(archived: bool) -> Option<serde_json::Value> {
+    Some(json!({ "archived": archived }))
+}
+
+fn retired(retired: bool) -> Option<serde_json::Value> {
+    Some(json!({ "retired": retired }))
+}
+
+pub struct GetHistory {
+    pub project_id: ProjectId,
+    /// Only the changes to this Toggle and its Variants.
+    pub toggle_id: Option<ToggleId>,
+    pub filter: HistoryFilter,
+}
+
+pub struct GetHistoryHandler<'a, E, A>
+where
+    A: AuditLog<Err = E>,
+{
+    pub audit_log: &'a A,
+}
+
+impl<'a, E, A> GetHistoryHandler<'a, E, A>
+where
+    A: AuditLog<Err = E>,
+    HistoryHandlerError: From<E>,
+{
+    pub fn handle(&self, command: GetHistory) -> Result<Vec<HistoryEntry>, HistoryHandlerError> {
+        let events = self.audit_log.project(command.project_id)?;
+        // Every Project's history starts with it being created
+        if events.is_empty() {
+            return Err(HistoryHandlerError::ProjectNotFound);
+        }
+        // The whole history is replayed, as what a change was
+        // depends on everything before it, and filtered after
+        let mut replay = Replay::default();
+        let mut entries = vec![];
+        for event in &events {
+            if let Some(entry) = replay.entry(event)? {
+                entries.push(entry);
+            }
+        }
+        if let Some(toggle_id) = command.toggle_id {
+            if !replay.toggles.contains_key(&toggle_id) {
+                return Err(HistoryHandlerError::ToggleNotFound);
+            }
+            entries.retain(|entry| entry.toggle_id == Some(toggle_id));
+        }
+        entries.retain(|entry| command.filter.matches(entry));
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::{Error, Fail};
+    use serde::de::DeserializeOwned;
+    use serde::Serialize;
+    use uuid::Uuid;
+
+    use crate::domain::{
+        Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, Repository,
+    };
+    use crate::environment::{Environment, EnvironmentId};
+    use crate::event_store::DieselEventStore;
+    use crate::project::{Project, ProjectId};
+    use crate::projection::audit::DieselAuditLog;
+    use crate::projection::Projector;
+    use crate::toggle::{Toggle, ToggleId};
+
+    use super::error::HistoryHandlerError;
+    use super::{GetHistory, GetHistoryHandler, HistoryFilter};
+
+    fn persist<A>(
+        db: &SqliteConnection,
+        aggregate_id: A::Id,
+        generation: Generation,
+        events: Vec<A::Event>,
+        actor: &str,
+    ) -> Result<(), Error>
+    where
+        A: Aggregate + DeserializeOwned + Serialize,
+        A::Id: Copy + From<Uuid> + Into<Uuid>,
+        A::Event: AggregateEvent + DeserializeOwned + Serialize,
+        A::Err: Fail,
+    {
+        let events = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id,
+                created_at: Utc::now(),
+                metadata: Metadata {
+                    actor: Some(actor.to_owned()),
+                    ..Metadata::default()
+                },
+                event,
+            })
+            .collect::<Vec<_>>();
+        DieselEventStore::<_, A>::new(db).persist(generation, &events)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_history() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let project_id = ProjectId::from(Uuid::new_v4());
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let toggle_id = ToggleId::from(Uuid::new_v4());
+
+        let events = Project::create(project_id, "checkout".to_owned())?;
+        persist::<Project>(db, project_id, Generation::first(), events, "alice")?;
+        let events = Environment::create(environment_id, project_id, "production".to_owned())?;
+        let environment = Environment::hydrate(&events)?.expect("Environment is not None");
+        persist::<Environment>(db, environment_id, Generation::first(), events, "alice")?;
+        let events = Toggle::create(toggle_id, project_id, "new-cart".to_owned())?;
+        let toggle = Toggle::hydrate(&events)?.expect("Toggle is not None");
+        persist::<Toggle>(db, toggle_id, Generation::first(), events, "alice")?;
+        let events = toggle.enable(&environment)?;
+        let toggle = Toggle::hydrate_from(Some(toggle), &events)?.expect("Toggle is not None");
+        persist::<Toggle>(db, toggle_id, Generation::first().next(), events, "bob")?;
+        let events = toggle.change_rollout(&environment, 25)?;
+        persist::<Toggle>(
+            db,
+            toggle_id,
+            Generation::first().next().next(),
+            events,
+            "bob",
+        )?;
+
+        let projector = Projector { db };
+        let audit_log = &mut DieselAuditLog { db };
+        projector.catch_up(audit_log)?;
+        let handler = GetHistoryHandler { audit_log };
+
+        let entries = handler.handle(GetHistory {
+            project_id,
+            toggle_id: None,
+            filter: HistoryFilter::default(),
+        })?;
+        let summaries = entries
+            .iter()
+            .map(|e| e.summary.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summaries,
+            vec![
+                "Created project \"checkout\"",
+                "Created environment \"production\"",
+                "Created toggle \"new-cart\"",
+                "Enabled toggle \"new-cart\" in production",
+                "Rolled toggle \"new-cart\" out to 25% in production",
+            ],
+        );
+        assert_eq!(
+            entries[4].before,
+            Some(serde_json::json!({ "rollout": null }))
+        );
+        assert_eq!(entries[4].after, Some(serde_json::json!({ "rollout": 25 })));
+
+        let entries = handler.handle(GetHistory {
+            project_id,
+            toggle_id: Some(toggle_id),
+            filter: HistoryFilter {
+                actor: Some("bob".to_owned()),
+                environment_id: Some(environment_id),
+                ..HistoryFilter::default()
+            },
+        })?;
+        let changes = entries
+            .iter()
+            .map(|e| e.change.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(changes, vec!["Enabled", "RolloutChanged"]);
+        assert_eq!(
+            entries[0].before,
+            Some(serde_json::json!({ "enabled": false }))
+        );
+
+        let entries = handler.handle(GetHistory {
+            project_id,
+            toggle_id: None,
+            filter: HistoryFilter {
+                from: Some(Utc::now() + Duration::hours(1)),
+                ..HistoryFilter::default()
+            },
+        })?;
+        assert!(entries.is_empty());
+
+        match handler.handle(GetHistory {
+            project_id,
+            toggle_id: Some(ToggleId::from(Uuid::new_v4())),
+            filter: HistoryFilter::default(),
+        }) {
+            Err(HistoryHandlerError::ToggleNotFound) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match handler.handle(GetHistory {
+            project_id: ProjectId::from(Uuid::new_v4()),
+            toggle_id: None,
+            filter: HistoryFilter::default(),
+        }) {
+            Err(HistoryHandlerError::ProjectNotFound) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+// Need a macro_use so that macros are brought
+// in globally for use in crate::database::schema
+#[macro_use]
+extern crate diesel;
+
+pub mod app;
+pub mod database;
+pub mod domain;
+pub mod environment;
+pub mod evaluation;
+pub mod event_store;
+pub mod history;
+pub mod project;
+pub mod projection;
+pub mod segment;
+pub mod toggle;
+pub mod variant;
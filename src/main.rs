@@ -1,22 +1,6 @@
-// Need a macro_use so that macros are brought
-// in globally for use in crate::database::schema
-#[macro_use]
-extern crate diesel;
-
-mod app;
-mod database;
-mod domain;
-mod project;
-mod toggle;
-
-use actix::SyncArbiter;
-use actix_web::middleware::Logger;
-use actix_web::{http::Method, server, App};
-use diesel::r2d2::{ConnectionManager, Pool};
-use diesel::sqlite::SqliteConnection;
 use failure::Error;
 
-use crate::app::{AppState, Executor};
+use feature_toggler::app;
 
 fn main() -> Result<(), Error> {
     std::env::set_var("RUST_LOG", "actix_web=debug");
@@ -24,7 +8,8 @@ fn main() -> Result<(), Error> {
 
     let sys = actix::System::new("feature-toggler");
 
-    app::create("db.sqlite")?.bind("127.0.0.1:8088")?.start();
+    let database_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "db.sqlite".to_owned());
+    app::create(&database_url)?.bind("127.0.0.1:8088")?.start();
 
     let _ = sys.run();
 
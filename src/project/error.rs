@@ -1,5 +1,8 @@
 use failure_derive::Fail;
 
+use crate::domain::Generation;
+use crate::event_store::error::EventStoreError;
+
 #[derive(Debug, Fail)]
 pub enum ProjectIdParseError {
     #[fail(display = "fail to parse uuid")]
@@ -16,104 +19,100 @@ impl From<uuid::parser::ParseError> for ProjectIdParseError {
 pub enum ProjectError {
     #[fail(display = "invalid project name: {}", name)]
     InvalidName { name: String },
+    #[fail(display = "duplicate project name: {}", name)]
+    DuplicateName { name: String },
+    #[fail(display = "project {} is archived", project)]
+    Archived { project: String },
     #[fail(display = "invalid event `{}` applied to state `{}", event, state)]
     InvalidStateEvent { state: String, event: String },
 }
 
 #[derive(Debug, Fail)]
-pub enum DomainEventError {
-    #[fail(display = "failed to parse uuid")]
-    UuidParseError(#[cause] uuid::parser::ParseError),
-    #[fail(display = "failed to parse datetime")]
-    DateTimeParseError(#[cause] chrono::format::ParseError),
-    #[fail(display = "failed to parse JSON data")]
-    JsonParseError(#[cause] serde_json::error::Error),
+pub enum CreateProjectHandlerError {
+    #[fail(display = "project error")]
+    ProjectError(#[cause] ProjectError),
+    #[fail(display = "projects error")]
+    ProjectsError(#[cause] diesel::result::Error),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<ProjectError>),
 }
 
-impl From<uuid::parser::ParseError> for DomainEventError {
-    fn from(e: uuid::parser::ParseError) -> Self {
-        DomainEventError::UuidParseError(e)
+impl From<ProjectError> for CreateProjectHandlerError {
+    fn from(e: ProjectError) -> Self {
+        CreateProjectHandlerError::ProjectError(e)
     }
 }
 
-impl From<chrono::format::ParseError> for DomainEventError {
-    fn from(e: chrono::format::ParseError) -> Self {
-        DomainEventError::DateTimeParseError(e)
+impl From<diesel::result::Error> for CreateProjectHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        CreateProjectHandlerError::ProjectsError(e)
     }
 }
 
-impl From<serde_json::error::Error> for DomainEventError {
-    fn from(e: serde_json::error::Error) -> Self {
-        DomainEventError::JsonParseError(e)
+impl From<EventStoreError<ProjectError>> for CreateProjectHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        CreateProjectHandlerError::RepositoryError(e)
     }
 }
 
 #[derive(Debug, Fail)]
-pub enum SqliteRepositoryError {
-    #[fail(display = "database error")]
-    DatabaseError(#[cause] diesel::result::Error),
-    #[fail(display = "domain event error")]
-    DomainEventError(#[cause] DomainEventError),
-    #[fail(display = "project error")]
-    ProjectError(#[cause] ProjectError),
-    #[fail(display = "json format error")]
-    JsonFormatError(#[cause] serde_json::error::Error),
-    #[fail(display = "not found error")]
-    NotFoundError,
-}
-
-impl From<diesel::result::Error> for SqliteRepositoryError {
-    fn from(e: diesel::result::Error) -> Self {
-        SqliteRepositoryError::DatabaseError(e)
-    }
+pub enum ListProjectHandlerError {
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<ProjectError>),
 }
 
-impl From<DomainEventError> for SqliteRepositoryError {
-    fn from(e: DomainEventError) -> Self {
-        SqliteRepositoryError::DomainEventError(e)
+impl From<EventStoreError<ProjectError>> for ListProjectHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        ListProjectHandlerError::RepositoryError(e)
     }
 }
 
-impl From<ProjectError> for SqliteRepositoryError {
-    fn from(e: ProjectError) -> Self {
-        SqliteRepositoryError::ProjectError(e)
-    }
+#[derive(Debug, Fail)]
+pub enum ListProjectsHandlerError {
+    #[fail(display = "invalid cursor: {}", cursor)]
+    InvalidCursor { cursor: String },
+    #[fail(display = "projects error")]
+    ProjectsError(#[cause] diesel::result::Error),
 }
 
-impl From<serde_json::error::Error> for SqliteRepositoryError {
-    fn from(e: serde_json::error::Error) -> Self {
-        SqliteRepositoryError::JsonFormatError(e)
+impl From<diesel::result::Error> for ListProjectsHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        ListProjectsHandlerError::ProjectsError(e)
     }
 }
 
 #[derive(Debug, Fail)]
-pub enum CreateProjectHandlerError {
+pub enum UpdateProjectHandlerError {
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
     #[fail(display = "project error")]
     ProjectError(#[cause] ProjectError),
+    #[fail(display = "projects error")]
+    ProjectsError(#[cause] diesel::result::Error),
     #[fail(display = "repository error")]
-    RepositoryError(#[cause] SqliteRepositoryError),
+    RepositoryError(#[cause] EventStoreError<ProjectError>),
 }
 
-impl From<ProjectError> for CreateProjectHandlerError {
+impl From<ProjectError> for UpdateProjectHandlerError {
     fn from(e: ProjectError) -> Self {
-        CreateProjectHandlerError::ProjectError(e)
+        UpdateProjectHandlerError::ProjectError(e)
     }
 }
 
-impl From<SqliteRepositoryError> for CreateProjectHandlerError {
-    fn from(e: SqliteRepositoryError) -> Self {
-        CreateProjectHandlerError::RepositoryError(e)
+impl From<diesel::result::Error> for UpdateProjectHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        UpdateProjectHandlerError::ProjectsError(e)
     }
 }
 
-#[derive(Debug, Fail)]
-pub enum ListProjectHandlerError {
-    #[fail(display = "repository error")]
-    RepositoryError(#[cause] SqliteRepositoryError),
-}
-
-impl From<SqliteRepositoryError> for ListProjectHandlerError {
-    fn from(e: SqliteRepositoryError) -> Self {
-        ListProjectHandlerError::RepositoryError(e)
+impl From<EventStoreError<ProjectError>> for UpdateProjectHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        UpdateProjectHandlerError::RepositoryError(e)
     }
 }
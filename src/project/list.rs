@@ -0,0 +1,397 @@
+use chrono::{DateTime, Utc};
+use diesel;
+#[cfg(feature = "postgres")]
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use diesel::sqlite::{Sqlite, SqliteConnection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models;
+use crate::database::schema;
+use crate::projection::error::ProjectionError;
+use crate::projection::Projection;
+
+use super::{Project, ProjectEvent, ProjectId};
+
+/// A Project as kept in the projects read model.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProjectSummary {
+    pub id: ProjectId,
+    pub name: String,
+    pub archived: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectSort {
+    #[default]
+    Name,
+    Created,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Which Projects to list and in what order. Projects sorting equal
+/// are ordered by id, so every Project has a stable place to page from.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ProjectQuery {
+    /// Only Projects with a name containing this, ignoring ASCII case.
+    pub name: Option<String>,
+    pub sort: ProjectSort,
+    pub order: SortOrder,
+}
+
+/// Read model of all Projects, so Projects can be listed and
+/// found by name without replaying every Project's events.
+pub trait Projects {
+    type Err;
+
+    fn get(&self, id: ProjectId) -> Result<Option<ProjectSummary>, Self::Err>;
+    fn find(&self, name: &str) -> Result<Option<ProjectId>, Self::Err>;
+    /// Projects matching `query` that come after `after`, at most `limit` of them.
+    fn list(
+        &self,
+        query: &ProjectQuery,
+        after: Option<&ProjectSummary>,
+        limit: usize,
+    ) -> Result<Vec<ProjectSummary>, Self::Err>;
+}
+
+pub struct DieselProjects<'a, C> {
+    pub db: &'a C,
+}
+
+impl ProjectSummary {
+    fn from_row(row: models::Project) -> Result<Self, diesel::result::Error> {
+        Ok(ProjectSummary {
+            id: Uuid::parse_str(&row.id)
+                .map(ProjectId)
+                .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?,
+            name: row.name,
+            archived: row.archived,
+            created_at: row
+                .created_at
+                .parse::<DateTime<Utc>>()
+                .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))?,
+        })
+    }
+}
+
+/// Pattern for a LIKE matching names containing `name` literally.
+fn contains_pattern(name: &str) -> String {
+    let mut pattern = String::with_capacity(name.len() + 2);
+    pattern.push('%');
+    for c in name.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern.push('%');
+    pattern
+}
+
+/// Names containing `name`, ignoring ASCII case as SQLite's LIKE does.
+fn sqlite_name_contains(
+    name: &str,
+) -> Box<dyn BoxableExpression<schema::projects::table, Sqlite, SqlType = Bool>> {
+    Box::new(
+        schema::projects::name
+            .like(contains_pattern(name))
+            .escape('\\'),
+    )
+}
+
+/// Names containing `name`, ignoring case. PostgreSQL's LIKE tells case
+/// apart so it takes ILIKE, whose escape character is a backslash already.
+#[cfg(feature = "postgres")]
+fn pg_name_contains(
+    name: &str,
+) -> Box<dyn BoxableExpression<schema::projects::table, Pg, SqlType = Bool>> {
+    Box::new(schema::projects::name.ilike(contains_pattern(name)))
+}
+
+/// Implements the projects read model for a kind of diesel connection,
+/// filtering names with `$name_contains`.
+macro_rules! impl_projects {
+    ($connection:ty, $name_contains:path) => {
+        impl<'a> Projects for DieselProjects<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn get(&self, id: ProjectId) -> Result<Option<ProjectSummary>, Self::Err> {
+                use crate::database::schema::projects::dsl;
+
+                dsl::projects
+                    .find(id.to_string())
+                    .first::<models::Project>(self.db)
+                    .optional()?
+                    .map(ProjectSummary::from_row)
+                    .transpose()
+            }
+
+            fn find(&self, name: &str) -> Result<Option<ProjectId>, Self::Err> {
+                use crate::database::schema::projects::dsl;
+
+                let id = dsl::projects
+                    .filter(dsl::name.eq(name))
+                    .select(dsl::id)
+                    .first::<String>(self.db)
+                    .optional()?;
+                id.map(|id| {
+                    Uuid::parse_str(&id)
+                        .map(ProjectId)
+                        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+                })
+                .transpose()
+            }
+
+            fn list(
+                &self,
+                query: &ProjectQuery,
+                after: Option<&ProjectSummary>,
+                limit: usize,
+            ) -> Result<Vec<ProjectSummary>, Self::Err> {
+                use crate::database::schema::projects::dsl;
+
+                let mut statement = dsl::projects.into_boxed();
+                if let Some(name) = &query.name {
+                    statement = statement.filter($name_contains(name));
+                }
+                if let Some(after) = after {
+                    let id = after.id.to_string();
+                    let name = after.name.clone();
+                    let created_at = after.created_at.to_rfc3339();
+                    statement = match (query.sort, query.order) {
+                        (ProjectSort::Name, SortOrder::Asc) => statement.filter(
+                            dsl::name
+                                .gt(name.clone())
+                                .or(dsl::name.eq(name).and(dsl::id.gt(id))),
+                        ),
+                        (ProjectSort::Name, SortOrder::Desc) => statement.filter(
+                            dsl::name
+                                .lt(name.clone())
+                                .or(dsl::name.eq(name).and(dsl::id.lt(id))),
+                        ),
+                        (ProjectSort::Created, SortOrder::Asc) => statement.filter(
+                            dsl::created_at
+                                .gt(created_at.clone())
+                                .or(dsl::created_at.eq(created_at).and(dsl::id.gt(id))),
+                        ),
+                        (ProjectSort::Created, SortOrder::Desc) => statement.filter(
+                            dsl::created_at
+                                .lt(created_at.clone())
+                                .or(dsl::created_at.eq(created_at).and(dsl::id.lt(id))),
+                        ),
+                    };
+                }
+                statement = match (query.sort, query.order) {
+                    (ProjectSort::Name, SortOrder::Asc) => {
+                        statement.order((dsl::name.asc(), dsl::id.asc()))
+                    }
+                    (ProjectSort::Name, SortOrder::Desc) => {
+                        statement.order((dsl::name.desc(), dsl::id.desc()))
+                    }
+                    (ProjectSort::Created, SortOrder::Asc) => {
+                        statement.order((dsl::created_at.asc(), dsl::id.asc()))
+                    }
+                    (ProjectSort::Created, SortOrder::Desc) => {
+                        statement.order((dsl::created_at.desc(), dsl::id.desc()))
+                    }
+                };
+                statement
+                    .limit(limit as i64)
+                    .load::<models::Project>(self.db)?
+                    .into_iter()
+                    .map(ProjectSummary::from_row)
+                    .collect()
+            }
+        }
+
+        impl<'a> Projection for DieselProjects<'a, $connection> {
+            fn name(&self) -> &'static str {
+                "projects"
+            }
+
+            fn apply(&mut self, event: &models::Event) -> Result<(), ProjectionError> {
+                use crate::database::schema::projects::dsl;
+
+                let event = match event.decode::<Project>()? {
+                    Some(event) => event,
+                    None => return Ok(()),
+                };
+                let project = dsl::projects.find(event.aggregate_id.to_string());
+                match event.event {
+                    ProjectEvent::Created { id, name } => {
+                        diesel::insert_into(schema::projects::table)
+                            .values(&models::NewProject {
+                                id: &id.to_string(),
+                                name: &name,
+                                archived: false,
+                                created_at: &event.created_at.to_rfc3339(),
+                            })
+                            .execute(self.db)?;
+                    }
+                    ProjectEvent::Renamed { name } => {
+                        diesel::update(project)
+                            .set(dsl::name.eq(name))
+                            .execute(self.db)?;
+                    }
+                    ProjectEvent::Archived => {
+                        diesel::update(project)
+                            .set(dsl::archived.eq(true))
+                            .execute(self.db)?;
+                    }
+                    ProjectEvent::Restored => {
+                        diesel::update(project)
+                            .set(dsl::archived.eq(false))
+                            .execute(self.db)?;
+                    }
+                }
+                Ok(())
+            }
+
+            fn reset(&mut self) -> Result<(), ProjectionError> {
+                diesel::delete(schema::projects::table).execute(self.db)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_projects!(SqliteConnection, sqlite_name_contains);
+#[cfg(feature = "postgres")]
+impl_projects!(diesel::pg::PgConnection, pg_name_contains);
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::{DateTime, Utc};
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::event_store::DieselEventStore;
+    use crate::projection::Projector;
+
+    use super::super::{Project, ProjectEvent, ProjectId};
+    use super::{DieselProjects, ProjectQuery, ProjectSort, Projects, SortOrder};
+
+    fn persist(
+        db: &SqliteConnection,
+        id: ProjectId,
+        generation: Generation,
+        created_at: DateTime<Utc>,
+        event: ProjectEvent,
+    ) -> Result<(), Error> {
+        DieselEventStore::<_, Project>::new(db).persist(
+            generation,
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: id,
+                created_at,
+                event,
+                metadata: Metadata::default(),
+            }],
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_list() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        for (day, name) in [(3, "Shop"), (1, "shop_v2"), (2, "Search"), (4, "shopping")].iter() {
+            let id = ProjectId(Uuid::new_v4());
+            let created_at = Utc.ymd(2019, 5, *day).and_hms(0, 0, 0);
+            let name = (*name).to_owned();
+            persist(
+                db,
+                id,
+                Generation::first(),
+                created_at,
+                ProjectEvent::Created { id, name },
+            )?;
+        }
+        let projects = &mut DieselProjects { db };
+        Projector { db }.catch_up(projects)?;
+
+        let names = |query: &ProjectQuery, after_name: Option<&str>, limit| -> Result<_, Error> {
+            let after = match after_name {
+                Some(name) => projects.get(projects.find(name)?.expect("Project exists"))?,
+                None => None,
+            };
+            Ok(projects
+                .list(query, after.as_ref(), limit)?
+                .into_iter()
+                .map(|p| p.name)
+                .collect::<Vec<_>>())
+        };
+
+        let by_name = ProjectQuery::default();
+        assert_eq!(names(&by_name, None, 3)?, vec!["Search", "Shop", "shop_v2"]);
+        assert_eq!(names(&by_name, Some("shop_v2"), 3)?, vec!["shopping"]);
+
+        let newest = ProjectQuery {
+            sort: ProjectSort::Created,
+            order: SortOrder::Desc,
+            ..ProjectQuery::default()
+        };
+        assert_eq!(names(&newest, Some("shopping"), 2)?, vec!["Shop", "Search"]);
+
+        let shop = ProjectQuery {
+            name: Some("SHOP_".to_owned()),
+            ..ProjectQuery::default()
+        };
+        assert_eq!(names(&shop, None, 10)?, vec!["shop_v2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let id = ProjectId(Uuid::new_v4());
+        let created_at = Utc.ymd(2019, 5, 1).and_hms(0, 0, 0);
+        let events = vec![
+            ProjectEvent::Created {
+                id,
+                name: "test".to_owned(),
+            },
+            ProjectEvent::Renamed {
+                name: "renamed".to_owned(),
+            },
+            ProjectEvent::Archived,
+        ];
+        let mut generation = Generation::first();
+        for event in events {
+            persist(db, id, generation, created_at, event)?;
+            generation = generation.next();
+        }
+        let projects = &mut DieselProjects { db };
+        let projector = Projector { db };
+        projector.catch_up(projects)?;
+
+        assert_eq!(projects.find("test")?, None);
+        assert_eq!(projects.find("renamed")?, Some(id));
+        let summary = projects.get(id)?.expect("Project exists");
+        assert!(summary.archived);
+        assert_eq!(summary.created_at, created_at);
+
+        persist(db, id, generation, Utc::now(), ProjectEvent::Restored)?;
+        projector.rebuild(projects)?;
+        assert_eq!(projects.find("renamed")?, Some(id));
+        assert!(!projects.get(id)?.expect("Project exists").archived);
+        Ok(())
+    }
+}
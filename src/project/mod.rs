@@ -1,25 +1,29 @@
 pub mod error;
+pub mod list;
 
 use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
-use diesel;
-use diesel::sqlite::SqliteConnection;
-use diesel::RunQueryDsl;
 use serde::{Deserialize, Serialize};
-use serde_json;
 use uuid::Uuid;
 
-use crate::database::models::{Event, NewEvent};
-use crate::database::schema;
-use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Repository};
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, Repository,
+};
 
 use self::error::{
-    CreateProjectHandlerError, DomainEventError, ListProjectHandlerError, ProjectError,
-    ProjectIdParseError, SqliteRepositoryError,
+    CreateProjectHandlerError, ListProjectHandlerError, ListProjectsHandlerError, ProjectError,
+    ProjectIdParseError, UpdateProjectHandlerError,
 };
+use self::list::{ProjectQuery, ProjectSummary, Projects};
+
+/// Longest name a Project can have, in characters.
+const MAX_NAME_LENGTH: usize = 64;
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+/// Most Projects listed at once.
+pub const MAX_PAGE_SIZE: usize = 100;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct ProjectId(Uuid);
 
 impl ProjectId {
@@ -43,28 +47,85 @@ impl From<ProjectId> for Uuid {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+impl From<Uuid> for ProjectId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Project {
     pub id: ProjectId,
     pub generation: Generation,
     pub name: String,
+    pub archived: bool,
+}
+
+/// Names are limited in length and to letters, digits,
+/// spaces, `-`, `_` and `.`, without surrounding spaces.
+fn validate_name(name: String) -> Result<String, ProjectError> {
+    let valid = !name.is_empty()
+        && name.trim() == name
+        && name.chars().count() <= MAX_NAME_LENGTH
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.');
+    if !valid {
+        return Err(ProjectError::InvalidName { name });
+    }
+    Ok(name)
 }
 
 impl Project {
     pub fn create(id: ProjectId, name: String) -> Result<Vec<ProjectEvent>, ProjectError> {
+        let name = validate_name(name)?;
         Ok(vec![ProjectEvent::Created { id, name }])
     }
+
+    pub fn rename(&self, name: String) -> Result<Vec<ProjectEvent>, ProjectError> {
+        let name = validate_name(name)?;
+        self.transition(ProjectEvent::Renamed { name })
+    }
+
+    pub fn archive(&self) -> Result<Vec<ProjectEvent>, ProjectError> {
+        self.transition(ProjectEvent::Archived)
+    }
+
+    pub fn restore(&self) -> Result<Vec<ProjectEvent>, ProjectError> {
+        self.transition(ProjectEvent::Restored)
+    }
+
+    /// Archived Projects keep what they have but can't be added to.
+    pub fn check_active(&self) -> Result<(), ProjectError> {
+        if self.archived {
+            return Err(ProjectError::Archived {
+                project: self.id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn transition(&self, event: ProjectEvent) -> Result<Vec<ProjectEvent>, ProjectError> {
+        Self::apply_event(Some(self.clone()), &event)?;
+        Ok(vec![event])
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum ProjectEvent {
     Created { id: ProjectId, name: String },
+    Renamed { name: String },
+    Archived,
+    Restored,
 }
 
-impl ProjectEvent {
-    pub fn type_(&self) -> String {
+impl AggregateEvent for ProjectEvent {
+    fn type_(&self) -> String {
         match self {
             ProjectEvent::Created { .. } => "Created".to_owned(),
+            ProjectEvent::Renamed { .. } => "Renamed".to_owned(),
+            ProjectEvent::Archived => "Archived".to_owned(),
+            ProjectEvent::Restored => "Restored".to_owned(),
         }
     }
 }
@@ -73,6 +134,7 @@ impl Aggregate for Project {
     type Id = ProjectId;
     type Event = ProjectEvent;
     type Err = ProjectError;
+    const TYPE: &'static str = "project";
 
     fn id(&self) -> &ProjectId {
         &self.id
@@ -88,6 +150,22 @@ impl Aggregate for Project {
                 id: *id,
                 generation: Generation::first(),
                 name: name.clone(),
+                archived: false,
+            }),
+            (Some(project), ProjectEvent::Renamed { name }) if !project.archived => Ok(Project {
+                generation: project.generation.next(),
+                name: name.clone(),
+                ..project.clone()
+            }),
+            (Some(project), ProjectEvent::Archived) if !project.archived => Ok(Project {
+                generation: project.generation.next(),
+                archived: true,
+                ..project.clone()
+            }),
+            (Some(project), ProjectEvent::Restored) if project.archived => Ok(Project {
+                generation: project.generation.next(),
+                archived: false,
+                ..project.clone()
             }),
             _ => Err(ProjectError::InvalidStateEvent {
                 state: format!("{:?}", project),
@@ -97,97 +175,117 @@ impl Aggregate for Project {
     }
 }
 
-impl DomainEvent<Project> {
-    pub fn from_event(event: Event) -> Result<Self, DomainEventError> {
-        Ok(Self {
-            id: DomainEventId::new(Uuid::parse_str(&event.id)?),
-            aggregate_id: ProjectId(Uuid::parse_str(&event.aggregate_id)?),
-            created_at: event.created_at.parse::<DateTime<Utc>>()?,
-            event: serde_json::from_str(&event.data)?,
-        })
-    }
+pub struct CreateProject {
+    pub id: Uuid,
+    pub name: String,
+    pub metadata: Metadata,
 }
 
-pub struct SqliteRepository<'a> {
-    pub db: &'a SqliteConnection,
+pub struct CreateProjectHandler<'a, PE, P, E, R>
+where
+    P: Projects<Err = PE>,
+    R: Repository<Aggregate = Project, Err = E>,
+{
+    pub projects: &'a P,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
 }
 
-impl<'a> Repository for SqliteRepository<'a> {
-    type Aggregate = Project;
-    type Err = SqliteRepositoryError;
-
-    fn get(&self, id: ProjectId) -> Result<Project, SqliteRepositoryError> {
-        use crate::database::schema::events::dsl::{aggregate_id, events};
-        use diesel::prelude::*;
-
-        let results: Result<Vec<_>, DomainEventError> = events
-            .filter(aggregate_id.eq(id.to_string()))
-            .load::<Event>(self.db)?
+impl<'a, PE, P, E, R> CreateProjectHandler<'a, PE, P, E, R>
+where
+    P: Projects<Err = PE>,
+    R: Repository<Aggregate = Project, Err = E>,
+    CreateProjectHandlerError: From<PE> + From<E>,
+{
+    pub fn handle(&mut self, command: CreateProject) -> Result<Project, CreateProjectHandlerError> {
+        if self.projects.find(&command.name)?.is_some() {
+            return Err(ProjectError::DuplicateName { name: command.name }.into());
+        }
+        let project_id = ProjectId(command.id);
+        let events = Project::create(project_id, command.name)?;
+        let project = Project::hydrate(&events)?.expect("Project is not None");
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Project>> = events
             .into_iter()
-            .map(DomainEvent::from_event)
-            .map(|x| x.map(|e| e.event))
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: project_id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
             .collect();
-        let project = Project::hydrate(&results?)?;
-        project.ok_or_else(|| SqliteRepositoryError::NotFoundError)
+        self.repository.persist(Generation::first(), &events)?;
+        Ok(project)
     }
+}
 
-    fn persist(
-        &mut self,
-        generation: Generation,
-        events: &[DomainEvent<Project>],
-    ) -> Result<(), SqliteRepositoryError> {
-        let mut generation = generation;
-        for event in events {
-            let new = NewEvent {
-                id: &event.id.to_string(),
-                aggregate_id: &event.aggregate_id.to_string(),
-                generation: generation.into(),
-                created_at: &event.created_at.to_rfc3339(),
-                type_: &event.event.type_(),
-                data: &serde_json::to_string(&event.event)?,
-            };
-            diesel::insert_into(schema::events::table)
-                .values(&new)
-                .execute(self.db)?;
-            generation = generation.next();
-        }
-
-        Ok(())
-    }
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProjectAction {
+    Rename(String),
+    Archive,
+    Restore,
 }
 
-pub struct CreateProject {
-    pub id: Uuid,
-    pub name: String,
+pub struct UpdateProject {
+    pub id: ProjectId,
+    pub action: ProjectAction,
+    pub expected_generation: Option<Generation>,
+    pub metadata: Metadata,
 }
 
-pub struct CreateProjectHandler<'a, E, R>
+pub struct UpdateProjectHandler<'a, PE, P, E, R>
 where
+    P: Projects<Err = PE>,
     R: Repository<Aggregate = Project, Err = E>,
 {
+    pub projects: &'a P,
     pub repository: &'a mut R,
     pub utc_now: fn() -> DateTime<Utc>,
 }
 
-impl<'a, E, R> CreateProjectHandler<'a, E, R>
+impl<'a, PE, P, E, R> UpdateProjectHandler<'a, PE, P, E, R>
 where
+    P: Projects<Err = PE>,
     R: Repository<Aggregate = Project, Err = E>,
-    CreateProjectHandlerError: From<E>,
+    UpdateProjectHandlerError: From<PE> + From<E>,
 {
-    pub fn handle(&mut self, command: CreateProject) -> Result<Project, CreateProjectHandlerError> {
-        let project_id = ProjectId(command.id);
-        let events = Project::create(project_id, command.name)?;
-        let project = Project::hydrate(&events)?.expect("Project is not None");
+    pub fn handle(&mut self, command: UpdateProject) -> Result<Project, UpdateProjectHandlerError> {
+        let mut project = self.repository.get(command.id)?;
+        if let Some(expected) = command.expected_generation {
+            if expected != project.generation {
+                return Err(UpdateProjectHandlerError::ConcurrencyConflict {
+                    expected,
+                    actual: project.generation,
+                });
+            }
+        }
+        let events = match command.action {
+            ProjectAction::Rename(name) => match self.projects.find(&name)? {
+                Some(id) if id != project.id => {
+                    return Err(ProjectError::DuplicateName { name }.into());
+                }
+                _ => project.rename(name)?,
+            },
+            ProjectAction::Archive => project.archive()?,
+            ProjectAction::Restore => project.restore()?,
+        };
+        let generation = project.generation.next();
+        for event in &events {
+            project = Project::apply_event(Some(project), event)?;
+        }
+        let metadata = command.metadata;
         let events: Vec<DomainEvent<Project>> = events
             .into_iter()
             .map(|event| DomainEvent {
                 id: DomainEventId::new(Uuid::new_v4()),
-                aggregate_id: project_id,
+                aggregate_id: project.id,
                 created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
                 event,
             })
             .collect();
-        self.repository.persist(Generation::first(), &events)?;
+        self.repository.persist(generation, &events)?;
         Ok(project)
     }
 }
@@ -196,21 +294,81 @@ pub struct ListProject {
     pub id: ProjectId,
 }
 
-pub struct ListProjectHandler<'a> {
-    pub repository: &'a SqliteRepository<'a>,
+pub struct ListProjectHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Project, Err = E>,
+{
+    pub repository: &'a R,
 }
 
-impl<'a> ListProjectHandler<'a> {
+impl<'a, E, R> ListProjectHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Project, Err = E>,
+    ListProjectHandlerError: From<E>,
+{
     pub fn handle(&self, command: ListProject) -> Result<Project, ListProjectHandlerError> {
         Ok(self.repository.get(command.id)?)
     }
 }
 
+pub struct ListProjects {
+    pub query: ProjectQuery,
+    /// Id of the last Project of the previous page.
+    pub after: Option<ProjectId>,
+    pub limit: usize,
+}
+
+pub struct ProjectPage {
+    pub projects: Vec<ProjectSummary>,
+    /// Cursor to list the next page with, if there is one.
+    pub next: Option<ProjectId>,
+}
+
+pub struct ListProjectsHandler<'a, PE, P>
+where
+    P: Projects<Err = PE>,
+{
+    pub projects: &'a P,
+}
+
+impl<'a, PE, P> ListProjectsHandler<'a, PE, P>
+where
+    P: Projects<Err = PE>,
+    ListProjectsHandlerError: From<PE>,
+{
+    pub fn handle(&self, command: ListProjects) -> Result<ProjectPage, ListProjectsHandlerError> {
+        let after = match command.after {
+            Some(id) => Some(self.projects.get(id)?.ok_or_else(|| {
+                ListProjectsHandlerError::InvalidCursor {
+                    cursor: id.to_string(),
+                }
+            })?),
+            None => None,
+        };
+        let limit = command.limit.clamp(1, MAX_PAGE_SIZE);
+        // One more than asked for tells whether there is a next page
+        let mut projects = self
+            .projects
+            .list(&command.query, after.as_ref(), limit + 1)?;
+        let next = if projects.len() > limit {
+            projects.truncate(limit);
+            projects.last().map(|project| project.id)
+        } else {
+            None
+        };
+        Ok(ProjectPage { projects, next })
+    }
+}
+
 #[cfg(test)]
 mod test {
     mod project {
+        use failure::Error;
         use uuid::Uuid;
 
+        use crate::domain::{Aggregate, Generation};
+
+        use super::super::error::ProjectError;
         use super::super::{Project, ProjectEvent, ProjectId};
 
         #[test]
@@ -225,6 +383,48 @@ mod test {
                 }])
             );
         }
+
+        #[test]
+        fn test_create_invalid_name() {
+            let id = ProjectId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8").unwrap());
+            for name in &["", " test", "test/", &"a".repeat(65)] {
+                assert_eq!(
+                    Project::create(id, (*name).to_owned()),
+                    Err(ProjectError::InvalidName {
+                        name: (*name).to_owned()
+                    }),
+                );
+            }
+            assert!(Project::create(id, "Checkout v2.1_beta-3".to_owned()).is_ok());
+        }
+
+        #[test]
+        fn test_lifecycle() -> Result<(), Error> {
+            let id = ProjectId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+            let project = Project::hydrate(&Project::create(id, "test".to_owned())?)?
+                .expect("Project is not None");
+            assert!(project.check_active().is_ok());
+            assert!(project.restore().is_err());
+
+            let events = project.rename("shop".to_owned())?;
+            let project = Project::apply_event(Some(project), &events[0])?;
+            let events = project.archive()?;
+            let project = Project::apply_event(Some(project), &events[0])?;
+            assert_eq!(project.name, "shop");
+            assert_eq!(
+                project.check_active(),
+                Err(ProjectError::Archived {
+                    project: id.to_string()
+                }),
+            );
+            assert!(project.rename("test".to_owned()).is_err());
+
+            let events = project.restore()?;
+            let project = Project::apply_event(Some(project), &events[0])?;
+            assert!(!project.archived);
+            assert_eq!(project.generation, Generation::first().next().next().next());
+            Ok(())
+        }
     }
 
     mod repository {
@@ -239,18 +439,18 @@ mod test {
         use crate::database::models::{Event, NewEvent};
         use crate::database::schema;
         use crate::database::schema::events::dsl::*;
-        use crate::domain::Repository;
+        use crate::domain::{Metadata, Repository};
+        use crate::event_store::DieselEventStore;
 
         use super::super::{
             DomainEvent, DomainEventId, Generation, Project, ProjectEvent, ProjectId,
-            SqliteRepository,
         };
 
         #[test]
         fn test_get() -> Result<(), Error> {
             let db = &SqliteConnection::establish(":memory:")?;
             diesel_migrations::run_pending_migrations(db)?;
-            let repository = SqliteRepository { db };
+            let repository = DieselEventStore::<_, Project>::new(db);
             let event = NewEvent {
                 id: "550e8400-e29b-41d4-a716-446655440000",
                 aggregate_id: "936da01f-9abd-4d9d-80c7-02af85c822a8",
@@ -258,6 +458,9 @@ mod test {
                 created_at: "2019-01-01T12:34:56+00:00",
                 type_: "Created",
                 data: "{\"Created\":{\"id\":\"936da01f-9abd-4d9d-80c7-02af85c822a8\",\"name\":\"test\"}}",
+                aggregate_type: "project",
+                metadata: "{}",
+                version: 1,
             };
             diesel::insert_into(schema::events::table)
                 .values(&event)
@@ -272,6 +475,7 @@ mod test {
                     id: project_id,
                     generation: Generation::first(),
                     name: "test".to_owned(),
+                    archived: false,
                 },
             );
             Ok(())
@@ -281,7 +485,7 @@ mod test {
         fn test_persist() -> Result<(), Error> {
             let db = &SqliteConnection::establish(":memory:")?;
             diesel_migrations::run_pending_migrations(db)?;
-            let mut repository = SqliteRepository { db };
+            let mut repository = DieselEventStore::<_, Project>::new(db);
             let project_id = ProjectId(Uuid::parse_str("936da01f-9abd-4d9d-80c7-02af85c822a8")?);
             let event_id =
                 DomainEventId::new(Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000")?);
@@ -292,6 +496,7 @@ mod test {
                     id: event_id,
                     aggregate_id: project_id,
                     created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                    metadata: Metadata::default(),
                     event: ProjectEvent::Created {
                         id: project_id,
                         name: "test".into(),
@@ -309,6 +514,10 @@ mod test {
                 created_at: "2019-01-01T00:00:00+00:00".to_owned(),
                 type_: "Created".to_owned(),
                 data: "{\"Created\":{\"id\":\"936da01f-9abd-4d9d-80c7-02af85c822a8\",\"name\":\"test\"}}".to_owned(),
+                aggregate_type: "project".to_owned(),
+                position: 1,
+                metadata: "{}".to_owned(),
+                version: 1,
             }]);
             Ok(())
         }
@@ -0,0 +1,243 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use crate::database::models::{Event, NewAuditLogEntry};
+use crate::database::schema;
+use crate::domain::DomainEvent;
+use crate::environment::{Environment, EnvironmentEvent};
+use crate::project::{Project, ProjectId};
+use crate::segment::{Segment, SegmentEvent};
+use crate::toggle::{self, Toggle};
+use crate::variant::{Variant, VariantEvent};
+
+use super::error::ProjectionError;
+use super::Projection;
+
+/// Every event stored, along with the Project it concerns,
+/// to tell what happened to a Project and everything in it.
+pub trait AuditLog {
+    type Err;
+
+    /// Events of the Project and everything in it, in the order they were stored.
+    fn project(&self, project_id: ProjectId) -> Result<Vec<Event>, Self::Err>;
+}
+
+pub struct DieselAuditLog<'a, C> {
+    pub db: &'a C,
+}
+
+/// Implements the audit log for a kind of diesel connection.
+macro_rules! impl_audit_log {
+    ($connection:ty) => {
+        impl<'a> AuditLog for DieselAuditLog<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn project(&self, project_id: ProjectId) -> Result<Vec<Event>, Self::Err> {
+                use crate::database::schema::audit_log::dsl;
+
+                dsl::audit_log
+                    .filter(dsl::project_id.eq(project_id.to_string()))
+                    .order(dsl::position.asc())
+                    .select((
+                        dsl::event_id,
+                        dsl::aggregate_id,
+                        dsl::generation,
+                        dsl::created_at,
+                        dsl::type_,
+                        dsl::data,
+                        dsl::aggregate_type,
+                        dsl::position,
+                        dsl::metadata,
+                        dsl::version,
+                    ))
+                    .load::<Event>(self.db)
+            }
+        }
+
+        impl<'a> DieselAuditLog<'a, $connection> {
+            /// Project of an aggregate already in the audit log.
+            fn project_of(
+                &self,
+                aggregate_id: &str,
+            ) -> Result<Option<String>, diesel::result::Error> {
+                use crate::database::schema::audit_log::dsl;
+
+                let project_id = dsl::audit_log
+                    .filter(dsl::aggregate_id.eq(aggregate_id))
+                    .select(dsl::project_id)
+                    .first::<Option<String>>(self.db)
+                    .optional()?;
+                Ok(project_id.and_then(|project_id| project_id))
+            }
+
+            /// Only events creating an aggregate say which Project it is in,
+            /// later ones are found by the aggregate's earlier entries.
+            fn project_id(&self, event: &Event) -> Result<Option<String>, ProjectionError> {
+                if let Some(event) = event.decode::<Project>()? {
+                    return Ok(Some(event.aggregate_id.to_string()));
+                }
+                if event.generation > 0 {
+                    return Ok(self.project_of(&event.aggregate_id)?);
+                }
+                let project_id = if let Some(DomainEvent {
+                    event: toggle::Event::Created { project_id, .. },
+                    ..
+                }) = event.decode::<Toggle>()?
+                {
+                    Some(project_id.to_string())
+                } else if let Some(DomainEvent {
+                    event: EnvironmentEvent::Created { project_id, .. },
+                    ..
+                }) = event.decode::<Environment>()?
+                {
+                    Some(project_id.to_string())
+                } else if let Some(DomainEvent {
+                    event: SegmentEvent::Created { project_id, .. },
+                    ..
+                }) = event.decode::<Segment>()?
+                {
+                    Some(project_id.to_string())
+                } else if let Some(DomainEvent {
+                    event: VariantEvent::Created { toggle_id, .. },
+                    ..
+                }) = event.decode::<Variant>()?
+                {
+                    self.project_of(&toggle_id.to_string())?
+                } else {
+                    None
+                };
+                Ok(project_id)
+            }
+        }
+
+        impl<'a> Projection for DieselAuditLog<'a, $connection> {
+            fn name(&self) -> &'static str {
+                "audit_log"
+            }
+
+            fn apply(&mut self, event: &Event) -> Result<(), ProjectionError> {
+                let project_id = self.project_id(event)?;
+                diesel::insert_into(schema::audit_log::table)
+                    .values(&NewAuditLogEntry {
+                        position: event.position,
+                        event_id: &event.id,
+                        aggregate_type: &event.aggregate_type,
+                        aggregate_id: &event.aggregate_id,
+                        project_id: project_id.as_ref().map(String::as_str),
+                        generation: event.generation,
+                        created_at: &event.created_at,
+                        type_: &event.type_,
+                        data: &event.data,
+                        metadata: &event.metadata,
+                        version: event.version,
+                    })
+                    .execute(self.db)?;
+                Ok(())
+            }
+
+            fn reset(&mut self) -> Result<(), ProjectionError> {
+                diesel::delete(schema::audit_log::table).execute(self.db)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_audit_log!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_audit_log!(diesel::pg::PgConnection);
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::event_store::DieselEventStore;
+    use crate::project::{Project, ProjectId};
+    use crate::toggle::{Toggle, ToggleId};
+    use crate::variant::{Variant, VariantId};
+
+    use super::super::Projector;
+    use super::DieselAuditLog;
+
+    #[test]
+    fn test_project_id() -> Result<(), Error> {
+        use crate::database::schema::audit_log::dsl;
+
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let project_id = ProjectId::from(Uuid::new_v4());
+        let toggle_id = ToggleId::from(Uuid::new_v4());
+        let variant_id = VariantId::from(Uuid::new_v4());
+        let event_id = || DomainEventId::new(Uuid::new_v4());
+
+        let events = Project::create(project_id, "test".to_owned())?;
+        DieselEventStore::<_, Project>::new(db).persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: event_id(),
+                aggregate_id: project_id,
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: events[0].clone(),
+            }],
+        )?;
+        let events = Toggle::create(toggle_id, project_id, "test".to_owned())?;
+        let toggle = Toggle::hydrate(&events)?.expect("Toggle is not None");
+        DieselEventStore::<_, Toggle>::new(db).persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: event_id(),
+                aggregate_id: toggle_id,
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: events[0].clone(),
+            }],
+        )?;
+        let events = Variant::create(variant_id, toggle_id, "on".to_owned())?;
+        DieselEventStore::<_, Variant>::new(db).persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: event_id(),
+                aggregate_id: variant_id,
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: events[0].clone(),
+            }],
+        )?;
+        let events = toggle.retire()?;
+        DieselEventStore::<_, Toggle>::new(db).persist(
+            Generation::first().next(),
+            &[DomainEvent {
+                id: event_id(),
+                aggregate_id: toggle_id,
+                created_at: Utc::now(),
+                metadata: Metadata::default(),
+                event: events[0].clone(),
+            }],
+        )?;
+
+        let projector = Projector { db };
+        assert_eq!(projector.catch_up(&mut DieselAuditLog { db })?, 4);
+        let entries = dsl::audit_log
+            .select((dsl::aggregate_type, dsl::type_, dsl::project_id))
+            .order(dsl::position.asc())
+            .load::<(String, String, Option<String>)>(db)?;
+        let project = Some(project_id.to_string());
+        assert_eq!(
+            entries,
+            vec![
+                ("project".to_owned(), "Created".to_owned(), project.clone()),
+                ("toggle".to_owned(), "Created".to_owned(), project.clone()),
+                ("variant".to_owned(), "Created".to_owned(), project.clone()),
+                ("toggle".to_owned(), "Retired".to_owned(), project.clone()),
+            ],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+use failure_derive::Fail;
+
+use crate::event_store::error::DomainEventError;
+
+#[derive(Debug, Fail)]
+pub enum ProjectionError {
+    #[fail(display = "database error")]
+    DatabaseError(#[cause] diesel::result::Error),
+    #[fail(display = "domain event error")]
+    DomainEventError(#[cause] DomainEventError),
+}
+
+impl From<diesel::result::Error> for ProjectionError {
+    fn from(e: diesel::result::Error) -> Self {
+        ProjectionError::DatabaseError(e)
+    }
+}
+
+impl From<DomainEventError> for ProjectionError {
+    fn from(e: DomainEventError) -> Self {
+        ProjectionError::DomainEventError(e)
+    }
+}
@@ -0,0 +1,203 @@
+pub mod audit;
+pub mod error;
+
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+
+use crate::database::models::{Event, NewProjectionCheckpoint};
+use crate::database::schema;
+use crate::event_store::{DieselEventLog, EventStore};
+
+use self::error::ProjectionError;
+
+/// Events handed to a Projection at a time.
+const BATCH_SIZE: usize = 100;
+
+/// A read model built from the events of every aggregate,
+/// applied one at a time in the order they were stored.
+pub trait Projection {
+    /// Name the position reached is checkpointed under.
+    fn name(&self) -> &'static str;
+    fn apply(&mut self, event: &Event) -> Result<(), ProjectionError>;
+    /// Remove everything applied so far, to start again from the first event.
+    fn reset(&mut self) -> Result<(), ProjectionError>;
+}
+
+/// Feeds stored events to Projections, checkpointing how far each has got.
+/// Run it within a transaction so a Projection and its checkpoint can't
+/// disagree.
+pub struct Projector<'a, C> {
+    pub db: &'a C,
+}
+
+/// Implements the Projector for a kind of diesel connection.
+macro_rules! impl_projector {
+    ($connection:ty) => {
+        impl<'a> Projector<'a, $connection> {
+            /// Position of the last event applied to the named Projection, 0 if none.
+            pub fn position(&self, name: &str) -> Result<i64, ProjectionError> {
+                use crate::database::schema::projection_checkpoints::dsl;
+
+                let position = dsl::projection_checkpoints
+                    .find(name)
+                    .select(dsl::position)
+                    .first::<i64>(self.db)
+                    .optional()?;
+                Ok(position.unwrap_or(0))
+            }
+
+            /// Apply every event stored since the last checkpoint, returning how many there were.
+            pub fn catch_up(
+                &self,
+                projection: &mut dyn Projection,
+            ) -> Result<usize, ProjectionError> {
+                let events = DieselEventLog { db: self.db };
+                let mut position = self.position(projection.name())?;
+                let mut applied = 0;
+                loop {
+                    let events = events.read_all(position, BATCH_SIZE)?;
+                    if events.is_empty() {
+                        break;
+                    }
+                    for event in &events {
+                        projection.apply(event)?;
+                        position = event.position;
+                    }
+                    applied += events.len();
+                    self.save_position(projection.name(), position)?;
+                }
+                Ok(applied)
+            }
+
+            /// Start the Projection again from the first event.
+            pub fn rebuild(
+                &self,
+                projection: &mut dyn Projection,
+            ) -> Result<usize, ProjectionError> {
+                projection.reset()?;
+                self.save_position(projection.name(), 0)?;
+                self.catch_up(projection)
+            }
+        }
+    };
+}
+
+impl_projector!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_projector!(diesel::pg::PgConnection);
+
+impl<'a> Projector<'a, SqliteConnection> {
+    fn save_position(&self, name: &str, position: i64) -> Result<(), ProjectionError> {
+        diesel::replace_into(schema::projection_checkpoints::table)
+            .values(&NewProjectionCheckpoint { name, position })
+            .execute(self.db)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl<'a> Projector<'a, diesel::pg::PgConnection> {
+    // An upsert, as a delete and insert would have two catching up at once
+    // both insert and one of them fail on the primary key
+    fn save_position(&self, name: &str, position: i64) -> Result<(), ProjectionError> {
+        use crate::database::schema::projection_checkpoints::dsl;
+
+        diesel::insert_into(schema::projection_checkpoints::table)
+            .values(&NewProjectionCheckpoint { name, position })
+            .on_conflict(dsl::name)
+            .do_update()
+            .set(dsl::position.eq(position))
+            .execute(self.db)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::database::models::Event;
+    use crate::domain::{DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::event_store::DieselEventStore;
+    use crate::project::{Project, ProjectEvent, ProjectId};
+    use crate::toggle::Toggle;
+
+    use super::error::ProjectionError;
+    use super::{Projection, Projector};
+
+    /// Names of the Projects created, in order.
+    struct Names(Vec<String>);
+
+    impl Projection for Names {
+        fn name(&self) -> &'static str {
+            "names"
+        }
+
+        fn apply(&mut self, event: &Event) -> Result<(), ProjectionError> {
+            assert!(event.decode::<Toggle>()?.is_none());
+            if let Some(event) = event.decode::<Project>()? {
+                if let ProjectEvent::Created { name, .. } = event.event {
+                    self.0.push(name);
+                }
+            }
+            Ok(())
+        }
+
+        fn reset(&mut self) -> Result<(), ProjectionError> {
+            self.0.clear();
+            Ok(())
+        }
+    }
+
+    fn create(
+        repository: &mut DieselEventStore<SqliteConnection, Project>,
+        name: &str,
+    ) -> Result<(), Error> {
+        let id = ProjectId::from(Uuid::new_v4());
+        repository.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: id,
+                created_at: Utc.ymd(2019, 5, 18).and_hms(0, 0, 0),
+                metadata: Metadata::default(),
+                event: ProjectEvent::Created {
+                    id,
+                    name: name.to_owned(),
+                },
+            }],
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_catch_up() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let repository = &mut DieselEventStore::<_, Project>::new(db);
+        let projector = Projector { db };
+        let names = &mut Names(vec![]);
+
+        // Random ids, so any order but the stored one would show
+        for name in &["a", "b", "c"] {
+            create(repository, name)?;
+        }
+        assert_eq!(projector.catch_up(names)?, 3);
+        assert_eq!(projector.position("names")?, 3);
+
+        create(repository, "d")?;
+        assert_eq!(projector.catch_up(names)?, 1);
+        assert_eq!(projector.catch_up(names)?, 0);
+        assert_eq!(names.0, vec!["a", "b", "c", "d"]);
+
+        assert_eq!(projector.rebuild(names)?, 4);
+        assert_eq!(names.0, vec!["a", "b", "c", "d"]);
+        Ok(())
+    }
+}
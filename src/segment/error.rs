@@ -0,0 +1,99 @@
+use failure_derive::Fail;
+
+use crate::domain::Generation;
+use crate::event_store::error::EventStoreError;
+use crate::project::error::ProjectError;
+
+#[derive(Debug, Fail)]
+pub enum SegmentIdParseError {
+    #[fail(display = "fail to parse uuid")]
+    UuidParseError(#[cause] uuid::parser::ParseError),
+}
+
+impl From<uuid::parser::ParseError> for SegmentIdParseError {
+    fn from(e: uuid::parser::ParseError) -> SegmentIdParseError {
+        SegmentIdParseError::UuidParseError(e)
+    }
+}
+
+#[derive(Debug, Eq, Fail, PartialEq)]
+pub enum SegmentError {
+    #[fail(display = "invalid segment name: {}", name)]
+    InvalidName { name: String },
+    #[fail(display = "invalid segment rule: {}", reason)]
+    InvalidRule { reason: String },
+    #[fail(display = "invalid event `{}` applied to state `{}", event, state)]
+    InvalidStateEvent { state: String, event: String },
+}
+
+#[derive(Debug, Fail)]
+pub enum CreateSegmentHandlerError {
+    #[fail(display = "segment error")]
+    SegmentError(#[cause] SegmentError),
+    #[fail(display = "project repository error")]
+    ProjectRepositoryError(#[cause] EventStoreError<ProjectError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<SegmentError>),
+}
+
+impl From<SegmentError> for CreateSegmentHandlerError {
+    fn from(e: SegmentError) -> Self {
+        CreateSegmentHandlerError::SegmentError(e)
+    }
+}
+
+impl From<EventStoreError<ProjectError>> for CreateSegmentHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        CreateSegmentHandlerError::ProjectRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<SegmentError>> for CreateSegmentHandlerError {
+    fn from(e: EventStoreError<SegmentError>) -> Self {
+        CreateSegmentHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum GetSegmentHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<SegmentError>),
+}
+
+impl From<EventStoreError<SegmentError>> for GetSegmentHandlerError {
+    fn from(e: EventStoreError<SegmentError>) -> Self {
+        GetSegmentHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum UpdateSegmentHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
+    #[fail(display = "segment error")]
+    SegmentError(#[cause] SegmentError),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<SegmentError>),
+}
+
+impl From<SegmentError> for UpdateSegmentHandlerError {
+    fn from(e: SegmentError) -> Self {
+        UpdateSegmentHandlerError::SegmentError(e)
+    }
+}
+
+impl From<EventStoreError<SegmentError>> for UpdateSegmentHandlerError {
+    fn from(e: EventStoreError<SegmentError>) -> Self {
+        UpdateSegmentHandlerError::RepositoryError(e)
+    }
+}
@@ -0,0 +1,416 @@
+pub mod error;
+
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, Repository,
+};
+use crate::project::{Project, ProjectId};
+use crate::toggle::rule::{Clause, Operator};
+
+use self::error::{
+    CreateSegmentHandlerError, GetSegmentHandlerError, SegmentError, SegmentIdParseError,
+    UpdateSegmentHandlerError,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SegmentId(Uuid);
+
+impl SegmentId {
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl FromStr for SegmentId {
+    type Err = SegmentIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(Self(id))
+    }
+}
+
+impl From<SegmentId> for Uuid {
+    fn from(id: SegmentId) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for SegmentId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+/// Matches every context that passes all of its clauses.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SegmentRule {
+    pub clauses: Vec<Clause>,
+}
+
+/// A named group of users of a Project that targeting Rules
+/// of any of its Toggles can refer to. Excluded keys take
+/// precedence over included keys, which take precedence
+/// over the rules.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Segment {
+    pub id: SegmentId,
+    pub project_id: ProjectId,
+    pub generation: Generation,
+    pub name: String,
+    pub included: BTreeSet<String>,
+    pub excluded: BTreeSet<String>,
+    pub rules: Vec<SegmentRule>,
+}
+
+impl Segment {
+    pub fn create(
+        id: SegmentId,
+        project_id: ProjectId,
+        name: String,
+    ) -> Result<Vec<SegmentEvent>, SegmentError> {
+        if name.trim().is_empty() {
+            return Err(SegmentError::InvalidName { name });
+        }
+        Ok(vec![SegmentEvent::Created {
+            id,
+            project_id,
+            name,
+        }])
+    }
+
+    pub fn rename(&self, name: String) -> Result<Vec<SegmentEvent>, SegmentError> {
+        if name.trim().is_empty() {
+            return Err(SegmentError::InvalidName { name });
+        }
+        self.transition(SegmentEvent::Renamed { name })
+    }
+
+    pub fn change_included(
+        &self,
+        keys: BTreeSet<String>,
+    ) -> Result<Vec<SegmentEvent>, SegmentError> {
+        self.transition(SegmentEvent::IncludedChanged { keys })
+    }
+
+    pub fn change_excluded(
+        &self,
+        keys: BTreeSet<String>,
+    ) -> Result<Vec<SegmentEvent>, SegmentError> {
+        self.transition(SegmentEvent::ExcludedChanged { keys })
+    }
+
+    pub fn change_rules(&self, rules: Vec<SegmentRule>) -> Result<Vec<SegmentEvent>, SegmentError> {
+        for rule in &rules {
+            if rule.clauses.is_empty() {
+                return Err(SegmentError::InvalidRule {
+                    reason: "rule without clauses".to_owned(),
+                });
+            }
+            for clause in &rule.clauses {
+                // Segments can't refer to each other, so membership
+                // never has to be resolved recursively
+                if let Operator::InSegment | Operator::NotInSegment = clause.operator {
+                    return Err(SegmentError::InvalidRule {
+                        reason: "segment rules can't refer to segments".to_owned(),
+                    });
+                }
+                clause
+                    .validate()
+                    .map_err(|reason| SegmentError::InvalidRule { reason })?;
+            }
+        }
+        self.transition(SegmentEvent::RulesChanged { rules })
+    }
+
+    fn transition(&self, event: SegmentEvent) -> Result<Vec<SegmentEvent>, SegmentError> {
+        Self::apply_event(Some(self.clone()), &event)?;
+        Ok(vec![event])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum SegmentEvent {
+    Created {
+        id: SegmentId,
+        project_id: ProjectId,
+        name: String,
+    },
+    Renamed {
+        name: String,
+    },
+    IncludedChanged {
+        keys: BTreeSet<String>,
+    },
+    ExcludedChanged {
+        keys: BTreeSet<String>,
+    },
+    RulesChanged {
+        rules: Vec<SegmentRule>,
+    },
+}
+
+impl AggregateEvent for SegmentEvent {
+    fn type_(&self) -> String {
+        match self {
+            SegmentEvent::Created { .. } => "Created".to_owned(),
+            SegmentEvent::Renamed { .. } => "Renamed".to_owned(),
+            SegmentEvent::IncludedChanged { .. } => "IncludedChanged".to_owned(),
+            SegmentEvent::ExcludedChanged { .. } => "ExcludedChanged".to_owned(),
+            SegmentEvent::RulesChanged { .. } => "RulesChanged".to_owned(),
+        }
+    }
+}
+
+impl Aggregate for Segment {
+    type Id = SegmentId;
+    type Event = SegmentEvent;
+    type Err = SegmentError;
+    const TYPE: &'static str = "segment";
+
+    fn id(&self) -> &SegmentId {
+        &self.id
+    }
+
+    fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    fn apply_event(segment: Option<Self>, event: &SegmentEvent) -> Result<Self, SegmentError> {
+        match (&segment, event) {
+            (
+                None,
+                SegmentEvent::Created {
+                    id,
+                    project_id,
+                    name,
+                },
+            ) => Ok(Segment {
+                id: *id,
+                project_id: *project_id,
+                generation: Generation::first(),
+                name: name.clone(),
+                included: BTreeSet::new(),
+                excluded: BTreeSet::new(),
+                rules: vec![],
+            }),
+            (Some(segment), SegmentEvent::Renamed { name }) => Ok(Segment {
+                generation: segment.generation.next(),
+                name: name.clone(),
+                ..segment.clone()
+            }),
+            (Some(segment), SegmentEvent::IncludedChanged { keys }) => Ok(Segment {
+                generation: segment.generation.next(),
+                included: keys.clone(),
+                ..segment.clone()
+            }),
+            (Some(segment), SegmentEvent::ExcludedChanged { keys }) => Ok(Segment {
+                generation: segment.generation.next(),
+                excluded: keys.clone(),
+                ..segment.clone()
+            }),
+            (Some(segment), SegmentEvent::RulesChanged { rules }) => Ok(Segment {
+                generation: segment.generation.next(),
+                rules: rules.clone(),
+                ..segment.clone()
+            }),
+            _ => Err(SegmentError::InvalidStateEvent {
+                state: format!("{:?}", segment),
+                event: format!("{:?}", event),
+            }),
+        }
+    }
+}
+
+pub struct CreateSegment {
+    pub id: Uuid,
+    pub project_id: ProjectId,
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+pub struct CreateSegmentHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Segment, Err = E>,
+{
+    pub projects: &'a P,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, PE, P, E, R> CreateSegmentHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Segment, Err = E>,
+    CreateSegmentHandlerError: From<PE> + From<E>,
+{
+    pub fn handle(&mut self, command: CreateSegment) -> Result<Segment, CreateSegmentHandlerError> {
+        let project = self.projects.get(command.project_id)?;
+        let segment_id = SegmentId(command.id);
+        let events = Segment::create(segment_id, project.id, command.name)?;
+        let segment = Segment::hydrate(&events)?.expect("Segment is not None");
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Segment>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: segment_id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(Generation::first(), &events)?;
+        Ok(segment)
+    }
+}
+
+pub struct GetSegment {
+    pub project_id: ProjectId,
+    pub id: SegmentId,
+}
+
+pub struct GetSegmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Segment, Err = E>,
+{
+    pub repository: &'a R,
+}
+
+impl<'a, E, R> GetSegmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Segment, Err = E>,
+    GetSegmentHandlerError: From<E>,
+{
+    pub fn handle(&self, command: GetSegment) -> Result<Segment, GetSegmentHandlerError> {
+        let segment = self.repository.get(command.id)?;
+        if segment.project_id != command.project_id {
+            return Err(GetSegmentHandlerError::NotFoundError);
+        }
+        Ok(segment)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SegmentAction {
+    Rename(String),
+    ChangeIncluded(BTreeSet<String>),
+    ChangeExcluded(BTreeSet<String>),
+    ChangeRules(Vec<SegmentRule>),
+}
+
+pub struct UpdateSegment {
+    pub project_id: ProjectId,
+    pub id: SegmentId,
+    pub action: SegmentAction,
+    pub expected_generation: Option<Generation>,
+    pub metadata: Metadata,
+}
+
+pub struct UpdateSegmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Segment, Err = E>,
+{
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, E, R> UpdateSegmentHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Segment, Err = E>,
+    UpdateSegmentHandlerError: From<E>,
+{
+    pub fn handle(&mut self, command: UpdateSegment) -> Result<Segment, UpdateSegmentHandlerError> {
+        let mut segment = self.repository.get(command.id)?;
+        if segment.project_id != command.project_id {
+            return Err(UpdateSegmentHandlerError::NotFoundError);
+        }
+        if let Some(expected) = command.expected_generation {
+            if expected != segment.generation {
+                return Err(UpdateSegmentHandlerError::ConcurrencyConflict {
+                    expected,
+                    actual: segment.generation,
+                });
+            }
+        }
+        let events = match command.action {
+            SegmentAction::Rename(name) => segment.rename(name)?,
+            SegmentAction::ChangeIncluded(keys) => segment.change_included(keys)?,
+            SegmentAction::ChangeExcluded(keys) => segment.change_excluded(keys)?,
+            SegmentAction::ChangeRules(rules) => segment.change_rules(rules)?,
+        };
+        let generation = segment.generation.next();
+        for event in &events {
+            segment = Segment::apply_event(Some(segment), event)?;
+        }
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Segment>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: segment.id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(generation, &events)?;
+        Ok(segment)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::Aggregate;
+    use crate::project::ProjectId;
+    use crate::toggle::rule::{Clause, Operator};
+
+    use super::error::SegmentError;
+    use super::{Segment, SegmentId, SegmentRule};
+
+    fn created() -> Result<Segment, Error> {
+        let id = SegmentId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = Segment::create(id, project_id, "beta testers".to_owned())?;
+        Ok(Segment::hydrate(&events)?.expect("Segment is not None"))
+    }
+
+    #[test]
+    fn test_change_keys() -> Result<(), Error> {
+        let segment = created()?;
+        let keys = vec!["alice".to_owned(), "bob".to_owned()]
+            .into_iter()
+            .collect();
+        let events = segment.change_included(keys)?;
+        let segment = Segment::apply_event(Some(segment), &events[0])?;
+        assert!(segment.included.contains("alice"));
+        assert!(segment.excluded.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_rules_referring_to_segment() -> Result<(), Error> {
+        let segment = created()?;
+        let rule = SegmentRule {
+            clauses: vec![Clause {
+                attribute: "user_id".to_owned(),
+                operator: Operator::InSegment,
+                values: vec![segment.id.to_string()],
+            }],
+        };
+        match segment.change_rules(vec![rule]) {
+            Err(SegmentError::InvalidRule { .. }) => Ok(()),
+            result => panic!("expected invalid rule, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use failure_derive::Fail;
+
+use crate::domain::Generation;
+use crate::environment::error::EnvironmentError;
+use crate::event_store::error::EventStoreError;
+use crate::project::error::ProjectError;
+use crate::variant::error::VariantError;
+
+#[derive(Debug, Fail)]
+pub enum ToggleIdParseError {
+    #[fail(display = "fail to parse uuid")]
+    UuidParseError(#[cause] uuid::parser::ParseError),
+}
+
+impl From<uuid::parser::ParseError> for ToggleIdParseError {
+    fn from(e: uuid::parser::ParseError) -> ToggleIdParseError {
+        ToggleIdParseError::UuidParseError(e)
+    }
+}
+
+#[derive(Debug, Eq, Fail, PartialEq)]
+pub enum ToggleError {
+    #[fail(display = "invalid name: {}", name)]
+    InvalidName { name: String },
+    #[fail(display = "invalid environment: {}", environment)]
+    InvalidEnvironment { environment: String },
+    #[fail(display = "invalid rollout percentage: {}", percentage)]
+    InvalidRollout { percentage: u8 },
+    #[fail(display = "invalid rule: {}", reason)]
+    InvalidRule { reason: String },
+    #[fail(display = "invalid prerequisite: {}", toggle)]
+    InvalidPrerequisite { toggle: String },
+    #[fail(display = "prerequisites of toggle {} form a cycle", toggle)]
+    PrerequisiteCycle { toggle: String },
+    #[fail(display = "invalid schedule: {}", schedule)]
+    InvalidSchedule { schedule: String },
+    #[fail(display = "cannot revert to generation {}", generation)]
+    InvalidRevert { generation: i32 },
+    #[fail(display = "invalid event `{}` applied to state `{}", event, state)]
+    InvalidStateEvent { state: String, event: String },
+}
+
+#[derive(Debug, Fail)]
+pub enum CreateToggleHandlerError {
+    #[fail(display = "toggle error")]
+    ToggleError(#[cause] ToggleError),
+    #[fail(display = "project error")]
+    ProjectError(#[cause] ProjectError),
+    #[fail(display = "project repository error")]
+    ProjectRepositoryError(#[cause] EventStoreError<ProjectError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<ToggleError>),
+}
+
+impl From<ToggleError> for CreateToggleHandlerError {
+    fn from(e: ToggleError) -> Self {
+        CreateToggleHandlerError::ToggleError(e)
+    }
+}
+
+impl From<ProjectError> for CreateToggleHandlerError {
+    fn from(e: ProjectError) -> Self {
+        CreateToggleHandlerError::ProjectError(e)
+    }
+}
+
+impl From<EventStoreError<ProjectError>> for CreateToggleHandlerError {
+    fn from(e: EventStoreError<ProjectError>) -> Self {
+        CreateToggleHandlerError::ProjectRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<ToggleError>> for CreateToggleHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        CreateToggleHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum GetToggleHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<ToggleError>),
+}
+
+impl From<EventStoreError<ToggleError>> for GetToggleHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        GetToggleHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum UpdateToggleHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
+    #[fail(display = "toggle error")]
+    ToggleError(#[cause] ToggleError),
+    #[fail(display = "environment repository error")]
+    EnvironmentRepositoryError(#[cause] EventStoreError<EnvironmentError>),
+    #[fail(display = "variant names error")]
+    VariantNamesError(#[cause] diesel::result::Error),
+    #[fail(display = "variant repository error")]
+    VariantRepositoryError(#[cause] EventStoreError<VariantError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<ToggleError>),
+}
+
+impl From<EventStoreError<EnvironmentError>> for UpdateToggleHandlerError {
+    fn from(e: EventStoreError<EnvironmentError>) -> Self {
+        UpdateToggleHandlerError::EnvironmentRepositoryError(e)
+    }
+}
+
+impl From<diesel::result::Error> for UpdateToggleHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        UpdateToggleHandlerError::VariantNamesError(e)
+    }
+}
+
+impl From<EventStoreError<VariantError>> for UpdateToggleHandlerError {
+    fn from(e: EventStoreError<VariantError>) -> Self {
+        UpdateToggleHandlerError::VariantRepositoryError(e)
+    }
+}
+
+impl From<ToggleError> for UpdateToggleHandlerError {
+    fn from(e: ToggleError) -> Self {
+        UpdateToggleHandlerError::ToggleError(e)
+    }
+}
+
+impl From<EventStoreError<ToggleError>> for UpdateToggleHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        UpdateToggleHandlerError::RepositoryError(e)
+    }
+}
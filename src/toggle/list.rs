@@ -0,0 +1,294 @@
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use uuid::Uuid;
+
+use crate::database::models::{self, NewToggle, NewToggleEnvironment};
+use crate::database::schema;
+use crate::environment::EnvironmentId;
+use crate::project::ProjectId;
+use crate::projection::error::ProjectionError;
+use crate::projection::Projection;
+
+use super::{Event, Toggle, ToggleId};
+
+/// A Toggle as it is within one Environment, as kept in the toggles read model.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToggleSummary {
+    pub id: ToggleId,
+    pub name: String,
+    pub enabled: bool,
+    pub rollout: Option<u8>,
+}
+
+/// Read model of the Toggles of every Project by Environment,
+/// so an Environment's Toggles can be listed without replaying
+/// every Toggle's events.
+pub trait Toggles {
+    type Err;
+
+    /// Toggles of the Project that aren't retired, by name.
+    fn list(
+        &self,
+        project_id: ProjectId,
+        environment_id: EnvironmentId,
+    ) -> Result<Vec<ToggleSummary>, Self::Err>;
+}
+
+pub struct DieselToggles<'a, C> {
+    pub db: &'a C,
+}
+
+/// Implements the toggles read model for a kind of diesel connection.
+macro_rules! impl_toggles {
+    ($connection:ty) => {
+        impl<'a> Toggles for DieselToggles<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn list(
+                &self,
+                project_id: ProjectId,
+                environment_id: EnvironmentId,
+            ) -> Result<Vec<ToggleSummary>, Self::Err> {
+                use crate::database::schema::toggle_environments::dsl as environments;
+                use crate::database::schema::toggles::dsl;
+
+                dsl::toggles
+                    .left_join(
+                        environments::toggle_environments.on(environments::toggle_id
+                            .eq(dsl::id)
+                            .and(environments::environment_id.eq(environment_id.to_string()))),
+                    )
+                    .filter(dsl::project_id.eq(project_id.to_string()))
+                    .filter(dsl::retired.eq(false))
+                    .order((dsl::name.asc(), dsl::id.asc()))
+                    .select((
+                        dsl::id,
+                        dsl::name,
+                        environments::enabled.nullable(),
+                        environments::rollout.nullable(),
+                    ))
+                    .load::<(String, String, Option<bool>, Option<i32>)>(self.db)?
+                    .into_iter()
+                    .map(|(id, name, enabled, rollout)| {
+                        Ok(ToggleSummary {
+                            id: Uuid::parse_str(&id).map(ToggleId).map_err(|e| {
+                                diesel::result::Error::DeserializationError(Box::new(e))
+                            })?,
+                            name,
+                            enabled: enabled.unwrap_or(false),
+                            rollout: rollout.map(|rollout| rollout as u8),
+                        })
+                    })
+                    .collect()
+            }
+        }
+
+        impl<'a> DieselToggles<'a, $connection> {
+            /// Change the Toggle within the Environment, starting
+            /// from a disabled Toggle if it hasn't been changed there before.
+            fn change<F>(
+                &self,
+                toggle_id: &str,
+                environment_id: EnvironmentId,
+                change: F,
+            ) -> Result<(), diesel::result::Error>
+            where
+                F: FnOnce(&mut NewToggleEnvironment),
+            {
+                use crate::database::schema::toggle_environments::dsl;
+
+                let environment_id = environment_id.to_string();
+                let current = dsl::toggle_environments
+                    .find((toggle_id, &environment_id))
+                    .select((dsl::enabled, dsl::rollout))
+                    .first::<(bool, Option<i32>)>(self.db)
+                    .optional()?;
+                let (enabled, rollout) = current.unwrap_or((false, None));
+                let mut row = NewToggleEnvironment {
+                    toggle_id,
+                    environment_id: &environment_id,
+                    enabled,
+                    rollout,
+                };
+                change(&mut row);
+                diesel::delete(dsl::toggle_environments.find((toggle_id, &environment_id)))
+                    .execute(self.db)?;
+                diesel::insert_into(schema::toggle_environments::table)
+                    .values(&row)
+                    .execute(self.db)?;
+                Ok(())
+            }
+        }
+
+        impl<'a> Projection for DieselToggles<'a, $connection> {
+            fn name(&self) -> &'static str {
+                "toggles"
+            }
+
+            fn apply(&mut self, event: &models::Event) -> Result<(), ProjectionError> {
+                use crate::database::schema::toggles::dsl;
+
+                let event = match event.decode::<Toggle>()? {
+                    Some(event) => event,
+                    None => return Ok(()),
+                };
+                let id = event.aggregate_id.to_string();
+                match event.event {
+                    Event::Created {
+                        project_id, name, ..
+                    } => {
+                        diesel::insert_into(schema::toggles::table)
+                            .values(&NewToggle {
+                                id: &id,
+                                project_id: &project_id.to_string(),
+                                name: &name,
+                                retired: false,
+                            })
+                            .execute(self.db)?;
+                    }
+                    Event::Enabled { environment_id } => {
+                        self.change(&id, environment_id, |row| row.enabled = true)?;
+                    }
+                    Event::Disabled { environment_id } => {
+                        self.change(&id, environment_id, |row| row.enabled = false)?;
+                    }
+                    Event::RolloutChanged {
+                        environment_id,
+                        percentage,
+                    } => {
+                        self.change(&id, environment_id, |row| {
+                            row.rollout = Some(i32::from(percentage))
+                        })?;
+                    }
+                    Event::RolloutCleared { environment_id } => {
+                        self.change(&id, environment_id, |row| row.rollout = None)?;
+                    }
+                    Event::Retired => {
+                        diesel::update(dsl::toggles.find(&id))
+                            .set(dsl::retired.eq(true))
+                            .execute(self.db)?;
+                    }
+                    Event::Revived => {
+                        diesel::update(dsl::toggles.find(&id))
+                            .set(dsl::retired.eq(false))
+                            .execute(self.db)?;
+                    }
+                    _ => {}
+                }
+                Ok(())
+            }
+
+            fn reset(&mut self) -> Result<(), ProjectionError> {
+                diesel::delete(schema::toggle_environments::table).execute(self.db)?;
+                diesel::delete(schema::toggles::table).execute(self.db)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_toggles!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_toggles!(diesel::pg::PgConnection);
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::environment::EnvironmentId;
+    use crate::event_store::DieselEventStore;
+    use crate::project::ProjectId;
+    use crate::projection::Projector;
+
+    use super::super::{Event, Toggle, ToggleId};
+    use super::{DieselToggles, ToggleSummary, Toggles};
+
+    #[test]
+    fn test_list() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let project_id = ProjectId::from(Uuid::new_v4());
+        let staging = EnvironmentId::from(Uuid::new_v4());
+        let production = EnvironmentId::from(Uuid::new_v4());
+        let persist = |id, generation, events: Vec<Event>| {
+            let events: Vec<DomainEvent<Toggle>> = events
+                .into_iter()
+                .map(|event| DomainEvent {
+                    id: DomainEventId::new(Uuid::new_v4()),
+                    aggregate_id: id,
+                    created_at: Utc::now(),
+                    metadata: Metadata::default(),
+                    event,
+                })
+                .collect();
+            DieselEventStore::<_, Toggle>::new(db).persist(generation, &events)
+        };
+        let created = |id, name: &str| Event::Created {
+            id,
+            project_id,
+            name: name.to_owned(),
+        };
+
+        let search = ToggleId::from(Uuid::new_v4());
+        let checkout = ToggleId::from(Uuid::new_v4());
+        let legacy = ToggleId::from(Uuid::new_v4());
+        persist(
+            search,
+            Generation::first(),
+            vec![
+                created(search, "search"),
+                Event::Enabled {
+                    environment_id: staging,
+                },
+                Event::RolloutChanged {
+                    environment_id: staging,
+                    percentage: 10,
+                },
+                Event::Enabled {
+                    environment_id: production,
+                },
+            ],
+        )?;
+        persist(
+            checkout,
+            Generation::first(),
+            vec![created(checkout, "checkout")],
+        )?;
+        persist(
+            legacy,
+            Generation::first(),
+            vec![created(legacy, "legacy"), Event::Retired],
+        )?;
+
+        let toggles = &mut DieselToggles { db };
+        Projector { db }.catch_up(toggles)?;
+
+        assert_eq!(
+            toggles.list(project_id, staging)?,
+            vec![
+                ToggleSummary {
+                    id: checkout,
+                    name: "checkout".to_owned(),
+                    enabled: false,
+                    rollout: None,
+                },
+                ToggleSummary {
+                    id: search,
+                    name: "search".to_owned(),
+                    enabled: true,
+                    rollout: Some(10),
+                },
+            ],
+        );
+        assert!(toggles
+            .list(ProjectId::from(Uuid::new_v4()), staging)?
+            .is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,1609 @@
+pub mod error;
+pub mod list;
+pub mod rule;
+pub mod schedule;
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, PointInTime,
+    Repository,
+};
+use crate::environment::{Environment, EnvironmentId};
+use crate::event_store::error::EventStoreError;
+use crate::project::{Project, ProjectId};
+use crate::variant::{self, Variant, VariantId, VariantNames, Variants};
+
+use self::rule::Rule;
+use self::schedule::{Change, ScheduleId, ScheduledChange};
+
+use self::error::{
+    CreateToggleHandlerError, GetToggleHandlerError, ToggleError, ToggleIdParseError,
+    UpdateToggleHandlerError,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ToggleId(Uuid);
+
+impl ToggleId {
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl FromStr for ToggleId {
+    type Err = ToggleIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(Self(id))
+    }
+}
+
+impl From<ToggleId> for Uuid {
+    fn from(id: ToggleId) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for ToggleId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Toggle {
+    // Universally unique identifier
+    id: ToggleId,
+    // Project the Toggle belongs to
+    project_id: ProjectId,
+    // For optimistic locking
+    generation: Generation,
+    // Human readable name
+    name: String,
+    // For evolving Toggles
+    version: i32,
+    // State in each Environment the Toggle has been switched in
+    environments: HashMap<EnvironmentId, EnvironmentState>,
+    // Toggles that have to serve a given variant for this one to be on
+    prerequisites: Vec<Prerequisite>,
+    // Changes still to be made, in the order they were scheduled
+    schedules: Vec<ScheduledChange>,
+    // Retired Toggles can no longer be switched on or off
+    retired: bool,
+}
+
+/// State of a Toggle within a single Environment.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct EnvironmentState {
+    pub enabled: bool,
+    // Percentage of users the Toggle is on for while enabled,
+    // everyone when no rollout has been set
+    pub rollout: Option<u8>,
+    // Checked in order before falling through to the rollout
+    pub rules: Vec<Rule>,
+}
+
+/// Another Toggle of the same Project, which has to serve
+/// the variant for the dependent Toggle to be on.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Prerequisite {
+    pub toggle_id: ToggleId,
+    // Name of the variant as of when the prerequisites were last changed
+    pub variant: String,
+    // The Variant of the other Toggle, as for Rules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant_id: Option<VariantId>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Event {
+    Created {
+        id: ToggleId,
+        project_id: ProjectId,
+        name: String,
+    },
+    Enabled {
+        environment_id: EnvironmentId,
+    },
+    Disabled {
+        environment_id: EnvironmentId,
+    },
+    RolloutChanged {
+        environment_id: EnvironmentId,
+        percentage: u8,
+    },
+    /// Back to having no rollout, as a reverted Environment may be.
+    RolloutCleared {
+        environment_id: EnvironmentId,
+    },
+    RulesChanged {
+        environment_id: EnvironmentId,
+        rules: Vec<Rule>,
+    },
+    PrerequisitesChanged {
+        prerequisites: Vec<Prerequisite>,
+    },
+    ChangeScheduled {
+        schedule: ScheduledChange,
+    },
+    ScheduleCancelled {
+        schedule_id: ScheduleId,
+    },
+    ScheduleExecuted {
+        schedule_id: ScheduleId,
+    },
+    /// The change could no longer be made when it was due.
+    ScheduleFailed {
+        schedule_id: ScheduleId,
+        reason: String,
+    },
+    Retired,
+    Revived,
+}
+
+impl AggregateEvent for Event {
+    fn type_(&self) -> String {
+        match self {
+            Event::Created { .. } => "Created".to_owned(),
+            Event::Enabled { .. } => "Enabled".to_owned(),
+            Event::Disabled { .. } => "Disabled".to_owned(),
+            Event::RolloutChanged { .. } => "RolloutChanged".to_owned(),
+            Event::RolloutCleared { .. } => "RolloutCleared".to_owned(),
+            Event::RulesChanged { .. } => "RulesChanged".to_owned(),
+            Event::PrerequisitesChanged { .. } => "PrerequisitesChanged".to_owned(),
+            Event::ChangeScheduled { .. } => "ChangeScheduled".to_owned(),
+            Event::ScheduleCancelled { .. } => "ScheduleCancelled".to_owned(),
+            Event::ScheduleExecuted { .. } => "ScheduleExecuted".to_owned(),
+            Event::ScheduleFailed { .. } => "ScheduleFailed".to_owned(),
+            Event::Retired => "Retired".to_owned(),
+            Event::Revived => "Revived".to_owned(),
+        }
+    }
+}
+
+impl Toggle {
+    pub fn create(
+        id: ToggleId,
+        project_id: ProjectId,
+        name: String,
+    ) -> Result<Vec<Event>, ToggleError> {
+        if name.trim().is_empty() {
+            return Err(ToggleError::InvalidName { name });
+        }
+        Ok(vec![Event::Created {
+            id,
+            project_id,
+            name,
+        }])
+    }
+
+    pub fn project_id(&self) -> ProjectId {
+        self.project_id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn environments(&self) -> &HashMap<EnvironmentId, EnvironmentState> {
+        &self.environments
+    }
+
+    pub fn enabled(&self, environment_id: EnvironmentId) -> bool {
+        self.environments
+            .get(&environment_id)
+            .map_or(false, |environment| environment.enabled)
+    }
+
+    pub fn prerequisites(&self) -> &[Prerequisite] {
+        &self.prerequisites
+    }
+
+    pub fn schedules(&self) -> &[ScheduledChange] {
+        &self.schedules
+    }
+
+    pub fn schedule(&self, id: ScheduleId) -> Option<&ScheduledChange> {
+        self.schedules.iter().find(|schedule| schedule.id == id)
+    }
+
+    pub fn retired(&self) -> bool {
+        self.retired
+    }
+
+    pub fn enable(&self, environment: &Environment) -> Result<Vec<Event>, ToggleError> {
+        Self::check_active(environment)?;
+        self.check_environment(environment)?;
+        self.transition(Event::Enabled {
+            environment_id: environment.id,
+        })
+    }
+
+    pub fn disable(&self, environment: &Environment) -> Result<Vec<Event>, ToggleError> {
+        self.check_environment(environment)?;
+        self.transition(Event::Disabled {
+            environment_id: environment.id,
+        })
+    }
+
+    /// Serve the Toggle to only a percentage of users while it is enabled.
+    pub fn change_rollout(
+        &self,
+        environment: &Environment,
+        percentage: u8,
+    ) -> Result<Vec<Event>, ToggleError> {
+        if percentage > 100 {
+            return Err(ToggleError::InvalidRollout { percentage });
+        }
+        self.check_environment(environment)?;
+        self.transition(Event::RolloutChanged {
+            environment_id: environment.id,
+            percentage,
+        })
+    }
+
+    /// Replace the targeting Rules, which are evaluated in the given order.
+    /// `variants` has to hold the live Variants of the Toggle, which Rules
+    /// serve other than the built-in `on` and `off`.
+    pub fn change_rules(
+        &self,
+        environment: &Environment,
+        rules: Vec<Rule>,
+        variants: &Variants,
+    ) -> Result<Vec<Event>, ToggleError> {
+        for rule in &rules {
+            rule.validate()
+                .map_err(|reason| ToggleError::InvalidRule { reason })?;
+        }
+        let rules = self.resolve_rules(rules, variants)?;
+        self.check_environment(environment)?;
+        self.transition(Event::RulesChanged {
+            environment_id: environment.id,
+            rules,
+        })
+    }
+
+    /// Replace the prerequisites. `toggles` has to hold every Toggle
+    /// reachable through them, so that cycles back to this Toggle
+    /// can be detected, and `variants` the live Variants of the
+    /// Toggles they name.
+    pub fn change_prerequisites(
+        &self,
+        prerequisites: Vec<Prerequisite>,
+        toggles: &HashMap<ToggleId, Toggle>,
+        variants: &Variants,
+    ) -> Result<Vec<Event>, ToggleError> {
+        let prerequisites = self.resolve_prerequisites(prerequisites, variants)?;
+        self.check_prerequisites(&prerequisites, toggles)?;
+        self.transition(Event::PrerequisitesChanged { prerequisites })
+    }
+
+    /// The Rules referring to their variants by id and current name.
+    fn resolve_rules(
+        &self,
+        rules: Vec<Rule>,
+        variants: &Variants,
+    ) -> Result<Vec<Rule>, ToggleError> {
+        rules
+            .into_iter()
+            .map(
+                |rule| match variant::resolve(self.id, rule.variant_id, &rule.variant, variants) {
+                    Some((variant_id, variant)) => Ok(Rule {
+                        variant,
+                        variant_id,
+                        ..rule
+                    }),
+                    None => Err(ToggleError::InvalidRule {
+                        reason: format!("unknown variant `{}`", rule.variant),
+                    }),
+                },
+            )
+            .collect()
+    }
+
+    /// The prerequisites referring to their variants by id and current name.
+    fn resolve_prerequisites(
+        &self,
+        prerequisites: Vec<Prerequisite>,
+        variants: &Variants,
+    ) -> Result<Vec<Prerequisite>, ToggleError> {
+        prerequisites
+            .into_iter()
+            .map(|prerequisite| {
+                let resolved = variant::resolve(
+                    prerequisite.toggle_id,
+                    prerequisite.variant_id,
+                    &prerequisite.variant,
+                    variants,
+                );
+                match resolved {
+                    Some((variant_id, variant)) => Ok(Prerequisite {
+                        variant,
+                        variant_id,
+                        ..prerequisite
+                    }),
+                    None => Err(ToggleError::InvalidPrerequisite {
+                        toggle: prerequisite.toggle_id.to_string(),
+                    }),
+                }
+            })
+            .collect()
+    }
+
+    fn check_prerequisites(
+        &self,
+        prerequisites: &[Prerequisite],
+        toggles: &HashMap<ToggleId, Toggle>,
+    ) -> Result<(), ToggleError> {
+        for prerequisite in prerequisites {
+            let valid = !prerequisite.variant.trim().is_empty()
+                && (prerequisite.toggle_id == self.id
+                    || toggles
+                        .get(&prerequisite.toggle_id)
+                        .map_or(false, |toggle| toggle.project_id == self.project_id));
+            if !valid {
+                return Err(ToggleError::InvalidPrerequisite {
+                    toggle: prerequisite.toggle_id.to_string(),
+                });
+            }
+        }
+        let mut visited = HashSet::new();
+        let mut pending: Vec<ToggleId> = prerequisites.iter().map(|p| p.toggle_id).collect();
+        while let Some(id) = pending.pop() {
+            if id == self.id {
+                return Err(ToggleError::PrerequisiteCycle {
+                    toggle: self.id.to_string(),
+                });
+            }
+            if visited.insert(id) {
+                if let Some(toggle) = toggles.get(&id) {
+                    pending.extend(toggle.prerequisites.iter().map(|p| p.toggle_id));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Make a change to the Toggle within an Environment at a later time.
+    pub fn schedule_change(
+        &self,
+        environment: &Environment,
+        schedule: ScheduledChange,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Event>, ToggleError> {
+        if schedule.at <= now || schedule.environment_id != environment.id {
+            return Err(ToggleError::InvalidSchedule {
+                schedule: schedule.id.to_string(),
+            });
+        }
+        if let Change::ChangeRollout(percentage) = schedule.change {
+            if percentage > 100 {
+                return Err(ToggleError::InvalidRollout { percentage });
+            }
+        }
+        self.check_environment(environment)?;
+        self.transition(Event::ChangeScheduled { schedule })
+    }
+
+    pub fn cancel_schedule(&self, schedule_id: ScheduleId) -> Result<Vec<Event>, ToggleError> {
+        if self.schedule(schedule_id).is_none() {
+            return Err(ToggleError::InvalidSchedule {
+                schedule: schedule_id.to_string(),
+            });
+        }
+        self.transition(Event::ScheduleCancelled { schedule_id })
+    }
+
+    /// Make a ScheduledChange that is due. A change that can no longer be
+    /// made, such as enabling a Toggle that has since been retired, is an
+    /// error and leaves the schedule pending, to be failed with `fail_schedule`.
+    pub fn execute_schedule(
+        &self,
+        environment: &Environment,
+        schedule_id: ScheduleId,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Event>, ToggleError> {
+        let schedule = match self.schedule(schedule_id) {
+            Some(schedule) if schedule.at <= now && schedule.environment_id == environment.id => {
+                schedule
+            }
+            _ => {
+                return Err(ToggleError::InvalidSchedule {
+                    schedule: schedule_id.to_string(),
+                })
+            }
+        };
+        let changed = match schedule.change {
+            Change::Enable => self.enable(environment),
+            Change::Disable => self.disable(environment),
+            Change::ChangeRollout(percentage) => self.change_rollout(environment, percentage),
+        };
+        let mut events = changed?;
+        events.extend(self.transition(Event::ScheduleExecuted { schedule_id })?);
+        Ok(events)
+    }
+
+    /// Give up on a pending ScheduledChange that could not be made, so it
+    /// is no longer tried, keeping why in the Toggle's history.
+    pub fn fail_schedule(
+        &self,
+        schedule_id: ScheduleId,
+        reason: String,
+    ) -> Result<Vec<Event>, ToggleError> {
+        if self.schedule(schedule_id).is_none() {
+            return Err(ToggleError::InvalidSchedule {
+                schedule: schedule_id.to_string(),
+            });
+        }
+        self.transition(Event::ScheduleFailed {
+            schedule_id,
+            reason,
+        })
+    }
+
+    /// Undo every change since `target`, the Toggle as it was at an earlier
+    /// generation, with events making the changes back rather than by
+    /// dropping any. Only the state within `environment_id` is reverted
+    /// when it is given. Schedules are left alone, being plans rather than
+    /// state. Rules and prerequisites can only be reverted to while their
+    /// variants are live. `environments` has to hold the Environments the
+    /// Toggle is enabled in at `target`, as it can't be enabled again in
+    /// those archived since.
+    pub fn revert(
+        &self,
+        target: &Toggle,
+        environment_id: Option<EnvironmentId>,
+        environments: &HashMap<EnvironmentId, Environment>,
+        toggles: &HashMap<ToggleId, Toggle>,
+        variants: &Variants,
+    ) -> Result<Vec<Event>, ToggleError> {
+        if target.id != self.id || i32::from(target.generation) >= i32::from(self.generation) {
+            return Err(ToggleError::InvalidRevert {
+                generation: target.generation.into(),
+            });
+        }
+        let mut environment_ids: Vec<EnvironmentId> = match environment_id {
+            Some(environment_id) => vec![environment_id],
+            None => self
+                .environments
+                .keys()
+                .chain(target.environments.keys())
+                .cloned()
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect(),
+        };
+        // The same revert always makes the same changes in the same order
+        environment_ids.sort_by_key(EnvironmentId::to_string);
+
+        let mut changes = vec![];
+        for environment_id in environment_ids {
+            let current = self.environment_state(environment_id);
+            let wanted = target.environment_state(environment_id);
+            if current.rules != wanted.rules {
+                changes.push(Event::RulesChanged {
+                    environment_id,
+                    rules: self.resolve_rules(wanted.rules, variants)?,
+                });
+            }
+            if current.rollout != wanted.rollout {
+                changes.push(match wanted.rollout {
+                    Some(percentage) => Event::RolloutChanged {
+                        environment_id,
+                        percentage,
+                    },
+                    None => Event::RolloutCleared { environment_id },
+                });
+            }
+            if current.enabled != wanted.enabled {
+                changes.push(if wanted.enabled {
+                    let environment = environments.get(&environment_id).ok_or_else(|| {
+                        ToggleError::InvalidEnvironment {
+                            environment: environment_id.to_string(),
+                        }
+                    })?;
+                    Self::check_active(environment)?;
+                    self.check_environment(environment)?;
+                    Event::Enabled { environment_id }
+                } else {
+                    Event::Disabled { environment_id }
+                });
+            }
+        }
+        let whole = environment_id.is_none();
+        if whole && self.prerequisites != target.prerequisites {
+            let prerequisites =
+                self.resolve_prerequisites(target.prerequisites.clone(), variants)?;
+            self.check_prerequisites(&prerequisites, toggles)?;
+            changes.push(Event::PrerequisitesChanged { prerequisites });
+        }
+
+        // Retired Toggles can't be changed, so are revived for the changes
+        // and retired again after them if they were retired back then
+        let retired = if whole { target.retired } else { self.retired };
+        let revive = self.retired && (!changes.is_empty() || !retired);
+        let retire = retired && (revive || !self.retired);
+        let mut events = vec![];
+        if revive {
+            events.push(Event::Revived);
+        }
+        events.extend(changes);
+        if retire {
+            events.push(Event::Retired);
+        }
+        Self::hydrate_from(Some(self.clone()), &events)?;
+        Ok(events)
+    }
+
+    fn environment_state(&self, environment_id: EnvironmentId) -> EnvironmentState {
+        self.environments
+            .get(&environment_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn retire(&self) -> Result<Vec<Event>, ToggleError> {
+        self.transition(Event::Retired)
+    }
+
+    pub fn revive(&self) -> Result<Vec<Event>, ToggleError> {
+        self.transition(Event::Revived)
+    }
+
+    /// Toggles can't be enabled in archived Environments.
+    fn check_active(environment: &Environment) -> Result<(), ToggleError> {
+        if environment.archived {
+            return Err(ToggleError::InvalidEnvironment {
+                environment: environment.id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Toggles can only be switched in Environments of their own Project.
+    fn check_environment(&self, environment: &Environment) -> Result<(), ToggleError> {
+        if environment.project_id != self.project_id {
+            return Err(ToggleError::InvalidEnvironment {
+                environment: environment.id.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Validate the event against the current state before handing
+    /// it back, so commands and `apply_event` share the same rules.
+    fn transition(&self, event: Event) -> Result<Vec<Event>, ToggleError> {
+        Self::apply_event(Some(self.clone()), &event)?;
+        Ok(vec![event])
+    }
+}
+
+impl Aggregate for Toggle {
+    type Id = ToggleId;
+    type Event = Event;
+    type Err = ToggleError;
+    const TYPE: &'static str = "toggle";
+    // Toggles are switched on and off again and again over their lifetime
+    const SNAPSHOT_INTERVAL: Option<i32> = Some(100);
+
+    fn id(&self) -> &Self::Id {
+        &self.id
+    }
+
+    fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    fn apply_event(state: Option<Self>, event: &Self::Event) -> Result<Self, Self::Err> {
+        match (&state, event) {
+            (
+                None,
+                Event::Created {
+                    id,
+                    project_id,
+                    name,
+                },
+            ) => Ok(Toggle {
+                id: *id,
+                project_id: *project_id,
+                generation: Generation::first(),
+                name: name.clone(),
+                version: 0,
+                environments: HashMap::new(),
+                prerequisites: Vec::new(),
+                schedules: Vec::new(),
+                retired: false,
+            }),
+            (Some(toggle), Event::Enabled { environment_id })
+                if !toggle.enabled(*environment_id) && !toggle.retired =>
+            {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .environments
+                    .entry(*environment_id)
+                    .or_default()
+                    .enabled = true;
+                Ok(toggle)
+            }
+            (Some(toggle), Event::Disabled { environment_id })
+                if toggle.enabled(*environment_id) && !toggle.retired =>
+            {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .environments
+                    .entry(*environment_id)
+                    .or_default()
+                    .enabled = false;
+                Ok(toggle)
+            }
+            (
+                Some(toggle),
+                Event::RolloutChanged {
+                    environment_id,
+                    percentage,
+                },
+            ) if *percentage <= 100 && !toggle.retired => {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .environments
+                    .entry(*environment_id)
+                    .or_default()
+                    .rollout = Some(*percentage);
+                Ok(toggle)
+            }
+            (Some(toggle), Event::RolloutCleared { environment_id }) if !toggle.retired => {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .environments
+                    .entry(*environment_id)
+                    .or_default()
+                    .rollout = None;
+                Ok(toggle)
+            }
+            (
+                Some(toggle),
+                Event::RulesChanged {
+                    environment_id,
+                    rules,
+                },
+            ) if !toggle.retired => {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .environments
+                    .entry(*environment_id)
+                    .or_default()
+                    .rules = rules.clone();
+                Ok(toggle)
+            }
+            (Some(toggle), Event::PrerequisitesChanged { prerequisites }) if !toggle.retired => {
+                Ok(Toggle {
+                    generation: toggle.generation.next(),
+                    prerequisites: prerequisites.clone(),
+                    ..toggle.clone()
+                })
+            }
+            (Some(toggle), Event::ChangeScheduled { schedule })
+                if !toggle.retired && toggle.schedule(schedule.id).is_none() =>
+            {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle.schedules.push(schedule.clone());
+                Ok(toggle)
+            }
+            (Some(toggle), Event::ScheduleCancelled { schedule_id })
+            | (Some(toggle), Event::ScheduleExecuted { schedule_id })
+            | (Some(toggle), Event::ScheduleFailed { schedule_id, .. })
+                if toggle.schedule(*schedule_id).is_some() =>
+            {
+                let mut toggle = toggle.clone();
+                toggle.generation = toggle.generation.next();
+                toggle
+                    .schedules
+                    .retain(|schedule| schedule.id != *schedule_id);
+                Ok(toggle)
+            }
+            (Some(toggle), Event::Retired) if !toggle.retired => Ok(Toggle {
+                generation: toggle.generation.next(),
+                retired: true,
+                ..toggle.clone()
+            }),
+            (Some(toggle), Event::Revived) if toggle.retired => Ok(Toggle {
+                generation: toggle.generation.next(),
+                retired: false,
+                ..toggle.clone()
+            }),
+            _ => Err(ToggleError::InvalidStateEvent {
+                state: format!("{:?}", state),
+                event: format!("{:?}", event),
+            }),
+        }
+    }
+}
+
+pub struct CreateToggle {
+    pub id: Uuid,
+    pub project_id: ProjectId,
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+pub struct CreateToggleHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Toggle, Err = E>,
+{
+    pub projects: &'a P,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, PE, P, E, R> CreateToggleHandler<'a, PE, P, E, R>
+where
+    P: Repository<Aggregate = Project, Err = PE>,
+    R: Repository<Aggregate = Toggle, Err = E>,
+    CreateToggleHandlerError: From<PE> + From<E>,
+{
+    pub fn handle(&mut self, command: CreateToggle) -> Result<Toggle, CreateToggleHandlerError> {
+        let project = self.projects.get(command.project_id)?;
+        project.check_active()?;
+        let toggle_id = ToggleId(command.id);
+        let events = Toggle::create(toggle_id, project.id, command.name)?;
+        let toggle = Toggle::hydrate(&events)?.expect("Toggle is not None");
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Toggle>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: toggle_id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(Generation::first(), &events)?;
+        Ok(toggle)
+    }
+}
+
+pub struct GetToggle {
+    pub project_id: ProjectId,
+    pub id: ToggleId,
+    /// The Toggle as it was then, rather than as it is now.
+    pub as_of: Option<PointInTime>,
+}
+
+pub struct GetToggleHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Toggle, Err = E>,
+{
+    pub repository: &'a R,
+}
+
+impl<'a, E, R> GetToggleHandler<'a, E, R>
+where
+    R: Repository<Aggregate = Toggle, Err = E>,
+    GetToggleHandlerError: From<E>,
+{
+    pub fn handle(&self, command: GetToggle) -> Result<Toggle, GetToggleHandlerError> {
+        let toggle = match command.as_of {
+            None => self.repository.get(command.id)?,
+            Some(PointInTime::Generation(generation)) => {
+                self.repository.get_at(command.id, generation)?
+            }
+            Some(PointInTime::Time(at)) => self.repository.get_as_of(command.id, at)?,
+        };
+        if toggle.project_id != command.project_id {
+            return Err(GetToggleHandlerError::NotFoundError);
+        }
+        Ok(toggle)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ToggleAction {
+    Enable(EnvironmentId),
+    Disable(EnvironmentId),
+    ChangeRollout(EnvironmentId, u8),
+    ChangeRules(EnvironmentId, Vec<Rule>),
+    ChangePrerequisites(Vec<Prerequisite>),
+    ScheduleChange(ScheduledChange),
+    CancelSchedule(ScheduleId),
+    ExecuteSchedule(ScheduleId),
+    /// Give up on a schedule, for the reason given.
+    FailSchedule(ScheduleId, String),
+    Retire,
+    Revive,
+    /// Back to how the Toggle was at the generation,
+    /// within only the Environment if one is given.
+    Revert(Generation, Option<EnvironmentId>),
+}
+
+pub struct UpdateToggle {
+    pub project_id: ProjectId,
+    pub id: ToggleId,
+    pub action: ToggleAction,
+    // Generation the caller last saw, if they want
+    // the update rejected when the Toggle has moved on
+    pub expected_generation: Option<Generation>,
+    pub metadata: Metadata,
+}
+
+pub struct UpdateToggleHandler<'a, EE, ER, NE, N, VE, VR, E, R>
+where
+    ER: Repository<Aggregate = Environment, Err = EE>,
+    N: VariantNames<Err = NE>,
+    VR: Repository<Aggregate = Variant, Err = VE>,
+    R: Repository<Aggregate = Toggle, Err = E>,
+{
+    pub environments: &'a ER,
+    pub names: &'a N,
+    pub variants: &'a VR,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, EE, ER, NE, N, VE, VR, E, R> UpdateToggleHandler<'a, EE, ER, NE, N, VE, VR, E, R>
+where
+    ER: Repository<Aggregate = Environment, Err = EE>,
+    N: VariantNames<Err = NE>,
+    VR: Repository<Aggregate = Variant, Err = VE>,
+    R: Repository<Aggregate = Toggle, Err = E>,
+    UpdateToggleHandlerError: From<EE> + From<NE> + From<VE> + From<E>,
+{
+    pub fn handle(&mut self, command: UpdateToggle) -> Result<Toggle, UpdateToggleHandlerError> {
+        let mut toggle = self.repository.get(command.id)?;
+        if toggle.project_id != command.project_id {
+            return Err(UpdateToggleHandlerError::NotFoundError);
+        }
+        if let Some(expected) = command.expected_generation {
+            if expected != toggle.generation() {
+                return Err(UpdateToggleHandlerError::ConcurrencyConflict {
+                    expected,
+                    actual: toggle.generation(),
+                });
+            }
+        }
+        let events = match command.action {
+            ToggleAction::Enable(environment_id) => {
+                toggle.enable(&self.environments.get(environment_id)?)?
+            }
+            ToggleAction::Disable(environment_id) => {
+                toggle.disable(&self.environments.get(environment_id)?)?
+            }
+            ToggleAction::ChangeRollout(environment_id, percentage) => {
+                toggle.change_rollout(&self.environments.get(environment_id)?, percentage)?
+            }
+            ToggleAction::ChangeRules(environment_id, rules) => {
+                let environment = self.environments.get(environment_id)?;
+                let variants = self.variants_of(Some(toggle.id))?;
+                toggle.change_rules(&environment, rules, &variants)?
+            }
+            ToggleAction::ChangePrerequisites(prerequisites) => {
+                let toggles = self.prerequisite_toggles(&toggle, &prerequisites)?;
+                let variants = self.variants_of(prerequisites.iter().map(|p| p.toggle_id))?;
+                toggle.change_prerequisites(prerequisites, &toggles, &variants)?
+            }
+            ToggleAction::ScheduleChange(schedule) => {
+                let environment = self.environments.get(schedule.environment_id)?;
+                toggle.schedule_change(&environment, schedule, (self.utc_now)())?
+            }
+            ToggleAction::CancelSchedule(schedule_id) => toggle.cancel_schedule(schedule_id)?,
+            ToggleAction::ExecuteSchedule(schedule_id) => {
+                let environment_id = toggle
+                    .schedule(schedule_id)
+                    .map(|schedule| schedule.environment_id)
+                    .ok_or_else(|| ToggleError::InvalidSchedule {
+                        schedule: schedule_id.to_string(),
+                    })?;
+                let environment = self.environments.get(environment_id)?;
+                toggle.execute_schedule(&environment, schedule_id, (self.utc_now)())?
+            }
+            ToggleAction::FailSchedule(schedule_id, reason) => {
+                toggle.fail_schedule(schedule_id, reason)?
+            }
+            ToggleAction::Retire => toggle.retire()?,
+            ToggleAction::Revive => toggle.revive()?,
+            ToggleAction::Revert(generation, environment_id) => {
+                let target = self.repository.get_at(toggle.id, generation)?;
+                let toggles = self.prerequisite_toggles(&toggle, &target.prerequisites)?;
+                let toggle_ids = target.prerequisites.iter().map(|p| p.toggle_id);
+                let variants = self.variants_of(toggle_ids.chain(Some(toggle.id)))?;
+                let mut environments = HashMap::new();
+                for (id, state) in target.environments() {
+                    if state.enabled && environment_id.map_or(true, |only| only == *id) {
+                        environments.insert(*id, self.environments.get(*id)?);
+                    }
+                }
+                toggle.revert(&target, environment_id, &environments, &toggles, &variants)?
+            }
+        };
+        let generation = toggle.generation().next();
+        for event in &events {
+            toggle = Toggle::apply_event(Some(toggle), event)?;
+        }
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Toggle>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: toggle.id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(generation, &events)?;
+        Ok(toggle)
+    }
+
+    /// Every Variant of the Toggles, retired ones included.
+    fn variants_of<I>(&self, toggle_ids: I) -> Result<Variants, UpdateToggleHandlerError>
+    where
+        I: IntoIterator<Item = ToggleId>,
+    {
+        let mut variants = Variants::new();
+        for toggle_id in toggle_ids {
+            for id in self.names.list(toggle_id)? {
+                variants.insert(id, self.variants.get(id)?);
+            }
+        }
+        Ok(variants)
+    }
+
+    /// Every Toggle reachable through the prerequisites, except the Toggle itself.
+    fn prerequisite_toggles(
+        &self,
+        toggle: &Toggle,
+        prerequisites: &[Prerequisite],
+    ) -> Result<HashMap<ToggleId, Toggle>, UpdateToggleHandlerError> {
+        let mut toggles = HashMap::new();
+        let mut pending: Vec<ToggleId> = prerequisites.iter().map(|p| p.toggle_id).collect();
+        while let Some(id) = pending.pop() {
+            if id == toggle.id || toggles.contains_key(&id) {
+                continue;
+            }
+            let prerequisite = match self.repository.get(id).map_err(From::from) {
+                // A Toggle that doesn't exist is an invalid prerequisite,
+                // not a reason to say the Toggle being changed is missing
+                Err(UpdateToggleHandlerError::RepositoryError(EventStoreError::NotFoundError)) => {
+                    return Err(ToggleError::InvalidPrerequisite {
+                        toggle: id.to_string(),
+                    }
+                    .into());
+                }
+                result => result?,
+            };
+            pending.extend(prerequisite.prerequisites.iter().map(|p| p.toggle_id));
+            toggles.insert(id, prerequisite);
+        }
+        Ok(toggles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use chrono::offset::TimeZone;
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::environment::{Environment, EnvironmentEvent, EnvironmentId};
+    use crate::project::ProjectId;
+    use crate::variant::{DieselVariantNames, Variant, VariantEvent, VariantId, Variants};
+
+    use crate::event_store::DieselEventStore;
+
+    use super::error::UpdateToggleHandlerError;
+    use super::rule::{Clause, Operator, Rule};
+    use super::schedule::{Change, ScheduleId, ScheduledChange};
+    use super::{
+        Event, Prerequisite, Toggle, ToggleAction, ToggleError, ToggleId, UpdateToggle,
+        UpdateToggleHandler,
+    };
+
+    #[test]
+    fn test_create() -> Result<(), Error> {
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = Toggle::create(id, project_id, "test".to_owned())?;
+        assert_eq!(
+            events,
+            vec![Event::Created {
+                id,
+                project_id,
+                name: "test".to_owned()
+            },],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_empty_name() -> Result<(), Error> {
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        assert_eq!(
+            Toggle::create(id, project_id, " ".to_owned()),
+            Err(ToggleError::InvalidName {
+                name: " ".to_owned()
+            }),
+        );
+        Ok(())
+    }
+
+    fn created() -> Result<Toggle, Error> {
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = Toggle::create(id, project_id, "test".to_owned())?;
+        Ok(Toggle::hydrate(&events)?.expect("Toggle is not None"))
+    }
+
+    fn environment() -> Result<Environment, Error> {
+        let id = EnvironmentId::from(Uuid::parse_str("6ba7b810-9dad-11d1-80b4-00c04fd430c8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = Environment::create(id, project_id, "staging".to_owned())?;
+        Ok(Environment::hydrate(&events)?.expect("Environment is not None"))
+    }
+
+    #[test]
+    fn test_enable() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = environment()?;
+        assert_eq!(
+            toggle.enable(&environment)?,
+            vec![Event::Enabled {
+                environment_id: environment.id
+            }],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_per_environment() -> Result<(), Error> {
+        let environment = environment()?;
+        let other = EnvironmentId::from(Uuid::new_v4());
+        let toggle = Toggle::apply_event(
+            Some(created()?),
+            &Event::Enabled {
+                environment_id: environment.id,
+            },
+        )?;
+        assert!(toggle.enabled(environment.id));
+        assert!(!toggle.enabled(other));
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_other_project() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = Environment {
+            project_id: "6ba7b811-9dad-11d1-80b4-00c04fd430c8".parse()?,
+            ..environment()?
+        };
+        match toggle.enable(&environment) {
+            Err(ToggleError::InvalidEnvironment { .. }) => Ok(()),
+            result => panic!("expected invalid environment, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_enable_archived_environment() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment =
+            Environment::apply_event(Some(environment()?), &EnvironmentEvent::Archived)?;
+        match toggle.enable(&environment) {
+            Err(ToggleError::InvalidEnvironment { .. }) => Ok(()),
+            result => panic!("expected invalid environment, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_change_rollout() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = environment()?;
+        assert_eq!(
+            toggle.change_rollout(&environment, 101),
+            Err(ToggleError::InvalidRollout { percentage: 101 }),
+        );
+        let events = toggle.change_rollout(&environment, 20)?;
+        let toggle = Toggle::apply_event(Some(toggle), &events[0])?;
+        assert_eq!(toggle.environments()[&environment.id].rollout, Some(20));
+        assert!(!toggle.enabled(environment.id));
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_prerequisites() -> Result<(), Error> {
+        let first = created()?;
+        let second = Toggle::hydrate(&Toggle::create(
+            ToggleId(Uuid::new_v4()),
+            first.project_id(),
+            "second".to_owned(),
+        )?)?
+        .expect("Toggle is not None");
+        let on = |toggle: &Toggle| Prerequisite {
+            toggle_id: toggle.id,
+            variant: "on".to_owned(),
+            variant_id: None,
+        };
+
+        let toggles = vec![(first.id, first.clone())].into_iter().collect();
+        let events = second.change_prerequisites(vec![on(&first)], &toggles, &HashMap::new())?;
+        let second = Toggle::apply_event(Some(second), &events[0])?;
+        assert_eq!(second.prerequisites(), &[on(&first)][..]);
+
+        let toggles = vec![(second.id, second.clone())].into_iter().collect();
+        assert_eq!(
+            first.change_prerequisites(vec![on(&second)], &toggles, &HashMap::new()),
+            Err(ToggleError::PrerequisiteCycle {
+                toggle: first.id.to_string(),
+            }),
+        );
+        assert_eq!(
+            first.change_prerequisites(vec![on(&first)], &HashMap::new(), &HashMap::new()),
+            Err(ToggleError::PrerequisiteCycle {
+                toggle: first.id.to_string(),
+            }),
+        );
+        assert_eq!(
+            first.change_prerequisites(vec![on(&second)], &HashMap::new(), &HashMap::new()),
+            Err(ToggleError::InvalidPrerequisite {
+                toggle: second.id.to_string(),
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_change_rules_variants() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = environment()?;
+        let other = Toggle::hydrate(&Toggle::create(
+            ToggleId(Uuid::new_v4()),
+            toggle.project_id(),
+            "other".to_owned(),
+        )?)?
+        .expect("Toggle is not None");
+        let variant = |toggle: &Toggle, name: &str| -> Result<Variant, Error> {
+            let id = VariantId::from(Uuid::new_v4());
+            let events = Variant::create(id, toggle.id, name.to_owned())?;
+            Ok(Variant::hydrate(&events)?.expect("Variant is not None"))
+        };
+        let blue = variant(&toggle, "blue")?;
+        let green = Variant::apply_event(Some(variant(&toggle, "green")?), &VariantEvent::Retired)?;
+        let red = variant(&other, "red")?;
+        let variants: Variants = vec![&blue, &green, &red]
+            .into_iter()
+            .map(|variant| (variant.id, variant.clone()))
+            .collect();
+        let rule = |variant: &str| Rule {
+            clauses: vec![Clause {
+                attribute: "country".to_owned(),
+                operator: Operator::In,
+                values: vec!["GB".to_owned()],
+            }],
+            variant: variant.to_owned(),
+            variant_id: None,
+        };
+
+        // Referred to by id from then on
+        let events =
+            toggle.change_rules(&environment, vec![rule("blue"), rule("off")], &variants)?;
+        assert_eq!(
+            events,
+            vec![Event::RulesChanged {
+                environment_id: environment.id,
+                rules: vec![
+                    Rule {
+                        variant_id: Some(blue.id),
+                        ..rule("blue")
+                    },
+                    rule("off"),
+                ],
+            }],
+        );
+        for name in &["purple", "green", "red"] {
+            match toggle.change_rules(&environment, vec![rule(name)], &variants) {
+                Err(ToggleError::InvalidRule { .. }) => {}
+                result => panic!("expected invalid rule, got {:?}", result),
+            }
+        }
+
+        let toggles = vec![(other.id, other.clone())].into_iter().collect();
+        let requires = |variant: &str| Prerequisite {
+            toggle_id: other.id,
+            variant: variant.to_owned(),
+            variant_id: None,
+        };
+        let events = toggle.change_prerequisites(vec![requires("red")], &toggles, &variants)?;
+        assert_eq!(
+            events,
+            vec![Event::PrerequisitesChanged {
+                prerequisites: vec![Prerequisite {
+                    variant_id: Some(red.id),
+                    ..requires("red")
+                }],
+            }],
+        );
+        assert_eq!(
+            toggle.change_prerequisites(vec![requires("blue")], &toggles, &variants),
+            Err(ToggleError::InvalidPrerequisite {
+                toggle: other.id.to_string(),
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scheduled_change() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = environment()?;
+        let now = Utc.ymd(2019, 6, 1).and_hms(0, 0, 0);
+        let schedule = ScheduledChange {
+            id: ScheduleId::from(Uuid::new_v4()),
+            environment_id: environment.id,
+            at: Utc.ymd(2019, 6, 1).and_hms(3, 0, 0),
+            change: Change::Enable,
+        };
+        assert_eq!(
+            toggle.schedule_change(
+                &environment,
+                ScheduledChange {
+                    at: now,
+                    ..schedule.clone()
+                },
+                now
+            ),
+            Err(ToggleError::InvalidSchedule {
+                schedule: schedule.id.to_string(),
+            }),
+        );
+
+        let events = toggle.schedule_change(&environment, schedule.clone(), now)?;
+        let toggle = Toggle::apply_event(Some(toggle), &events[0])?;
+        assert_eq!(toggle.schedules(), &[schedule.clone()][..]);
+        assert!(toggle
+            .execute_schedule(&environment, schedule.id, now)
+            .is_err());
+
+        let events = toggle.execute_schedule(&environment, schedule.id, schedule.at)?;
+        assert_eq!(
+            events,
+            vec![
+                Event::Enabled {
+                    environment_id: environment.id,
+                },
+                Event::ScheduleExecuted {
+                    schedule_id: schedule.id,
+                },
+            ],
+        );
+        let mut toggle = Some(toggle);
+        for event in &events {
+            toggle = Some(Toggle::apply_event(toggle, event)?);
+        }
+        let toggle = toggle.expect("Toggle is not None");
+        assert!(toggle.enabled(environment.id));
+        assert!(toggle.schedules().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_scheduled_change() -> Result<(), Error> {
+        let toggle = created()?;
+        let environment = environment()?;
+        let schedule = ScheduledChange {
+            id: ScheduleId::from(Uuid::new_v4()),
+            environment_id: environment.id,
+            at: Utc.ymd(2019, 6, 1).and_hms(3, 0, 0),
+            change: Change::Enable,
+        };
+        let mut toggle = Some(toggle);
+        for event in &[
+            Event::ChangeScheduled {
+                schedule: schedule.clone(),
+            },
+            Event::Retired,
+        ] {
+            toggle = Some(Toggle::apply_event(toggle, event)?);
+        }
+        let toggle = toggle.expect("Toggle is not None");
+
+        // Left pending rather than dropped without a trace
+        assert!(toggle
+            .execute_schedule(&environment, schedule.id, schedule.at)
+            .is_err());
+        let events = toggle.fail_schedule(schedule.id, "retired".to_owned())?;
+        assert_eq!(
+            events,
+            vec![Event::ScheduleFailed {
+                schedule_id: schedule.id,
+                reason: "retired".to_owned(),
+            }],
+        );
+        let toggle = Toggle::apply_event(Some(toggle), &events[0])?;
+        assert!(toggle.schedules().is_empty());
+        assert!(toggle
+            .fail_schedule(schedule.id, "retired".to_owned())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_enable_retired() -> Result<(), Error> {
+        let toggle = Toggle::apply_event(Some(created()?), &Event::Retired)?;
+        match toggle.enable(&environment()?) {
+            Err(ToggleError::InvalidStateEvent { .. }) => Ok(()),
+            result => panic!("expected invalid state event, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_disable_disabled() -> Result<(), Error> {
+        let toggle = created()?;
+        match toggle.disable(&environment()?) {
+            Err(ToggleError::InvalidStateEvent { .. }) => Ok(()),
+            result => panic!("expected invalid state event, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_hydrate_lifecycle() -> Result<(), Error> {
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let environment_id = EnvironmentId::from(Uuid::new_v4());
+        let toggle = Toggle::hydrate(&[
+            Event::Created {
+                id,
+                project_id,
+                name: "test".to_owned(),
+            },
+            Event::Enabled { environment_id },
+            Event::Retired,
+            Event::Revived,
+            Event::Disabled { environment_id },
+        ])?
+        .expect("Toggle is not None");
+        assert_eq!(
+            toggle.generation(),
+            Generation::first().next().next().next().next()
+        );
+        assert!(!toggle.enabled(environment_id));
+        assert!(!toggle.retired());
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert() -> Result<(), Error> {
+        let environment = environment()?;
+        let other = Environment {
+            id: EnvironmentId::from(Uuid::new_v4()),
+            ..environment.clone()
+        };
+        let environments: HashMap<_, _> = vec![
+            (environment.id, environment.clone()),
+            (other.id, other.clone()),
+        ]
+        .into_iter()
+        .collect();
+        let target = Toggle::hydrate_from(
+            Some(created()?),
+            &[
+                Event::Enabled {
+                    environment_id: environment.id,
+                },
+                Event::Enabled {
+                    environment_id: other.id,
+                },
+            ],
+        )?
+        .expect("Toggle is not None");
+        let toggle = Toggle::hydrate_from(
+            Some(target.clone()),
+            &[
+                Event::RolloutChanged {
+                    environment_id: environment.id,
+                    percentage: 10,
+                },
+                Event::Disabled {
+                    environment_id: environment.id,
+                },
+                Event::Disabled {
+                    environment_id: other.id,
+                },
+                Event::Retired,
+            ],
+        )?
+        .expect("Toggle is not None");
+
+        let events = toggle.revert(
+            &target,
+            Some(environment.id),
+            &environments,
+            &HashMap::new(),
+            &HashMap::new(),
+        )?;
+        assert_eq!(
+            events,
+            vec![
+                Event::Revived,
+                Event::RolloutCleared {
+                    environment_id: environment.id,
+                },
+                Event::Enabled {
+                    environment_id: environment.id,
+                },
+                Event::Retired,
+            ],
+        );
+
+        let events = toggle.revert(
+            &target,
+            None,
+            &environments,
+            &HashMap::new(),
+            &HashMap::new(),
+        )?;
+        let reverted = Toggle::hydrate_from(Some(toggle.clone()), &events)?;
+        let reverted = reverted.expect("Toggle is not None");
+        assert!(!reverted.retired());
+        assert!(reverted.enabled(environment.id));
+        assert!(reverted.enabled(other.id));
+        assert_eq!(reverted.environments()[&environment.id].rollout, None);
+
+        // Not enabled again in an Environment archived since
+        let mut archived = environments.clone();
+        archived.insert(
+            other.id,
+            Environment {
+                archived: true,
+                ..other.clone()
+            },
+        );
+        assert_eq!(
+            toggle.revert(&target, None, &archived, &HashMap::new(), &HashMap::new()),
+            Err(ToggleError::InvalidEnvironment {
+                environment: other.id.to_string(),
+            }),
+        );
+
+        assert_eq!(
+            target.revert(
+                &toggle,
+                None,
+                &environments,
+                &HashMap::new(),
+                &HashMap::new()
+            ),
+            Err(ToggleError::InvalidRevert {
+                generation: toggle.generation().into(),
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repository() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let mut repository = DieselEventStore::<_, Toggle>::new(db);
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let events = vec![
+            Event::Created {
+                id,
+                project_id,
+                name: "test".to_owned(),
+            },
+            Event::Enabled {
+                environment_id: EnvironmentId::from(Uuid::new_v4()),
+            },
+        ];
+
+        repository.persist(
+            Generation::first(),
+            &events
+                .iter()
+                .map(|event| DomainEvent {
+                    id: DomainEventId::new(Uuid::new_v4()),
+                    aggregate_id: id,
+                    created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                    metadata: Metadata::default(),
+                    event: event.clone(),
+                })
+                .collect::<Vec<_>>(),
+        )?;
+
+        assert_eq!(
+            repository.get(id)?,
+            Toggle::hydrate(&events)?.expect("Toggle is not None"),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_stale_generation() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let repository = &mut DieselEventStore::<_, Toggle>::new(db);
+        let id = ToggleId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        repository.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: id,
+                created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                metadata: Metadata::default(),
+                event: Event::Created {
+                    id,
+                    project_id,
+                    name: "test".to_owned(),
+                },
+            }],
+        )?;
+        let environment = environment()?;
+        let environments = &mut DieselEventStore::<_, Environment>::new(db);
+        environments.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: environment.id,
+                created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                metadata: Metadata::default(),
+                event: EnvironmentEvent::Created {
+                    id: environment.id,
+                    project_id: environment.project_id,
+                    name: environment.name.clone(),
+                },
+            }],
+        )?;
+        let handler = &mut UpdateToggleHandler {
+            environments,
+            names: &DieselVariantNames { db },
+            variants: &DieselEventStore::<_, Variant>::new(db),
+            repository,
+            utc_now: Utc::now,
+        };
+
+        let toggle = handler.handle(UpdateToggle {
+            project_id,
+            id,
+            action: ToggleAction::Enable(environment.id),
+            expected_generation: Some(Generation::first()),
+            metadata: Metadata::default(),
+        })?;
+        assert!(toggle.enabled(environment.id));
+
+        match handler.handle(UpdateToggle {
+            project_id,
+            id,
+            action: ToggleAction::Disable(environment.id),
+            expected_generation: Some(Generation::first()),
+            metadata: Metadata::default(),
+        }) {
+            Err(UpdateToggleHandlerError::ConcurrencyConflict { expected, actual }) => {
+                assert_eq!(expected, Generation::first());
+                assert_eq!(actual, Generation::first().next());
+                Ok(())
+            }
+            result => panic!("expected concurrency conflict, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::cmp::Ordering;
+
+use chrono::{DateTime, FixedOffset};
+use regex::Regex;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::segment::SegmentId;
+use crate::variant::VariantId;
+
+/// Selects a variant for every context that matches all of its clauses.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Rule {
+    pub clauses: Vec<Clause>,
+    // Name of the variant as of when the Rule was last changed
+    pub variant: String,
+    // The Variant the Rule refers to, which keeps referring to it when it is
+    // renamed, and which the built-in variants and older Rules don't have
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant_id: Option<VariantId>,
+}
+
+/// A test of a single context attribute, which passes
+/// when the attribute matches any of the values.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Clause {
+    pub attribute: String,
+    pub operator: Operator,
+    pub values: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Operator {
+    In,
+    NotIn,
+    StartsWith,
+    EndsWith,
+    Matches,
+    Semver(Comparison),
+    Number(Comparison),
+    Before,
+    After,
+    // Values are ids of Segments the attribute, as a key, is in
+    InSegment,
+    NotInSegment,
+}
+
+/// How an attribute has to compare to a value, as in `attribute < value`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Comparison {
+    LessThan,
+    LessThanOrEqual,
+    Equal,
+    GreaterThanOrEqual,
+    GreaterThan,
+}
+
+impl Comparison {
+    fn holds(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::LessThan => ordering == Ordering::Less,
+            Comparison::LessThanOrEqual => ordering != Ordering::Greater,
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::GreaterThanOrEqual => ordering != Ordering::Less,
+            Comparison::GreaterThan => ordering == Ordering::Greater,
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<f64, String> {
+    s.parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .ok_or_else(|| format!("invalid number `{}`", s))
+}
+
+fn parse_date(s: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(s).map_err(|_| format!("invalid date `{}`", s))
+}
+
+fn parse_version(s: &str) -> Result<Version, String> {
+    Version::parse(s).map_err(|_| format!("invalid version `{}`", s))
+}
+
+impl Rule {
+    /// Reason the Rule can never be evaluated, if any.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.variant.trim().is_empty() {
+            return Err("rule without a variant".to_owned());
+        }
+        if self.clauses.is_empty() {
+            return Err("rule without clauses".to_owned());
+        }
+        self.clauses.iter().try_for_each(Clause::validate)
+    }
+
+    /// Ids of the Segments the Rule refers to.
+    pub fn segment_ids(&self) -> Vec<SegmentId> {
+        self.clauses
+            .iter()
+            .filter(|clause| match clause.operator {
+                Operator::InSegment | Operator::NotInSegment => true,
+                _ => false,
+            })
+            .flat_map(|clause| clause.values.iter())
+            .filter_map(|value| value.parse().ok())
+            .collect()
+    }
+}
+
+impl Clause {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.values.is_empty() {
+            return Err(format!("clause on `{}` without values", self.attribute));
+        }
+        for value in &self.values {
+            match self.operator {
+                Operator::Matches => {
+                    Regex::new(value).map_err(|_| format!("invalid pattern `{}`", value))?;
+                }
+                Operator::Semver(_) => {
+                    parse_version(value)?;
+                }
+                Operator::Number(_) => {
+                    parse_number(value)?;
+                }
+                Operator::Before | Operator::After => {
+                    parse_date(value)?;
+                }
+                Operator::InSegment | Operator::NotInSegment => {
+                    value
+                        .parse::<SegmentId>()
+                        .map_err(|_| format!("invalid segment `{}`", value))?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an attribute value passes the Clause. Attribute values
+    /// that can't be parsed for the operator never pass. Segment
+    /// membership depends on more than the attribute, so it is left
+    /// to evaluation and segment operators never pass here.
+    pub fn matches(&self, attribute: &str) -> bool {
+        let values = &mut self.values.iter();
+        match self.operator {
+            Operator::In => values.any(|value| value == attribute),
+            Operator::NotIn => values.all(|value| value != attribute),
+            Operator::StartsWith => values.any(|value| attribute.starts_with(value.as_str())),
+            Operator::EndsWith => values.any(|value| attribute.ends_with(value.as_str())),
+            Operator::Matches => values.any(|value| {
+                Regex::new(value)
+                    .map(|pattern| pattern.is_match(attribute))
+                    .unwrap_or(false)
+            }),
+            Operator::Semver(comparison) => parse_version(attribute)
+                .map(|attribute| {
+                    values.any(|value| {
+                        parse_version(value)
+                            .map(|value| comparison.holds(attribute.cmp(&value)))
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false),
+            Operator::Number(comparison) => parse_number(attribute)
+                .map(|attribute| {
+                    values.any(|value| {
+                        parse_number(value)
+                            .ok()
+                            .and_then(|value| attribute.partial_cmp(&value))
+                            .map_or(false, |ordering| comparison.holds(ordering))
+                    })
+                })
+                .unwrap_or(false),
+            Operator::Before | Operator::After => parse_date(attribute)
+                .map(|attribute| {
+                    values.any(|value| {
+                        parse_date(value)
+                            .map(|value| match self.operator {
+                                Operator::Before => attribute < value,
+                                _ => attribute > value,
+                            })
+                            .unwrap_or(false)
+                    })
+                })
+                .unwrap_or(false),
+            Operator::InSegment | Operator::NotInSegment => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Clause, Comparison, Operator, Rule};
+
+    fn clause(operator: Operator, values: &[&str]) -> Clause {
+        Clause {
+            attribute: "attribute".to_owned(),
+            operator,
+            values: values.iter().map(|value| (*value).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_string_operators() {
+        assert!(clause(Operator::In, &["GB", "IE"]).matches("IE"));
+        assert!(!clause(Operator::In, &["GB", "IE"]).matches("FR"));
+        assert!(clause(Operator::NotIn, &["GB", "IE"]).matches("FR"));
+        assert!(!clause(Operator::NotIn, &["GB", "IE"]).matches("GB"));
+        assert!(clause(Operator::StartsWith, &["admin"]).matches("admin@ourcorp.com"));
+        assert!(clause(Operator::EndsWith, &["@ourcorp.com"]).matches("me@ourcorp.com"));
+        assert!(!clause(Operator::EndsWith, &["@ourcorp.com"]).matches("me@gmail.com"));
+        assert!(clause(Operator::Matches, &["^[0-9]+$"]).matches("123"));
+        assert!(!clause(Operator::Matches, &["^[0-9]+$"]).matches("12a"));
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let semver = |comparison| clause(Operator::Semver(comparison), &["2.3.0"]);
+        assert!(semver(Comparison::GreaterThanOrEqual).matches("2.3.0"));
+        assert!(semver(Comparison::GreaterThanOrEqual).matches("2.10.0"));
+        assert!(!semver(Comparison::GreaterThanOrEqual).matches("2.2.9"));
+        assert!(!semver(Comparison::LessThan).matches("not a version"));
+
+        let number = |comparison| clause(Operator::Number(comparison), &["10"]);
+        assert!(number(Comparison::LessThan).matches("9.5"));
+        assert!(!number(Comparison::LessThan).matches("10"));
+        assert!(number(Comparison::Equal).matches("10.0"));
+
+        let date = |operator| clause(operator, &["2019-06-01T00:00:00Z"]);
+        assert!(date(Operator::Before).matches("2019-05-31T23:59:59Z"));
+        assert!(date(Operator::After).matches("2019-06-01T01:00:00+00:00"));
+        assert!(!date(Operator::After).matches("2019-06-01"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let rule = |clause| Rule {
+            clauses: vec![clause],
+            variant: "on".to_owned(),
+            variant_id: None,
+        };
+        assert!(rule(clause(Operator::In, &["GB"])).validate().is_ok());
+        assert!(rule(clause(Operator::In, &[])).validate().is_err());
+        assert!(rule(clause(Operator::Matches, &["("])).validate().is_err());
+        assert!(rule(clause(Operator::Semver(Comparison::Equal), &["2"]))
+            .validate()
+            .is_err());
+        assert!(rule(clause(Operator::Before, &["yesterday"]))
+            .validate()
+            .is_err());
+    }
+}
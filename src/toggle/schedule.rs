@@ -0,0 +1,263 @@
+use chrono::{DateTime, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models::{self, NewScheduledChange};
+use crate::database::schema;
+use crate::domain::DomainEvent;
+use crate::environment::EnvironmentId;
+use crate::project::ProjectId;
+use crate::projection::error::ProjectionError;
+use crate::projection::Projection;
+
+use super::{Event, Toggle, ToggleId};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct ScheduleId(Uuid);
+
+impl ScheduleId {
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl From<ScheduleId> for Uuid {
+    fn from(id: ScheduleId) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for ScheduleId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+/// What to do to a Toggle once a ScheduledChange is due.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Change {
+    Enable,
+    Disable,
+    ChangeRollout(u8),
+}
+
+/// A Change to a Toggle within an Environment, to be made at a later time.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ScheduledChange {
+    pub id: ScheduleId,
+    pub environment_id: EnvironmentId,
+    pub at: DateTime<Utc>,
+    pub change: Change,
+}
+
+/// Lookup of pending ScheduledChanges across all Toggles by when they are
+/// due, since the event store can only load Toggles one at a time.
+pub trait Schedules {
+    type Err;
+
+    fn due(&self, now: DateTime<Utc>) -> Result<Vec<(ProjectId, ToggleId, ScheduleId)>, Self::Err>;
+}
+
+pub struct DieselSchedules<'a, C> {
+    pub db: &'a C,
+}
+
+fn parse_uuid(id: &str) -> Result<Uuid, diesel::result::Error> {
+    Uuid::parse_str(id).map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+}
+
+/// Implements the schedules read model for a kind of diesel connection.
+macro_rules! impl_schedules {
+    ($connection:ty) => {
+        impl<'a> Schedules for DieselSchedules<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn due(
+                &self,
+                now: DateTime<Utc>,
+            ) -> Result<Vec<(ProjectId, ToggleId, ScheduleId)>, Self::Err> {
+                use crate::database::schema::scheduled_changes::dsl;
+
+                dsl::scheduled_changes
+                    .filter(dsl::at.le(now.timestamp()))
+                    .order(dsl::at.asc())
+                    .load::<models::ScheduledChange>(self.db)?
+                    .iter()
+                    .map(|row| {
+                        Ok((
+                            ProjectId::from(parse_uuid(&row.project_id)?),
+                            ToggleId::from(parse_uuid(&row.toggle_id)?),
+                            ScheduleId::from(parse_uuid(&row.id)?),
+                        ))
+                    })
+                    .collect()
+            }
+        }
+
+        impl<'a> DieselSchedules<'a, $connection> {
+            /// Project of a Toggle, which only the event creating it says.
+            fn project_id(&self, toggle_id: &str) -> Result<Option<String>, ProjectionError> {
+                use crate::database::schema::events::dsl;
+
+                let created = dsl::events
+                    .filter(dsl::aggregate_id.eq(toggle_id))
+                    .filter(dsl::generation.eq(0))
+                    .first::<models::Event>(self.db)?;
+                Ok(match created.decode::<Toggle>()? {
+                    Some(DomainEvent {
+                        event: Event::Created { project_id, .. },
+                        ..
+                    }) => Some(project_id.to_string()),
+                    _ => None,
+                })
+            }
+
+            fn remove(&self, id: ScheduleId) -> Result<(), ProjectionError> {
+                use crate::database::schema::scheduled_changes::dsl;
+
+                diesel::delete(dsl::scheduled_changes.filter(dsl::id.eq(id.to_string())))
+                    .execute(self.db)?;
+                Ok(())
+            }
+        }
+
+        impl<'a> Projection for DieselSchedules<'a, $connection> {
+            fn name(&self) -> &'static str {
+                "schedules"
+            }
+
+            fn apply(&mut self, event: &models::Event) -> Result<(), ProjectionError> {
+                let event = match event.decode::<Toggle>()? {
+                    Some(event) => event,
+                    None => return Ok(()),
+                };
+                match event.event {
+                    Event::ChangeScheduled { schedule } => {
+                        let toggle_id = event.aggregate_id.to_string();
+                        let project_id = match self.project_id(&toggle_id)? {
+                            Some(project_id) => project_id,
+                            None => return Ok(()),
+                        };
+                        // Replaced, for changes indexed before schedules were projected
+                        self.remove(schedule.id)?;
+                        diesel::insert_into(schema::scheduled_changes::table)
+                            .values(&NewScheduledChange {
+                                id: &schedule.id.to_string(),
+                                project_id: &project_id,
+                                toggle_id: &toggle_id,
+                                // Rounded up, so the change is never found before it is due
+                                at: schedule.at.timestamp()
+                                    + i64::from(schedule.at.timestamp_subsec_nanos() > 0),
+                            })
+                            .execute(self.db)?;
+                    }
+                    Event::ScheduleCancelled { schedule_id }
+                    | Event::ScheduleExecuted { schedule_id }
+                    | Event::ScheduleFailed { schedule_id, .. } => self.remove(schedule_id)?,
+                    _ => {}
+                }
+                Ok(())
+            }
+
+            fn reset(&mut self) -> Result<(), ProjectionError> {
+                diesel::delete(schema::scheduled_changes::table).execute(self.db)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_schedules!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_schedules!(diesel::pg::PgConnection);
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::{DateTime, Utc};
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::environment::EnvironmentId;
+    use crate::event_store::DieselEventStore;
+    use crate::project::ProjectId;
+    use crate::projection::Projector;
+
+    use super::super::{Event, Toggle, ToggleId};
+    use super::{Change, DieselSchedules, ScheduleId, ScheduledChange, Schedules};
+
+    #[test]
+    fn test_due() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let repository = &mut DieselEventStore::<_, Toggle>::new(db);
+        let schedules = &mut DieselSchedules { db };
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let toggle_id = ToggleId::from(Uuid::new_v4());
+        let schedule = |at| ScheduledChange {
+            id: ScheduleId::from(Uuid::new_v4()),
+            environment_id: EnvironmentId::from(Uuid::new_v4()),
+            at,
+            change: Change::Disable,
+        };
+        let earlier = schedule(Utc.ymd(2019, 6, 1).and_hms_milli(3, 0, 0, 500));
+        let later = schedule(Utc.ymd(2019, 6, 2).and_hms(3, 0, 0));
+        let events = vec![
+            Event::Created {
+                id: toggle_id,
+                project_id,
+                name: "test".to_owned(),
+            },
+            Event::ChangeScheduled {
+                schedule: later.clone(),
+            },
+            Event::ChangeScheduled {
+                schedule: earlier.clone(),
+            },
+        ];
+        let event = |event: Event| DomainEvent {
+            id: DomainEventId::new(Uuid::new_v4()),
+            aggregate_id: toggle_id,
+            created_at: Utc.ymd(2019, 5, 1).and_hms(0, 0, 0),
+            metadata: Metadata::default(),
+            event,
+        };
+        let at = |at: DateTime<Utc>| -> Result<_, Error> {
+            Projector { db }.catch_up(&mut DieselSchedules { db })?;
+            Ok(DieselSchedules { db }.due(at)?)
+        };
+        repository.persist(
+            Generation::first(),
+            &events.into_iter().map(event).collect::<Vec<_>>(),
+        )?;
+
+        assert!(at(Utc.ymd(2019, 6, 1).and_hms(3, 0, 0))?.is_empty());
+        assert_eq!(
+            at(Utc.ymd(2019, 6, 1).and_hms(3, 0, 1))?,
+            vec![(project_id, toggle_id, earlier.id)],
+        );
+        repository.persist(
+            Generation::from(3),
+            &[event(Event::ScheduleExecuted {
+                schedule_id: earlier.id,
+            })],
+        )?;
+        assert_eq!(
+            at(Utc.ymd(2019, 6, 3).and_hms(0, 0, 0))?,
+            vec![(project_id, toggle_id, later.id)],
+        );
+
+        Projector { db }.rebuild(schedules)?;
+        assert_eq!(
+            schedules.due(Utc.ymd(2019, 6, 3).and_hms(0, 0, 0))?,
+            vec![(project_id, toggle_id, later.id)],
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use failure_derive::Fail;
+
+use crate::domain::Generation;
+use crate::event_store::error::EventStoreError;
+use crate::toggle::error::ToggleError;
+
+#[derive(Debug, Fail)]
+pub enum VariantIdParseError {
+    #[fail(display = "fail to parse uuid")]
+    UuidParseError(#[cause] uuid::parser::ParseError),
+}
+
+impl From<uuid::parser::ParseError> for VariantIdParseError {
+    fn from(e: uuid::parser::ParseError) -> VariantIdParseError {
+        VariantIdParseError::UuidParseError(e)
+    }
+}
+
+#[derive(Debug, Eq, Fail, PartialEq)]
+pub enum VariantError {
+    #[fail(display = "invalid variant name: {}", name)]
+    InvalidName { name: String },
+    #[fail(display = "duplicate variant name: {}", name)]
+    DuplicateName { name: String },
+    #[fail(display = "invalid event `{}` applied to state `{}", event, state)]
+    InvalidStateEvent { state: String, event: String },
+}
+
+#[derive(Debug, Fail)]
+pub enum CreateVariantHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "variant error")]
+    VariantError(#[cause] VariantError),
+    #[fail(display = "variant names error")]
+    VariantNamesError(#[cause] diesel::result::Error),
+    #[fail(display = "toggle repository error")]
+    ToggleRepositoryError(#[cause] EventStoreError<ToggleError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<VariantError>),
+}
+
+impl From<VariantError> for CreateVariantHandlerError {
+    fn from(e: VariantError) -> Self {
+        CreateVariantHandlerError::VariantError(e)
+    }
+}
+
+impl From<diesel::result::Error> for CreateVariantHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        CreateVariantHandlerError::VariantNamesError(e)
+    }
+}
+
+impl From<EventStoreError<ToggleError>> for CreateVariantHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        CreateVariantHandlerError::ToggleRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<VariantError>> for CreateVariantHandlerError {
+    fn from(e: EventStoreError<VariantError>) -> Self {
+        CreateVariantHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum GetVariantHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "toggle repository error")]
+    ToggleRepositoryError(#[cause] EventStoreError<ToggleError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<VariantError>),
+}
+
+impl From<EventStoreError<ToggleError>> for GetVariantHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        GetVariantHandlerError::ToggleRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<VariantError>> for GetVariantHandlerError {
+    fn from(e: EventStoreError<VariantError>) -> Self {
+        GetVariantHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum UpdateVariantHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(
+        display = "concurrency conflict: expected generation {:?}, actual generation {:?}",
+        expected, actual
+    )]
+    ConcurrencyConflict {
+        expected: Generation,
+        actual: Generation,
+    },
+    #[fail(display = "variant error")]
+    VariantError(#[cause] VariantError),
+    #[fail(display = "variant names error")]
+    VariantNamesError(#[cause] diesel::result::Error),
+    #[fail(display = "toggle repository error")]
+    ToggleRepositoryError(#[cause] EventStoreError<ToggleError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<VariantError>),
+}
+
+impl From<VariantError> for UpdateVariantHandlerError {
+    fn from(e: VariantError) -> Self {
+        UpdateVariantHandlerError::VariantError(e)
+    }
+}
+
+impl From<diesel::result::Error> for UpdateVariantHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        UpdateVariantHandlerError::VariantNamesError(e)
+    }
+}
+
+impl From<EventStoreError<ToggleError>> for UpdateVariantHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        UpdateVariantHandlerError::ToggleRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<VariantError>> for UpdateVariantHandlerError {
+    fn from(e: EventStoreError<VariantError>) -> Self {
+        UpdateVariantHandlerError::RepositoryError(e)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum ListVariantsHandlerError {
+    #[fail(display = "not found error")]
+    NotFoundError,
+    #[fail(display = "variant names error")]
+    VariantNamesError(#[cause] diesel::result::Error),
+    #[fail(display = "toggle repository error")]
+    ToggleRepositoryError(#[cause] EventStoreError<ToggleError>),
+    #[fail(display = "repository error")]
+    RepositoryError(#[cause] EventStoreError<VariantError>),
+}
+
+impl From<diesel::result::Error> for ListVariantsHandlerError {
+    fn from(e: diesel::result::Error) -> Self {
+        ListVariantsHandlerError::VariantNamesError(e)
+    }
+}
+
+impl From<EventStoreError<ToggleError>> for ListVariantsHandlerError {
+    fn from(e: EventStoreError<ToggleError>) -> Self {
+        ListVariantsHandlerError::ToggleRepositoryError(e)
+    }
+}
+
+impl From<EventStoreError<VariantError>> for ListVariantsHandlerError {
+    fn from(e: EventStoreError<VariantError>) -> Self {
+        ListVariantsHandlerError::RepositoryError(e)
+    }
+}
@@ -0,0 +1,641 @@
+pub mod error;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use diesel;
+use diesel::prelude::*;
+use diesel::sqlite::SqliteConnection;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::database::models::NewVariantName;
+use crate::database::schema;
+use crate::domain::{
+    Aggregate, AggregateEvent, DomainEvent, DomainEventId, Generation, Metadata, Repository,
+};
+use crate::evaluation::{OFF, ON};
+use crate::project::ProjectId;
+use crate::toggle::{Toggle, ToggleId};
+
+use self::error::{
+    CreateVariantHandlerError, GetVariantHandlerError, ListVariantsHandlerError,
+    UpdateVariantHandlerError, VariantError, VariantIdParseError,
+};
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct VariantId(Uuid);
+
+impl VariantId {
+    pub fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl FromStr for VariantId {
+    type Err = VariantIdParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = Uuid::parse_str(s)?;
+        Ok(Self(id))
+    }
+}
+
+impl From<VariantId> for Uuid {
+    fn from(id: VariantId) -> Self {
+        id.0
+    }
+}
+
+impl From<Uuid> for VariantId {
+    fn from(id: Uuid) -> Self {
+        Self(id)
+    }
+}
+
+/// One of the values a multivariate Toggle can serve.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Variant {
+    pub id: VariantId,
+    pub generation: Generation,
+    pub toggle_id: ToggleId,
+    pub name: String,
+    pub retired: bool,
+}
+
+impl Variant {
+    pub fn create(
+        id: VariantId,
+        toggle_id: ToggleId,
+        name: String,
+    ) -> Result<Vec<VariantEvent>, VariantError> {
+        if name.trim().is_empty() {
+            return Err(VariantError::InvalidName { name });
+        }
+        Ok(vec![VariantEvent::Created {
+            id,
+            toggle_id,
+            name,
+        }])
+    }
+
+    pub fn rename(&self, name: String) -> Result<Vec<VariantEvent>, VariantError> {
+        if name.trim().is_empty() {
+            return Err(VariantError::InvalidName { name });
+        }
+        self.transition(VariantEvent::Renamed { name })
+    }
+
+    pub fn retire(&self) -> Result<Vec<VariantEvent>, VariantError> {
+        self.transition(VariantEvent::Retired)
+    }
+
+    pub fn revive(&self) -> Result<Vec<VariantEvent>, VariantError> {
+        self.transition(VariantEvent::Revived)
+    }
+
+    fn transition(&self, event: VariantEvent) -> Result<Vec<VariantEvent>, VariantError> {
+        Self::apply_event(Some(self.clone()), &event)?;
+        Ok(vec![event])
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum VariantEvent {
+    Created {
+        id: VariantId,
+        toggle_id: ToggleId,
+        name: String,
+    },
+    Renamed {
+        name: String,
+    },
+    Retired,
+    Revived,
+}
+
+impl AggregateEvent for VariantEvent {
+    fn type_(&self) -> String {
+        match self {
+            VariantEvent::Created { .. } => "Created".to_owned(),
+            VariantEvent::Renamed { .. } => "Renamed".to_owned(),
+            VariantEvent::Retired => "Retired".to_owned(),
+            VariantEvent::Revived => "Revived".to_owned(),
+        }
+    }
+}
+
+impl Aggregate for Variant {
+    type Id = VariantId;
+    type Event = VariantEvent;
+    type Err = VariantError;
+    const TYPE: &'static str = "variant";
+    // Renamed held a bare name in version 1
+    const EVENT_VERSION: i32 = 2;
+
+    fn id(&self) -> &VariantId {
+        &self.id
+    }
+
+    fn generation(&self) -> Generation {
+        self.generation
+    }
+
+    fn apply_event(variant: Option<Self>, event: &VariantEvent) -> Result<Self, VariantError> {
+        match (&variant, event) {
+            (
+                None,
+                VariantEvent::Created {
+                    id,
+                    toggle_id,
+                    name,
+                },
+            ) => Ok(Variant {
+                id: *id,
+                generation: Generation::first(),
+                toggle_id: *toggle_id,
+                name: name.clone(),
+                retired: false,
+            }),
+            (Some(variant), VariantEvent::Renamed { name }) if !variant.retired => Ok(Variant {
+                generation: variant.generation.next(),
+                name: name.clone(),
+                ..variant.clone()
+            }),
+            (Some(variant), VariantEvent::Retired) if !variant.retired => Ok(Variant {
+                generation: variant.generation.next(),
+                retired: true,
+                ..variant.clone()
+            }),
+            (Some(variant), VariantEvent::Revived) if variant.retired => Ok(Variant {
+                generation: variant.generation.next(),
+                retired: false,
+                ..variant.clone()
+            }),
+            _ => Err(VariantError::InvalidStateEvent {
+                state: format!("{:?}", variant),
+                event: format!("{:?}", event),
+            }),
+        }
+    }
+}
+
+/// Variants by id, such as those Rules and prerequisites may refer to.
+pub type Variants = HashMap<VariantId, Variant>;
+
+/// The variant of a Toggle that a Rule or prerequisite refers to, as its
+/// id and current name. That is a live Variant of the Toggle, found by id
+/// once known and by name until then, or one of the built-in `on` and
+/// `off`, which have no id.
+pub fn resolve(
+    toggle_id: ToggleId,
+    id: Option<VariantId>,
+    name: &str,
+    variants: &Variants,
+) -> Option<(Option<VariantId>, String)> {
+    let live = |variant: &&Variant| variant.toggle_id == toggle_id && !variant.retired;
+    let variant = match id {
+        Some(id) => variants.get(&id).filter(live),
+        None if name == ON || name == OFF => return Some((None, name.to_owned())),
+        None => variants
+            .values()
+            .filter(live)
+            .find(|variant| variant.name == name),
+    };
+    variant.map(|variant| (Some(variant.id), variant.name.clone()))
+}
+
+/// Index of the names of each Toggle's Variants, used to
+/// keep names unique within a Toggle. Retired Variants keep
+/// their names so they can always be revived.
+pub trait VariantNames {
+    type Err;
+
+    fn find(&self, toggle_id: ToggleId, name: &str) -> Result<Option<VariantId>, Self::Err>;
+    fn list(&self, toggle_id: ToggleId) -> Result<Vec<VariantId>, Self::Err>;
+    fn save(&mut self, variant: &Variant) -> Result<(), Self::Err>;
+}
+
+pub struct DieselVariantNames<'a, C> {
+    pub db: &'a C,
+}
+
+fn parse_variant_id(id: String) -> Result<VariantId, diesel::result::Error> {
+    Uuid::parse_str(&id)
+        .map(VariantId)
+        .map_err(|e| diesel::result::Error::DeserializationError(Box::new(e)))
+}
+
+/// Implements the index of Variant names for a kind of diesel connection.
+macro_rules! impl_variant_names {
+    ($connection:ty) => {
+        impl<'a> VariantNames for DieselVariantNames<'a, $connection> {
+            type Err = diesel::result::Error;
+
+            fn find(
+                &self,
+                toggle_id: ToggleId,
+                name: &str,
+            ) -> Result<Option<VariantId>, Self::Err> {
+                use crate::database::schema::variant_names::dsl;
+
+                let id = dsl::variant_names
+                    .filter(dsl::toggle_id.eq(toggle_id.to_string()))
+                    .filter(dsl::name.eq(name))
+                    .select(dsl::id)
+                    .first::<String>(self.db)
+                    .optional()?;
+                id.map(parse_variant_id).transpose()
+            }
+
+            fn list(&self, toggle_id: ToggleId) -> Result<Vec<VariantId>, Self::Err> {
+                use crate::database::schema::variant_names::dsl;
+
+                dsl::variant_names
+                    .filter(dsl::toggle_id.eq(toggle_id.to_string()))
+                    .order(dsl::name.asc())
+                    .select(dsl::id)
+                    .load::<String>(self.db)?
+                    .into_iter()
+                    .map(parse_variant_id)
+                    .collect()
+            }
+
+            fn save(&mut self, variant: &Variant) -> Result<(), Self::Err> {
+                use crate::database::schema::variant_names::dsl;
+
+                diesel::delete(dsl::variant_names.find(variant.id.to_string())).execute(self.db)?;
+                diesel::insert_into(schema::variant_names::table)
+                    .values(&NewVariantName {
+                        id: &variant.id.to_string(),
+                        toggle_id: &variant.toggle_id.to_string(),
+                        name: &variant.name,
+                    })
+                    .execute(self.db)?;
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_variant_names!(SqliteConnection);
+#[cfg(feature = "postgres")]
+impl_variant_names!(diesel::pg::PgConnection);
+
+pub struct CreateVariant {
+    pub id: Uuid,
+    pub project_id: ProjectId,
+    pub toggle_id: ToggleId,
+    pub name: String,
+    pub metadata: Metadata,
+}
+
+pub struct CreateVariantHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+{
+    pub toggles: &'a T,
+    pub names: &'a mut N,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, TE, T, NE, N, E, R> CreateVariantHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+    CreateVariantHandlerError: From<TE> + From<NE> + From<E>,
+{
+    pub fn handle(&mut self, command: CreateVariant) -> Result<Variant, CreateVariantHandlerError> {
+        let toggle = self.toggles.get(command.toggle_id)?;
+        if toggle.project_id() != command.project_id {
+            return Err(CreateVariantHandlerError::NotFoundError);
+        }
+        if self.names.find(command.toggle_id, &command.name)?.is_some() {
+            return Err(VariantError::DuplicateName { name: command.name }.into());
+        }
+        let variant_id = VariantId(command.id);
+        let events = Variant::create(variant_id, command.toggle_id, command.name)?;
+        let variant = Variant::hydrate(&events)?.expect("Variant is not None");
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Variant>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: variant_id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(Generation::first(), &events)?;
+        self.names.save(&variant)?;
+        Ok(variant)
+    }
+}
+
+pub struct GetVariant {
+    pub project_id: ProjectId,
+    pub toggle_id: ToggleId,
+    pub id: VariantId,
+}
+
+pub struct GetVariantHandler<'a, TE, T, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+{
+    pub toggles: &'a T,
+    pub repository: &'a R,
+}
+
+impl<'a, TE, T, E, R> GetVariantHandler<'a, TE, T, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+    GetVariantHandlerError: From<TE> + From<E>,
+{
+    pub fn handle(&self, command: GetVariant) -> Result<Variant, GetVariantHandlerError> {
+        let toggle = self.toggles.get(command.toggle_id)?;
+        let variant = self.repository.get(command.id)?;
+        if toggle.project_id() != command.project_id || variant.toggle_id != command.toggle_id {
+            return Err(GetVariantHandlerError::NotFoundError);
+        }
+        Ok(variant)
+    }
+}
+
+pub struct ListVariants {
+    pub project_id: ProjectId,
+    pub toggle_id: ToggleId,
+}
+
+pub struct ListVariantsHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+{
+    pub toggles: &'a T,
+    pub names: &'a N,
+    pub repository: &'a R,
+}
+
+impl<'a, TE, T, NE, N, E, R> ListVariantsHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+    ListVariantsHandlerError: From<TE> + From<NE> + From<E>,
+{
+    pub fn handle(&self, command: ListVariants) -> Result<Vec<Variant>, ListVariantsHandlerError> {
+        let toggle = self.toggles.get(command.toggle_id)?;
+        if toggle.project_id() != command.project_id {
+            return Err(ListVariantsHandlerError::NotFoundError);
+        }
+        let mut variants = vec![];
+        for id in self.names.list(command.toggle_id)? {
+            variants.push(self.repository.get(id)?);
+        }
+        Ok(variants)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VariantAction {
+    Rename(String),
+    Retire,
+    Revive,
+}
+
+pub struct UpdateVariant {
+    pub project_id: ProjectId,
+    pub toggle_id: ToggleId,
+    pub id: VariantId,
+    pub action: VariantAction,
+    pub expected_generation: Option<Generation>,
+    pub metadata: Metadata,
+}
+
+pub struct UpdateVariantHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+{
+    pub toggles: &'a T,
+    pub names: &'a mut N,
+    pub repository: &'a mut R,
+    pub utc_now: fn() -> DateTime<Utc>,
+}
+
+impl<'a, TE, T, NE, N, E, R> UpdateVariantHandler<'a, TE, T, NE, N, E, R>
+where
+    T: Repository<Aggregate = Toggle, Err = TE>,
+    N: VariantNames<Err = NE>,
+    R: Repository<Aggregate = Variant, Err = E>,
+    UpdateVariantHandlerError: From<TE> + From<NE> + From<E>,
+{
+    pub fn handle(&mut self, command: UpdateVariant) -> Result<Variant, UpdateVariantHandlerError> {
+        let toggle = self.toggles.get(command.toggle_id)?;
+        let mut variant = self.repository.get(command.id)?;
+        if toggle.project_id() != command.project_id || variant.toggle_id != command.toggle_id {
+            return Err(UpdateVariantHandlerError::NotFoundError);
+        }
+        if let Some(expected) = command.expected_generation {
+            if expected != variant.generation {
+                return Err(UpdateVariantHandlerError::ConcurrencyConflict {
+                    expected,
+                    actual: variant.generation,
+                });
+            }
+        }
+        let events = match command.action {
+            VariantAction::Rename(name) => match self.names.find(variant.toggle_id, &name)? {
+                Some(id) if id != variant.id => {
+                    return Err(VariantError::DuplicateName { name }.into());
+                }
+                _ => variant.rename(name)?,
+            },
+            VariantAction::Retire => variant.retire()?,
+            VariantAction::Revive => variant.revive()?,
+        };
+        let generation = variant.generation.next();
+        for event in &events {
+            variant = Variant::apply_event(Some(variant), event)?;
+        }
+        let metadata = command.metadata;
+        let events: Vec<DomainEvent<Variant>> = events
+            .into_iter()
+            .map(|event| DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: variant.id,
+                created_at: (self.utc_now)(),
+                metadata: metadata.clone(),
+                event,
+            })
+            .collect();
+        self.repository.persist(generation, &events)?;
+        self.names.save(&variant)?;
+        Ok(variant)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::offset::TimeZone;
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use diesel::sqlite::SqliteConnection;
+    use failure::Error;
+    use uuid::Uuid;
+
+    use crate::domain::{Aggregate, DomainEvent, DomainEventId, Generation, Metadata, Repository};
+    use crate::event_store::DieselEventStore;
+    use crate::project::ProjectId;
+    use crate::toggle::{Event, Toggle, ToggleId};
+
+    use super::error::{CreateVariantHandlerError, UpdateVariantHandlerError};
+    use super::{
+        CreateVariant, CreateVariantHandler, DieselVariantNames, UpdateVariant,
+        UpdateVariantHandler, Variant, VariantAction, VariantError, VariantEvent, VariantId,
+    };
+
+    fn created() -> Result<Variant, Error> {
+        let id = VariantId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let toggle_id = ToggleId::from(Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000")?);
+        let events = Variant::create(id, toggle_id, "blue".to_owned())?;
+        Ok(Variant::hydrate(&events)?.expect("Variant is not None"))
+    }
+
+    #[test]
+    fn test_create_empty_name() -> Result<(), Error> {
+        let id = VariantId(Uuid::parse_str("936DA01F9ABD4d9d80C702AF85C822A8")?);
+        let toggle_id = ToggleId::from(Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000")?);
+        assert_eq!(
+            Variant::create(id, toggle_id, "".to_owned()),
+            Err(VariantError::InvalidName {
+                name: "".to_owned()
+            }),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename_retired() -> Result<(), Error> {
+        let variant = Variant::apply_event(Some(created()?), &VariantEvent::Retired)?;
+        match variant.rename("green".to_owned()) {
+            Err(VariantError::InvalidStateEvent { .. }) => Ok(()),
+            result => panic!("expected invalid state event, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_revive() -> Result<(), Error> {
+        let variant = created()?;
+        assert!(variant.revive().is_err());
+        let variant = Variant::apply_event(Some(variant), &VariantEvent::Retired)?;
+        assert_eq!(variant.revive()?, vec![VariantEvent::Revived]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_names() -> Result<(), Error> {
+        let db = &SqliteConnection::establish(":memory:")?;
+        diesel_migrations::run_pending_migrations(db)?;
+        let project_id: ProjectId = "550e8400-e29b-41d4-a716-446655440000".parse()?;
+        let toggle_id = ToggleId::from(Uuid::new_v4());
+        let toggles = &mut DieselEventStore::<_, Toggle>::new(db);
+        toggles.persist(
+            Generation::first(),
+            &[DomainEvent {
+                id: DomainEventId::new(Uuid::new_v4()),
+                aggregate_id: toggle_id,
+                created_at: Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+                metadata: Metadata::default(),
+                event: Event::Created {
+                    id: toggle_id,
+                    project_id,
+                    name: "test".to_owned(),
+                },
+            }],
+        )?;
+        let names = &mut DieselVariantNames { db };
+        let repository = &mut DieselEventStore::<_, Variant>::new(db);
+
+        let mut create = CreateVariantHandler {
+            toggles,
+            names,
+            repository,
+            utc_now: Utc::now,
+        };
+        let blue = create.handle(CreateVariant {
+            id: Uuid::new_v4(),
+            project_id,
+            toggle_id,
+            name: "blue".to_owned(),
+            metadata: Metadata::default(),
+        })?;
+        let green = create.handle(CreateVariant {
+            id: Uuid::new_v4(),
+            project_id,
+            toggle_id,
+            name: "green".to_owned(),
+            metadata: Metadata::default(),
+        })?;
+        match create.handle(CreateVariant {
+            id: Uuid::new_v4(),
+            project_id,
+            toggle_id,
+            name: "blue".to_owned(),
+            metadata: Metadata::default(),
+        }) {
+            Err(CreateVariantHandlerError::VariantError(VariantError::DuplicateName {
+                ..
+            })) => {}
+            result => panic!("expected duplicate name, got {:?}", result),
+        }
+
+        let mut update = UpdateVariantHandler {
+            toggles,
+            names,
+            repository,
+            utc_now: Utc::now,
+        };
+        match update.handle(UpdateVariant {
+            project_id,
+            toggle_id,
+            id: green.id,
+            action: VariantAction::Rename("blue".to_owned()),
+            expected_generation: None,
+            metadata: Metadata::default(),
+        }) {
+            Err(UpdateVariantHandlerError::VariantError(VariantError::DuplicateName {
+                ..
+            })) => {}
+            result => panic!("expected duplicate name, got {:?}", result),
+        }
+        let blue = update.handle(UpdateVariant {
+            project_id,
+            toggle_id,
+            id: blue.id,
+            action: VariantAction::Rename("red".to_owned()),
+            expected_generation: Some(Generation::first()),
+            metadata: Metadata::default(),
+        })?;
+        assert_eq!(blue.name, "red");
+        update.handle(UpdateVariant {
+            project_id,
+            toggle_id,
+            id: green.id,
+            action: VariantAction::Rename("blue".to_owned()),
+            expected_generation: None,
+            metadata: Metadata::default(),
+        })?;
+        Ok(())
+    }
+}